@@ -26,7 +26,7 @@ import { ThunderboltOutlined } from '@ant-design/icons';
 import { ParamsType } from '@ant-design/pro-components';
 import { ActionType, ProColumns } from '@ant-design/pro-table';
 import { FormattedMessage, useModel } from '@umijs/max';
-import { Button, Modal } from 'antd';
+import { Badge, Button, Modal, Tooltip } from 'antd';
 import { SortOrder } from 'antd/es/table/interface';
 import { Key, useRef, useState } from 'react';
 import PassiveUpdate from './passiveUpdateBtn';
@@ -104,6 +104,43 @@ export const PassiveAgentColumns: Columns = (intl) => [
       rules: [{ required: true }],
     },
   },
+  {
+    title: intl.get('tables.retrymaxtime'),
+    dataIndex: 'retry_max_time',
+    valueType: 'digit',
+    tooltip: intl.get('tables.retrymaxtime.tip'),
+    initialValue: 0,
+  },
+  {
+    title: intl.get('tables.retrymultiplier'),
+    dataIndex: 'retry_multiplier',
+    valueType: 'digit',
+    initialValue: 1,
+    fieldProps: {
+      min: 1,
+      max: 100,
+      step: 0.1,
+    },
+  },
+  {
+    title: intl.get('tables.retryjitter'),
+    dataIndex: 'retry_jitter',
+    valueType: 'digit',
+    tooltip: intl.get('tables.retryjitter.tip'),
+    initialValue: 0,
+    fieldProps: {
+      min: 0,
+      max: 1,
+      step: 0.1,
+    },
+  },
+  {
+    title: intl.get('tables.retrylimit'),
+    dataIndex: 'retry_limit',
+    valueType: 'digit',
+    tooltip: intl.get('tables.retrylimit.tip'),
+    initialValue: 0,
+  },
 ];
 
 const PassiveAgent = () => {
@@ -120,6 +157,14 @@ const PassiveAgent = () => {
       label: intl.get('tables.status.active'),
       color: 'success',
     },
+    2: {
+      label: intl.get('tables.status.waiting'),
+      color: 'warning',
+    },
+    3: {
+      label: intl.get('tables.status.failed'),
+      color: 'error',
+    },
   };
   const columns: ProColumns[] = [
     IDColumn(intl),
@@ -148,6 +193,34 @@ const PassiveAgent = () => {
       tooltip: intl.get('tables.retrytime.tip'),
     },
     StatusColumn(intl.get('tables.status'), 'status', statusEnum),
+    {
+      title: intl.get('tables.agent'),
+      dataIndex: 'agent_name',
+      align: 'center',
+      render: (_, row) =>
+        row.aid ? (
+          <Tooltip title={row.aid}>
+            <Badge
+              status={row.agent_status == 1 ? 'success' : 'default'}
+              text={row.agent_name ?? row.aid.substring(0, 8)}
+            />
+          </Tooltip>
+        ) : (
+          '-'
+        ),
+    },
+    {
+      title: intl.get('tables.failures'),
+      dataIndex: 'failures',
+      align: 'center',
+      tooltip: intl.get('tables.failures.tip'),
+      render: (_, row) =>
+        row.last_error ? (
+          <Tooltip title={row.last_error}>{row.failures}</Tooltip>
+        ) : (
+          row.failures
+        ),
+    },
     ...CreatedAtColumn(intl),
     {
       title: intl.get('app.op'),
@@ -171,7 +244,7 @@ const PassiveAgent = () => {
             perm={UserPerm.PermWrite}
             permName={`manage.${PLUGIN_ID}`}
             onClick={() => handleActivate(row.id)}
-            disabled={row.status == 1}
+            disabled={row.status == 1 || row.status == 2}
           />,
           <TableDelete
             key="delete"
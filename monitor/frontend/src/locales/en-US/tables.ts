@@ -3,6 +3,18 @@ export default {
   'tables.status': 'Status',
   'tables.status.inactive': 'Inactive',
   'tables.status.active': 'Active',
+  'tables.status.waiting': 'Waiting',
+  'tables.status.failed': 'Failed',
+  'tables.retrymaxtime': 'Max Retry Time',
+  'tables.retrymaxtime.tip': 'No limit when set to 0',
+  'tables.retrymultiplier': 'Retry Multiplier',
+  'tables.retryjitter': 'Retry Jitter',
+  'tables.retryjitter.tip': 'Random ratio applied to retry time',
+  'tables.retrylimit': 'Retry Limit',
+  'tables.retrylimit.tip': 'Max consecutive failures, no limit when set to 0',
+  'tables.agent': 'Agent',
+  'tables.failures': 'Failures',
+  'tables.failures.tip': 'Consecutive failures, hover to see the last error',
   'tables.address': 'Address',
   'tables.retrytime': 'Retry Time',
   'tables.retrytime.tip': 'Disable when set to 0',
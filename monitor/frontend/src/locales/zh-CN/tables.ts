@@ -3,6 +3,18 @@ export default {
   'tables.status': '状态',
   'tables.status.inactive': '不活跃',
   'tables.status.active': '活跃',
+  'tables.status.waiting': '等待',
+  'tables.status.failed': '失败',
+  'tables.retrymaxtime': '最大重试时间',
+  'tables.retrymaxtime.tip': '当值设为0时不限制',
+  'tables.retrymultiplier': '重试倍率',
+  'tables.retryjitter': '重试抖动',
+  'tables.retryjitter.tip': '重试时间的随机比例',
+  'tables.retrylimit': '重试次数',
+  'tables.retrylimit.tip': '最大连续失败次数，当值设为0时不限制',
+  'tables.agent': '客户端',
+  'tables.failures': '失败次数',
+  'tables.failures.tip': '连续失败次数，悬停查看最后错误',
   'tables.address': '地址',
   'tables.retrytime': '重试时间',
   'tables.retrytime.tip': '当值设为0时禁用',
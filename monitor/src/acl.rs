@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use parking_lot::RwLock;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use skynet_api::{
+    HyUuid, Result,
+    permission::{PERM_READ, PermEntry, PermissionItem},
+    sea_orm::ConnectionTrait,
+    viewer::{groups::GroupViewer, permissions::PermissionViewer},
+};
+use skynet_api_monitor::entity::acls;
+use validator::ValidationError;
+
+use crate::PLUGIN_INSTANCE;
+
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum AclRight {
+    View = 0,
+    Shell,
+    Exec,
+    File,
+    Forward,
+}
+
+impl AclRight {
+    const ALL: [Self; 5] = [
+        Self::View,
+        Self::Shell,
+        Self::Exec,
+        Self::File,
+        Self::Forward,
+    ];
+
+    const fn mask(self) -> i32 {
+        1 << self as u8
+    }
+
+    /// Encode `rights` to bit mask.
+    pub fn encode(rights: &[Self]) -> i32 {
+        rights.iter().fold(0, |acc, x| acc | x.mask())
+    }
+
+    /// Decode bit mask `perm` to rights.
+    pub fn decode(perm: i32) -> Vec<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|x| perm & x.mask() != 0)
+            .collect()
+    }
+}
+
+/// # Errors
+/// Will return `Err` when `x` is empty.
+pub fn rights_validator(x: &[AclRight]) -> Result<(), ValidationError> {
+    if x.is_empty() {
+        Err(ValidationError::new("empty rights"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Per-agent access control for users without the manager permission.
+#[derive(Default)]
+pub struct Acl {
+    enable: RwLock<bool>,
+    entry: RwLock<Vec<acls::Model>>,
+}
+
+impl Acl {
+    pub fn set_enable(&self, enable: bool) {
+        *self.enable.write() = enable;
+    }
+
+    /// Replace cached entries with `entry`.
+    pub fn load(&self, entry: Vec<acls::Model>) {
+        *self.entry.write() = entry;
+    }
+
+    /// Whether `perm` contains the monitor manager permission.
+    pub fn is_manager(perm: &HashMap<HyUuid, PermissionItem>) -> bool {
+        PLUGIN_INSTANCE.manage_id.get().is_some_and(|x| {
+            PermEntry {
+                pid: *x,
+                perm: PERM_READ,
+            }
+            .check(perm)
+        })
+    }
+
+    /// Load current permissions of user `uid`, used for checks outside of requests.
+    ///
+    /// Group permissions are merged, user permissions take precedence.
+    pub async fn user_perm<C>(db: &C, uid: &HyUuid) -> Result<HashMap<HyUuid, PermissionItem>>
+    where
+        C: ConnectionTrait,
+    {
+        let mut ret: HashMap<HyUuid, PermissionItem> = HashMap::new();
+        for i in GroupViewer::find_user_group(db, uid, false).await? {
+            for x in PermissionViewer::find_group(db, &i.id).await? {
+                if let Some(v) = ret.get_mut(&x.pid) {
+                    v.perm |= x.perm;
+                } else {
+                    ret.insert(x.pid, x);
+                }
+            }
+        }
+        for x in PermissionViewer::find_user(db, uid).await? {
+            ret.insert(x.pid, x);
+        }
+        Ok(ret)
+    }
+
+    /// Whether `perm` bypasses all checks, true when disabled or `perm` contains the manager permission.
+    pub fn unrestricted(&self, perm: &HashMap<HyUuid, PermissionItem>) -> bool {
+        !*self.enable.read() || Self::is_manager(perm)
+    }
+
+    /// Whether user `uid` with `perm` has `right` on agent `aid`, `labels` gets labels of the agent.
+    ///
+    /// Always true when `perm` is unrestricted.
+    pub fn check<F>(
+        &self,
+        uid: Option<HyUuid>,
+        perm: &HashMap<HyUuid, PermissionItem>,
+        aid: &HyUuid,
+        right: AclRight,
+        labels: F,
+    ) -> bool
+    where
+        F: FnOnce() -> Vec<String>,
+    {
+        if self.unrestricted(perm) {
+            return true;
+        }
+        let Some(uid) = uid else {
+            return false;
+        };
+        let labels = labels();
+        self.entry.read().iter().any(|x| {
+            x.uid == uid
+                && x.perm & right.mask() != 0
+                && (x.aid == Some(*aid) || x.label.as_ref().is_some_and(|x| labels.contains(x)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        uid: HyUuid,
+        aid: Option<HyUuid>,
+        label: Option<&str>,
+        rights: &[AclRight],
+    ) -> acls::Model {
+        acls::Model {
+            uid,
+            aid,
+            label: label.map(ToOwned::to_owned),
+            perm: AclRight::encode(rights),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn encode() {
+        let rights = [AclRight::Shell, AclRight::Forward];
+        assert_eq!(AclRight::encode(&rights), 0b10010);
+        assert_eq!(AclRight::decode(AclRight::encode(&rights)), rights);
+        assert!(AclRight::decode(0).is_empty());
+    }
+
+    #[test]
+    fn check() {
+        let (uid, other) = (HyUuid::new(), HyUuid::new());
+        let (a, b, c) = (HyUuid::new(), HyUuid::new(), HyUuid::new());
+        let labels = HashMap::from([(b, vec![String::from("web")])]);
+        let acl = Acl::default();
+        let perm = HashMap::new();
+        let check = |uid, aid, right| {
+            acl.check(uid, &perm, aid, right, || {
+                labels.get(aid).cloned().unwrap_or_default()
+            })
+        };
+        // Disabled ACL allows everyone.
+        assert!(check(None, &a, AclRight::Exec));
+
+        acl.set_enable(true);
+        acl.load(vec![
+            entry(uid, Some(a), None, &[AclRight::View, AclRight::Shell]),
+            entry(uid, None, Some("web"), &[AclRight::Exec]),
+        ]);
+        assert!(!check(None, &a, AclRight::View));
+        assert!(check(Some(uid), &a, AclRight::Shell));
+        assert!(!check(Some(uid), &a, AclRight::Exec));
+        // Granted through the agent label.
+        assert!(check(Some(uid), &b, AclRight::Exec));
+        assert!(!check(Some(uid), &b, AclRight::View));
+        assert!(!check(Some(uid), &c, AclRight::Exec));
+        assert!(!check(Some(other), &a, AclRight::View));
+    }
+}
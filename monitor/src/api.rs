@@ -1,10 +1,17 @@
 use core::str;
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
 use actix_cloud::{
     actix_web::{HttpResponse, web::Path},
+    chrono::Utc,
     response::{JsonResponse, RspResult},
-    tokio::{spawn, time::sleep},
+    tokio::{fs, spawn, time::sleep},
     tracing::{error, info},
 };
 use actix_web_validator::{Json, QsQuery};
@@ -12,23 +19,52 @@ use base64::{Engine, engine::general_purpose::STANDARD};
 use ecies::{PublicKey, utils::generate_keypair};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serde_repr::Serialize_repr;
 use skynet_api::{
-    HyUuid, Result, finish,
+    HyUuid, Result, anyhow, finish,
     request::{
-        Condition, IDsReq, IntoExpr, PageData, PaginationParam, TimeParam, unique_validator,
+        Condition, IDsReq, IntoExpr, PageData, PaginationParam, Request, TimeParam,
+        unique_validator,
     },
     sea_orm::{ColumnTrait, IntoSimpleExpr, TransactionTrait},
 };
 use skynet_api_monitor::{
-    AgentStatus, ReconnectMessage,
-    entity::passive_agents,
-    viewer::{agents::AgentViewer, passive_agents::PassiveAgentViewer},
+    AgentCommand, AgentStatus, CommandOption, ReconnectMessage,
+    entity::{
+        acls,
+        jobs::{self, JobKind, JobStatus},
+        passive_agents,
+        schedule_runs::{self, RunStatus},
+        schedules, scripts, shell_recordings, shell_sessions, socks_users,
+    },
+    viewer::{
+        acls::AclViewer, agents::AgentViewer, jobs::JobViewer, passive_agents::PassiveAgentViewer,
+        schedule_runs::ScheduleRunViewer, schedules::ScheduleViewer, scripts::ScriptViewer,
+        shell_recordings::ShellRecordingViewer, shell_sessions::ShellSessionViewer,
+        socks_users::SocksUserViewer,
+    },
 };
 use skynet_macro::common_req;
 use validator::Validate;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
 
-use crate::{MonitorResponse, PLUGIN_INSTANCE, Plugin};
+use crate::{
+    MonitorResponse, PLUGIN_INSTANCE, Plugin,
+    acl::{self, Acl, AclRight},
+    batch::{self, BatchJob, BatchResult, BatchSummary, Target, Task},
+    firewall::{FirewallCounter, FirewallSetting},
+    limit,
+    migrate::{MigrateJob, MigrateSummary},
+    proxy,
+    recording::{Cast, RecordMode},
+    scheduler::{self, Scheduler},
+    script::{self, ScriptParam},
+    server::PassiveState,
+    session::ShellLimit,
+    socks::{self, Password, SocksSetting},
+    tunnel::TunnelInfo,
+    utils::utf8_boundary,
+    ws::ShellService,
+};
 
 #[derive(Debug, Validate, Deserialize)]
 pub struct GetAgentsReq {
@@ -41,11 +77,29 @@ pub struct GetAgentsReq {
     page: PaginationParam,
 }
 
-pub async fn get_agents(param: QsQuery<GetAgentsReq>) -> RspResult<JsonResponse> {
+/// Whether user of `req` has `right` on agent `aid`.
+fn acl_check(req: &Request, aid: &HyUuid, right: AclRight) -> bool {
+    PLUGIN_INSTANCE
+        .acl
+        .check(req.uid, &req.perm, aid, right, || {
+            PLUGIN_INSTANCE.agent_labels(aid)
+        })
+}
+
+/// Whether `req` has `right` on all agents matched by `target`.
+fn acl_check_target(req: &Request, target: &Target, right: AclRight) -> bool {
+    target.resolve().iter().all(|x| acl_check(req, x, right))
+}
+
+#[allow(clippy::collapsible_if)]
+pub async fn get_agents(req: Request, param: QsQuery<GetAgentsReq>) -> RspResult<JsonResponse> {
     let data: Vec<serde_json::Value> = PLUGIN_INSTANCE
         .agent
         .iter()
         .filter(|v| {
+            if !acl_check(&req, &v.id, AclRight::View) {
+                return false;
+            }
             if let Some(x) = &param.status {
                 if !x.contains(&v.status) {
                     return false;
@@ -82,19 +136,24 @@ pub struct GetPassiveAgentsReq {
 }
 
 pub async fn get_passive_agents(param: QsQuery<GetPassiveAgentsReq>) -> RspResult<JsonResponse> {
-    #[derive(Serialize_repr)]
-    #[repr(u8)]
-    enum Status {
-        Inactive = 0,
-        Active,
-    }
     #[derive(Serialize)]
     struct Rsp {
         id: HyUuid,
         name: String,
-        status: Status,
         address: String,
         retry_time: i32,
+        retry_max_time: i32,
+        retry_multiplier: f64,
+        retry_jitter: f64,
+        retry_limit: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aid: Option<HyUuid>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        agent_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        agent_status: Option<AgentStatus>,
+        #[serde(flatten)]
+        state: PassiveState,
         created_at: i64,
         updated_at: i64,
     }
@@ -104,24 +163,31 @@ pub async fn get_passive_agents(param: QsQuery<GetPassiveAgentsReq>) -> RspResul
             Condition::any()
                 .add(text.like_expr(passive_agents::Column::Id))
                 .add(text.like_expr(passive_agents::Column::Name))
-                .add(text.like_expr(passive_agents::Column::Address)),
+                .add(text.like_expr(passive_agents::Column::Address))
+                .add(text.like_expr(passive_agents::Column::Aid)),
         );
     }
     let data = PassiveAgentViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
-    let agent = PLUGIN_INSTANCE.server.connecting();
     let data = (
         data.0
             .into_iter()
             .map(|x| Rsp {
                 id: x.id,
                 name: x.name,
-                status: if agent.contains(&x.id) {
-                    Status::Active
-                } else {
-                    Status::Inactive
-                },
                 address: x.address,
                 retry_time: x.retry_time,
+                retry_max_time: x.retry_max_time,
+                retry_multiplier: x.retry_multiplier,
+                retry_jitter: x.retry_jitter,
+                retry_limit: x.retry_limit,
+                aid: x.aid,
+                agent_name: x
+                    .aid
+                    .and_then(|aid| PLUGIN_INSTANCE.agent.get(&aid).map(|x| x.name.clone())),
+                agent_status: x
+                    .aid
+                    .and_then(|aid| PLUGIN_INSTANCE.agent.get(&aid).map(|x| x.status)),
+                state: PLUGIN_INSTANCE.server.passive_state(&x.id),
                 created_at: x.created_at,
                 updated_at: x.updated_at,
             })
@@ -140,6 +206,22 @@ pub struct AddPassiveAgentsReq {
     pub address: String,
     #[validate(range(min = 0))]
     pub retry_time: i32,
+    #[validate(range(min = 0))]
+    #[serde(default)]
+    pub retry_max_time: i32,
+    #[validate(range(min = 1.0, max = 100.0))]
+    #[serde(default = "default_multiplier")]
+    pub retry_multiplier: f64,
+    #[validate(range(min = 0.0, max = 1.0))]
+    #[serde(default)]
+    pub retry_jitter: f64,
+    #[validate(range(min = 0))]
+    #[serde(default)]
+    pub retry_limit: i32,
+}
+
+const fn default_multiplier() -> f64 {
+    1.0
 }
 
 pub async fn add_passive_agents(param: Json<AddPassiveAgentsReq>) -> RspResult<JsonResponse> {
@@ -157,6 +239,15 @@ pub async fn add_passive_agents(param: Json<AddPassiveAgentsReq>) -> RspResult<J
         finish!(JsonResponse::new(MonitorResponse::PassiveAgentAddressExist));
     }
     let m = PassiveAgentViewer::create(&tx, &param.name, &param.address, param.retry_time).await?;
+    PassiveAgentViewer::update_backoff(
+        &tx,
+        &m.id,
+        Some(param.retry_max_time),
+        Some(param.retry_multiplier),
+        Some(param.retry_jitter),
+        Some(param.retry_limit),
+    )
+    .await?;
     tx.commit().await?;
     PLUGIN_INSTANCE.server.connect(&m.id);
 
@@ -165,6 +256,10 @@ pub async fn add_passive_agents(param: Json<AddPassiveAgentsReq>) -> RspResult<J
         name = param.name,
         address = param.address,
         retry_time = param.retry_time,
+        retry_max_time = param.retry_max_time,
+        retry_multiplier = param.retry_multiplier,
+        retry_jitter = param.retry_jitter,
+        retry_limit = param.retry_limit,
         "Add passive agent",
     );
     finish!(JsonResponse::new(MonitorResponse::Success).json(m.id));
@@ -178,6 +273,14 @@ pub struct PutPassiveAgentsReq {
     pub address: Option<String>,
     #[validate(range(min = 0))]
     pub retry_time: Option<i32>,
+    #[validate(range(min = 0))]
+    pub retry_max_time: Option<i32>,
+    #[validate(range(min = 1.0, max = 100.0))]
+    pub retry_multiplier: Option<f64>,
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub retry_jitter: Option<f64>,
+    #[validate(range(min = 0))]
+    pub retry_limit: Option<i32>,
 }
 
 pub async fn put_passive_agents(
@@ -196,6 +299,15 @@ pub async fn put_passive_agents(
         param.retry_time,
     )
     .await?;
+    PassiveAgentViewer::update_backoff(
+        &tx,
+        &paid,
+        param.retry_max_time,
+        param.retry_multiplier,
+        param.retry_jitter,
+        param.retry_limit,
+    )
+    .await?;
     tx.commit().await?;
 
     info!(
@@ -204,6 +316,10 @@ pub async fn put_passive_agents(
         name = ?param.name,
         address = ?param.address,
         retry_time = ?param.retry_time,
+        retry_max_time = ?param.retry_max_time,
+        retry_multiplier = ?param.retry_multiplier,
+        retry_jitter = ?param.retry_jitter,
+        retry_limit = ?param.retry_limit,
         "Put passive agent",
     );
     finish!(JsonResponse::new(MonitorResponse::Success))
@@ -256,6 +372,17 @@ pub async fn get_settings() -> RspResult<JsonResponse> {
         address: String,
         msg_timeout: u32,
         alert_timeout: u32,
+        proxy_protocol: bool,
+        proxy_trusted: Vec<String>,
+        firewall: FirewallSetting,
+        firewall_counter: FirewallCounter,
+        job_retention: u32,
+        command_group_limit: HashMap<String, u32>,
+        shell_record: bool,
+        shell_grace: u32,
+        shell_limit: ShellLimit,
+        acl: bool,
+        socks: SocksSetting,
     }
 
     let db = PLUGIN_INSTANCE.db.get().unwrap();
@@ -270,18 +397,63 @@ pub async fn get_settings() -> RspResult<JsonResponse> {
             alert_timeout: Plugin::get_setting_alert_timeout(db)
                 .await?
                 .unwrap_or_default(),
+            proxy_protocol: Plugin::get_setting_proxy_protocol(db)
+                .await?
+                .unwrap_or_default(),
+            proxy_trusted: Plugin::get_setting_proxy_trusted(db)
+                .await?
+                .unwrap_or_default(),
+            firewall: Plugin::get_setting_firewall(db).await?.unwrap_or_default(),
+            firewall_counter: PLUGIN_INSTANCE.firewall.counter(),
+            job_retention: Plugin::get_setting_job_retention(db)
+                .await?
+                .unwrap_or_default(),
+            command_group_limit: Plugin::get_setting_command_group_limit(db)
+                .await?
+                .unwrap_or_default(),
+            shell_record: Plugin::get_setting_shell_record(db)
+                .await?
+                .unwrap_or_default(),
+            shell_grace: Plugin::get_setting_shell_grace(db)
+                .await?
+                .unwrap_or_default(),
+            shell_limit: Plugin::get_setting_shell_limit(db)
+                .await?
+                .unwrap_or_default(),
+            acl: Plugin::get_setting_acl(db).await?.unwrap_or_default(),
+            socks: Plugin::get_setting_socks(db).await?.unwrap_or_default(),
         })
     );
 }
 
-pub async fn get_settings_shell() -> RspResult<JsonResponse> {
-    finish!(
-        JsonResponse::new(MonitorResponse::Success).json(
-            Plugin::get_setting_shell(PLUGIN_INSTANCE.db.get().unwrap())
-                .await?
-                .unwrap_or_default()
-        )
-    );
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetSettingsShellReq {
+    pub aid: Option<HyUuid>,
+}
+
+pub async fn get_settings_shell(
+    req: Request,
+    param: QsQuery<GetSettingsShellReq>,
+) -> RspResult<JsonResponse> {
+    let shell = if let Some(aid) = &param.aid {
+        if PLUGIN_INSTANCE.agent.get(aid).is_none() {
+            finish!(JsonResponse::not_found());
+        }
+        if !acl_check(&req, aid, AclRight::Shell) {
+            finish!(JsonResponse::new_code(403));
+        }
+        PLUGIN_INSTANCE.agent_shell(aid)
+    } else {
+        PLUGIN_INSTANCE.shell_prog.read().clone()
+    };
+    finish!(JsonResponse::new(MonitorResponse::Success).json(shell));
+}
+
+pub async fn unban_settings_firewall() -> RspResult<JsonResponse> {
+    PLUGIN_INSTANCE.firewall.unban();
+
+    info!(success = true, "Unban monitor firewall");
+    finish!(JsonResponse::new(MonitorResponse::Success))
 }
 
 pub async fn get_settings_certificate() -> RspResult<HttpResponse> {
@@ -366,12 +538,37 @@ pub struct PutSettingsReq {
     pub address: Option<String>,
     pub msg_timeout: Option<u32>,
     pub alert_timeout: Option<u32>,
+    pub proxy_protocol: Option<bool>,
+    #[validate(
+        custom(function = "unique_validator"),
+        custom(function = "proxy::cidr_validator")
+    )]
+    pub proxy_trusted: Option<Vec<String>>,
+    #[validate(nested)]
+    pub firewall: Option<FirewallSetting>,
+    pub job_retention: Option<u32>,
+    /// Max concurrent commands of all agents having each label, 0 for unlimited.
+    #[validate(custom(function = "limit::group_limit_validator"))]
+    pub command_group_limit: Option<HashMap<String, u32>>,
+    pub shell_record: Option<bool>,
+    /// Seconds to keep shells after the frontend is gone, 0 to close immediately.
+    #[validate(range(max = 86400))]
+    pub shell_grace: Option<u32>,
+    #[validate(nested)]
+    pub shell_limit: Option<ShellLimit>,
+    pub acl: Option<bool>,
+    #[validate(nested)]
+    pub socks: Option<SocksSetting>,
 }
 
 pub async fn put_settings(param: Json<PutSettingsReq>) -> RspResult<JsonResponse> {
     let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if let Some(x) = &param.socks {
+        Plugin::set_setting_socks(&tx, x).await?;
+    }
     if let Some(x) = &param.shell {
         Plugin::set_setting_shell(&tx, x).await?;
+        *PLUGIN_INSTANCE.shell_prog.write() = x.clone();
     }
     if let Some(x) = &param.address {
         Plugin::set_setting_address(&tx, x).await?;
@@ -384,21 +581,251 @@ pub async fn put_settings(param: Json<PutSettingsReq>) -> RspResult<JsonResponse
         Plugin::set_setting_alert_timeout(&tx, *x).await?;
         *PLUGIN_INSTANCE.alert_timeout.write() = *x;
     }
+    if let Some(x) = &param.proxy_protocol {
+        Plugin::set_setting_proxy_protocol(&tx, *x).await?;
+        *PLUGIN_INSTANCE.proxy_protocol.write() = *x;
+    }
+    if let Some(x) = &param.proxy_trusted {
+        Plugin::set_setting_proxy_trusted(&tx, x).await?;
+        *PLUGIN_INSTANCE.proxy_trusted.write() =
+            x.iter().filter_map(|x| proxy::parse_cidr(x)).collect();
+    }
+    if let Some(x) = &param.firewall {
+        Plugin::set_setting_firewall(&tx, x).await?;
+        PLUGIN_INSTANCE.firewall.set(x.to_owned());
+    }
+    if let Some(x) = &param.job_retention {
+        Plugin::set_setting_job_retention(&tx, *x).await?;
+        *PLUGIN_INSTANCE.job_retention.write() = *x;
+    }
+    if let Some(x) = &param.command_group_limit {
+        Plugin::set_setting_command_group_limit(&tx, x).await?;
+    }
+    if let Some(x) = &param.shell_record {
+        Plugin::set_setting_shell_record(&tx, *x).await?;
+        PLUGIN_INSTANCE.recording.set_enable(*x);
+    }
+    if let Some(x) = &param.shell_grace {
+        Plugin::set_setting_shell_grace(&tx, *x).await?;
+        *PLUGIN_INSTANCE.shell_grace.write() = *x;
+    }
+    if let Some(x) = &param.shell_limit {
+        Plugin::set_setting_shell_limit(&tx, x).await?;
+        PLUGIN_INSTANCE.session.set_limit(x.to_owned());
+    }
+    if let Some(x) = &param.acl {
+        Plugin::set_setting_acl(&tx, *x).await?;
+        PLUGIN_INSTANCE.acl.set_enable(*x);
+    }
     tx.commit().await?;
 
+    if let Some(x) = &param.command_group_limit {
+        PLUGIN_INSTANCE.set_command_group_limit(x.clone());
+    }
     if param.address.is_some() {
         restart_server(5).await?;
     }
+    if let Some(x) = &param.socks
+        && let Err(e) = PLUGIN_INSTANCE.socks.start(x).await
+    {
+        finish!(JsonResponse::bad_request(e.to_string()));
+    }
 
     info!(
         success = true,
         address = ?param.address,
         shell = ?param.shell,
+        proxy_protocol = ?param.proxy_protocol,
+        proxy_trusted = ?param.proxy_trusted,
+        firewall = ?param.firewall,
+        job_retention = ?param.job_retention,
+        command_group_limit = ?param.command_group_limit,
+        shell_record = ?param.shell_record,
+        shell_grace = ?param.shell_grace,
+        shell_limit = ?param.shell_limit,
+        acl = ?param.acl,
+        socks = ?param.socks,
         "Put monitor settings",
     );
     finish!(JsonResponse::new(MonitorResponse::Success))
 }
 
+#[derive(Debug, Validate, Deserialize)]
+pub struct AddAgentCommandReq {
+    #[validate(length(min = 1, max = 4096))]
+    pub cmd: String,
+    #[validate(length(min = 1, max = 1024))]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub timeout: u32,
+    #[validate(length(min = 1, max = 256))]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub stdin: String,
+    #[serde(default)]
+    pub output_limit: u64,
+    #[serde(default)]
+    #[validate(range(max = 2_592_000))]
+    pub queue_ttl: u32,
+}
+
+impl AddAgentCommandReq {
+    fn option(&self) -> CommandOption {
+        CommandOption {
+            cwd: self.cwd.clone(),
+            env: self.env.clone(),
+            timeout: self.timeout,
+            user: self.user.clone(),
+            stdin: self.stdin.clone().into_bytes(),
+            output_limit: self.output_limit,
+            queue_ttl: self.queue_ttl,
+        }
+    }
+}
+
+pub async fn add_agent_command(
+    aid: Path<HyUuid>,
+    req: Request,
+    param: Json<AddAgentCommandReq>,
+) -> RspResult<JsonResponse> {
+    let log_failed = |error: &str| {
+        info!(
+            success = false,
+            aid = %aid,
+            cmd = param.cmd,
+            cwd = param.cwd,
+            user = param.user,
+            ip = %req.extension.real_ip.ip(),
+            error,
+            "Run monitor agent command",
+        );
+    };
+    if PLUGIN_INSTANCE.agent.get(&aid).is_none() {
+        log_failed("Agent not found");
+        finish!(JsonResponse::not_found());
+    }
+    if !acl_check(&req, &aid, AclRight::Exec) {
+        log_failed("Permission denied");
+        finish!(JsonResponse::new_code(403));
+    }
+    let cid = match PLUGIN_INSTANCE
+        .send_command(&aid, req.uid, &param.cmd, param.option(), None)
+        .await
+    {
+        Ok(x) => x,
+        Err(e) => {
+            let e = anyhow::Error::from(e);
+            log_failed(&e.to_string());
+            return Err(e.into());
+        }
+    };
+    info!(
+        success = true,
+        aid = %aid,
+        cid = %cid,
+        cmd = param.cmd,
+        cwd = param.cwd,
+        user = param.user,
+        ip = %req.extension.real_ip.ip(),
+        "Run monitor agent command",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(cid))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetAgentCommandReq {
+    #[serde(default)]
+    pub offset: u64,
+}
+
+pub async fn get_agent_command(
+    path: Path<(HyUuid, HyUuid)>,
+    req: Request,
+    param: QsQuery<GetAgentCommandReq>,
+) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<i32>,
+        output: String,
+        offset: u64,
+        running: bool,
+        timed_out: bool,
+        truncated: bool,
+    }
+    let (aid, cid) = path.into_inner();
+    if !acl_check(&req, &aid, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let (running, x) = if let Some((status, x)) =
+        PLUGIN_INSTANCE.command.with(&aid, &cid, |status, x| {
+            (status, x.map(|x| x.read(param.offset)))
+        }) {
+        (status.is_pending(), x.unwrap_or_default())
+    } else if let Some(x) = PLUGIN_INSTANCE.find_job(&aid, &cid, JobKind::Command).await {
+        let running = x.status == JobStatus::Running;
+        (running, AgentCommand::from(x).read(param.offset))
+    } else {
+        finish!(JsonResponse::not_found());
+    };
+    let len = utf8_boundary(&x.output);
+    let start = x.offset - x.output.len() as u64;
+    finish!(JsonResponse::new(MonitorResponse::Success).json(Rsp {
+        code: x.code,
+        output: String::from_utf8_lossy(&x.output[..len]).into_owned(),
+        offset: start + len as u64,
+        running,
+        timed_out: x.timed_out,
+        truncated: x.truncated,
+    }))
+}
+
+pub async fn get_agent_commands(aid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    if PLUGIN_INSTANCE.agent.get(&aid).is_none() {
+        finish!(JsonResponse::not_found());
+    }
+    if !acl_check(&req, &aid, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PLUGIN_INSTANCE.limiter.status(&aid)))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct DeleteAgentCommandReq {
+    #[serde(default)]
+    pub force: bool,
+}
+
+pub async fn delete_agent_command(
+    path: Path<(HyUuid, HyUuid)>,
+    req: Request,
+    param: QsQuery<DeleteAgentCommandReq>,
+) -> RspResult<JsonResponse> {
+    let (aid, cid) = path.into_inner();
+    if !acl_check(&req, &aid, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    if !PLUGIN_INSTANCE
+        .command
+        .with(&aid, &cid, |status, _| status.is_pending())
+        .unwrap_or_default()
+    {
+        finish!(JsonResponse::not_found());
+    }
+    PLUGIN_INSTANCE.send_command_kill(&aid, &cid, param.force)?;
+    info!(
+        success = true,
+        aid = %aid,
+        cid = %cid,
+        force = param.force,
+        ip = %req.extension.real_ip.ip(),
+        "Kill monitor agent command",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
 pub async fn reconnect_agent(aid: Path<HyUuid>) -> RspResult<JsonResponse> {
     if let Some(agent) = PLUGIN_INSTANCE.agent.get(&aid) {
         if let Some(x) = &agent.message {
@@ -420,7 +847,20 @@ pub async fn reconnect_agent(aid: Path<HyUuid>) -> RspResult<JsonResponse> {
 #[derive(Debug, Validate, Deserialize)]
 pub struct PutAgentsReq {
     #[validate(length(max = 32))]
-    name: String,
+    name: Option<String>,
+    #[validate(
+        length(max = 32),
+        custom(function = "unique_validator"),
+        custom(function = "batch::label_validator")
+    )]
+    labels: Option<Vec<String>>,
+    /// Max concurrent commands, 0 for unlimited.
+    #[validate(range(max = 1024))]
+    command_limit: Option<u32>,
+    shell_record: Option<RecordMode>,
+    /// Allowed shell programs, empty to use the global list.
+    #[validate(length(max = 32), custom(function = "unique_validator"))]
+    shell: Option<Vec<String>>,
 }
 
 pub async fn put_agent(aid: Path<HyUuid>, param: Json<PutAgentsReq>) -> RspResult<JsonResponse> {
@@ -429,12 +869,30 @@ pub async fn put_agent(aid: Path<HyUuid>, param: Json<PutAgentsReq>) -> RspResul
     }
 
     let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
-    if AgentViewer::find_by_name(&tx, &param.name).await?.is_some() {
-        finish!(JsonResponse::new(MonitorResponse::AgentExist));
+    if let Some(name) = &param.name {
+        if AgentViewer::find_by_name(&tx, name).await?.is_some() {
+            finish!(JsonResponse::new(MonitorResponse::AgentExist));
+        }
+        AgentViewer::rename(&tx, &aid, name).await?;
+        if let Some(mut x) = PLUGIN_INSTANCE.agent.get_mut(&aid) {
+            x.name = name.clone();
+        }
     }
-    AgentViewer::rename(&tx, &aid, &param.name).await?;
-    if let Some(mut x) = PLUGIN_INSTANCE.agent.get_mut(&aid) {
-        x.name = param.name.clone();
+    if let Some(labels) = &param.labels {
+        PLUGIN_INSTANCE.set_agent_labels(&tx, &aid, labels).await?;
+    }
+    if let Some(limit) = param.command_limit {
+        PLUGIN_INSTANCE
+            .set_agent_command_limit(&tx, &aid, limit)
+            .await?;
+    }
+    if let Some(mode) = param.shell_record {
+        PLUGIN_INSTANCE
+            .set_agent_record_mode(&tx, &aid, mode)
+            .await?;
+    }
+    if let Some(shell) = &param.shell {
+        PLUGIN_INSTANCE.set_agent_shell(&tx, &aid, shell).await?;
     }
     tx.commit().await?;
 
@@ -442,6 +900,10 @@ pub async fn put_agent(aid: Path<HyUuid>, param: Json<PutAgentsReq>) -> RspResul
         success = true,
         aid = %aid,
         name = param.name,
+        labels = ?param.labels,
+        command_limit = param.command_limit,
+        shell_record = ?param.shell_record,
+        shell = ?param.shell,
         "Put monitor agent",
     );
     finish!(JsonResponse::new(MonitorResponse::Success))
@@ -454,6 +916,7 @@ pub async fn delete_agent(aid: Path<HyUuid>) -> RspResult<JsonResponse> {
 
     let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
     let rows = AgentViewer::delete(&tx, &[*aid]).await?;
+    PassiveAgentViewer::unlink(&tx, &[*aid]).await?;
     PLUGIN_INSTANCE.remove_agent(&aid);
     tx.commit().await?;
 
@@ -468,6 +931,7 @@ pub async fn delete_agent(aid: Path<HyUuid>) -> RspResult<JsonResponse> {
 pub async fn delete_agents(param: Json<IDsReq>) -> RspResult<JsonResponse> {
     let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
     let rows = AgentViewer::delete(&tx, &param.id).await?;
+    PassiveAgentViewer::unlink(&tx, &param.id).await?;
     for i in &param.id {
         PLUGIN_INSTANCE.remove_agent(i);
     }
@@ -480,3 +944,1587 @@ pub async fn delete_agents(param: Json<IDsReq>) -> RspResult<JsonResponse> {
     );
     finish!(JsonResponse::new(MonitorResponse::Success).json(rows))
 }
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct AddMigrationsReq {
+    #[validate(length(min = 1, max = 1024), custom(function = "unique_validator"))]
+    id: Vec<HyUuid>,
+    #[validate(length(min = 1, max = 256))]
+    endpoint: String,
+    pubkey: String,
+    #[serde(default = "default_migrate_timeout")]
+    #[validate(range(min = 1, max = 3600))]
+    timeout: u32,
+}
+
+const fn default_migrate_timeout() -> u32 {
+    30
+}
+
+pub async fn add_migrations(param: Json<AddMigrationsReq>) -> RspResult<JsonResponse> {
+    let Some(pubkey) = STANDARD
+        .decode(&param.pubkey)
+        .ok()
+        .filter(|x| PublicKey::parse_slice(x, None).is_ok())
+    else {
+        finish!(JsonResponse::new(MonitorResponse::InvalidPublicKey));
+    };
+    let mid = PLUGIN_INSTANCE.migrate_agents(&param.id, &param.endpoint, &pubkey, param.timeout);
+    info!(
+        success = true,
+        mid = %mid,
+        aid = ?param.id,
+        endpoint = param.endpoint,
+        "Migrate monitor agents",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(mid))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetMigrationsReq {
+    #[serde(flatten)]
+    #[validate(nested)]
+    page: PaginationParam,
+}
+
+pub async fn get_migrations(param: QsQuery<GetMigrationsReq>) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        id: HyUuid,
+        endpoint: String,
+        timeout: u32,
+        #[serde(flatten)]
+        summary: MigrateSummary,
+        created_at: i64,
+    }
+    let mut data: Vec<Rsp> = PLUGIN_INSTANCE
+        .migrate
+        .iter()
+        .map(|x| Rsp {
+            id: x.id,
+            endpoint: x.endpoint.clone(),
+            timeout: x.timeout,
+            summary: x.summary(),
+            created_at: x.created_at,
+        })
+        .collect();
+    data.sort_unstable_by_key(|x| -x.created_at);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(param.page.split(data)));
+}
+
+pub async fn get_migration(mid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(flatten)]
+        job: MigrateJob,
+        summary: MigrateSummary,
+    }
+    let Some(job) = PLUGIN_INSTANCE.migrate.get(&mid) else {
+        finish!(JsonResponse::not_found());
+    };
+    let rsp = Rsp {
+        job: job.clone(),
+        summary: job.summary(),
+    };
+    drop(job);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rsp))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "batch_queue_validator"))]
+pub struct AddBatchesReq {
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub command: AddAgentCommandReq,
+    #[validate(nested)]
+    pub target: Target,
+    #[serde(default = "default_batch_concurrency")]
+    #[validate(range(min = 1, max = 256))]
+    pub concurrency: u32,
+}
+
+const fn default_batch_concurrency() -> u32 {
+    10
+}
+
+/// Batches report offline agents instead of queuing, reject `queue_ttl`.
+fn batch_queue_validator(x: &AddBatchesReq) -> Result<(), validator::ValidationError> {
+    if x.command.queue_ttl == 0 {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("queue is not supported"))
+    }
+}
+
+pub async fn add_batches(req: Request, param: Json<AddBatchesReq>) -> RspResult<JsonResponse> {
+    let aid: Vec<HyUuid> = param
+        .target
+        .resolve()
+        .into_iter()
+        .filter(|x| acl_check(&req, x, AclRight::Exec))
+        .collect();
+    if aid.is_empty() {
+        finish!(JsonResponse::not_found());
+    }
+    let command = &param.command;
+    let task = Task::Command {
+        cmd: command.cmd.clone(),
+        option: command.option(),
+    };
+    let bid = batch::run(aid, req.uid, task, param.concurrency, None);
+    info!(
+        success = true,
+        bid = %bid,
+        target = ?param.target,
+        concurrency = param.concurrency,
+        cmd = command.cmd,
+        cwd = command.cwd,
+        user = command.user,
+        ip = %req.extension.real_ip.ip(),
+        "Run monitor batch command",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(bid))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetBatchesReq {
+    #[serde(flatten)]
+    #[validate(nested)]
+    page: PaginationParam,
+}
+
+/// Clone batch `job` with results of agents on which `req` has exec right only.
+///
+/// Return `None` when no result is visible.
+fn acl_batch(req: &Request, job: &BatchJob) -> Option<BatchJob> {
+    let mut job = job.clone();
+    job.result.retain(|k, _| acl_check(req, k, AclRight::Exec));
+    (!job.result.is_empty()).then_some(job)
+}
+
+pub async fn get_batches(req: Request, param: QsQuery<GetBatchesReq>) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        id: HyUuid,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        uid: Option<HyUuid>,
+        cmd: String,
+        concurrency: u32,
+        #[serde(flatten)]
+        summary: BatchSummary,
+        created_at: i64,
+    }
+    let mut data: Vec<Rsp> = PLUGIN_INSTANCE
+        .batch
+        .iter()
+        .filter_map(|x| acl_batch(&req, &x))
+        .map(|x| Rsp {
+            summary: x.summary(),
+            id: x.id,
+            uid: x.uid,
+            cmd: x.cmd,
+            concurrency: x.concurrency,
+            created_at: x.created_at,
+        })
+        .collect();
+    data.sort_unstable_by_key(|x| -x.created_at);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(param.page.split(data)));
+}
+
+pub async fn get_batch(bid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(flatten)]
+        job: BatchJob,
+        summary: BatchSummary,
+    }
+    let Some(job) = PLUGIN_INSTANCE
+        .batch
+        .get(&bid)
+        .and_then(|x| acl_batch(&req, &x))
+    else {
+        finish!(JsonResponse::not_found());
+    };
+    let rsp = Rsp {
+        summary: job.summary(),
+        job,
+    };
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rsp))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOutputFormat {
+    #[default]
+    Json,
+    Zip,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetBatchOutputReq {
+    #[serde(default)]
+    pub format: BatchOutputFormat,
+}
+
+pub async fn get_batch_output(
+    bid: Path<HyUuid>,
+    req: Request,
+    param: QsQuery<GetBatchOutputReq>,
+) -> RspResult<HttpResponse> {
+    #[derive(Serialize)]
+    struct Output {
+        aid: HyUuid,
+        name: String,
+        #[serde(flatten)]
+        result: BatchResult,
+        output: String,
+    }
+    let Some(job) = PLUGIN_INSTANCE
+        .batch
+        .get(&bid)
+        .and_then(|x| acl_batch(&req, &x))
+    else {
+        finish!(HttpResponse::NotFound().finish());
+    };
+    let mut data = Vec::new();
+    for (aid, result) in job.result {
+        let output = if let Some(cid) = result.cid {
+            if let Some(x) = PLUGIN_INSTANCE.command.with(&aid, &cid, |_, x| {
+                x.map(|x| x.output.clone()).unwrap_or_default()
+            }) {
+                x
+            } else {
+                PLUGIN_INSTANCE
+                    .find_job(&aid, &cid, JobKind::Command)
+                    .await
+                    .map(|x| x.output)
+                    .unwrap_or_default()
+            }
+        } else {
+            Vec::new()
+        };
+        let name = PLUGIN_INSTANCE
+            .agent
+            .get(&aid)
+            .map(|x| x.name.clone())
+            .unwrap_or_default();
+        data.push(Output {
+            aid,
+            name,
+            result,
+            output: String::from_utf8_lossy(&output).into_owned(),
+        });
+    }
+    data.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    match param.format {
+        BatchOutputFormat::Json => finish!(JsonResponse::file(
+            format!("{bid}.json"),
+            serde_json::to_vec(&data)?
+        )),
+        BatchOutputFormat::Zip => {
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            let option =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            for i in data {
+                // Agent name is user input, never use it as path.
+                zip.start_file(format!("{}.txt", i.aid), option)?;
+                zip.write_all(i.output.as_bytes())?;
+            }
+            finish!(JsonResponse::file(
+                format!("{bid}.zip"),
+                zip.finish()?.into_inner()
+            ))
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ScheduleRsp {
+    id: HyUuid,
+    name: String,
+    cmd: String,
+    target: Target,
+    option: CommandOption,
+    cron: String,
+    timezone: String,
+    concurrency: i32,
+    alert: bool,
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid: Option<HyUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_run_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_run_at: Option<i64>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl From<schedules::Model> for ScheduleRsp {
+    fn from(v: schedules::Model) -> Self {
+        Self {
+            id: v.id,
+            target: serde_json::from_str(&v.target).unwrap_or_default(),
+            option: serde_json::from_str(&v.option).unwrap_or_default(),
+            next_run_at: PLUGIN_INSTANCE.scheduler.next_run(&v.id),
+            name: v.name,
+            cmd: v.cmd,
+            cron: v.cron,
+            timezone: v.timezone,
+            concurrency: v.concurrency,
+            alert: v.alert,
+            enabled: v.enabled,
+            uid: v.uid,
+            last_run_at: v.last_run_at,
+            created_at: v.created_at,
+            updated_at: v.updated_at,
+        }
+    }
+}
+
+#[common_req(schedules::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetSchedulesReq {
+    pub enabled: Option<bool>,
+    pub text: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+/// Whether `req` has exec right on all agents matched by schedule `x`.
+fn acl_check_schedule(req: &Request, x: &schedules::Model) -> bool {
+    PLUGIN_INSTANCE.acl.unrestricted(&req.perm)
+        || serde_json::from_str::<Target>(&x.target)
+            .is_ok_and(|x| acl_check_target(req, &x, AclRight::Exec))
+}
+
+pub async fn get_schedules(
+    req: Request,
+    param: QsQuery<GetSchedulesReq>,
+) -> RspResult<JsonResponse> {
+    let db = PLUGIN_INSTANCE.db.get().unwrap();
+    let mut cond = param
+        .common_cond()
+        .add_option(param.enabled.map(|x| schedules::Column::Enabled.eq(x)));
+    if let Some(text) = &param.text {
+        cond = cond.add(
+            Condition::any()
+                .add(text.like_expr(schedules::Column::Id))
+                .add(text.like_expr(schedules::Column::Name))
+                .add(text.like_expr(schedules::Column::Cmd)),
+        );
+    }
+    if !PLUGIN_INSTANCE.acl.unrestricted(&req.perm) {
+        let sid: Vec<HyUuid> = ScheduleViewer::find(db, Condition::new(Condition::all()))
+            .await?
+            .0
+            .into_iter()
+            .filter(|x| acl_check_schedule(&req, x))
+            .map(|x| x.id)
+            .collect();
+        cond = cond.add(schedules::Column::Id.is_in(sid));
+    }
+    let data = ScheduleViewer::find(db, cond).await?;
+    let data: (Vec<ScheduleRsp>, u64) = (data.0.into_iter().map(Into::into).collect(), data.1);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+pub async fn get_schedule(sid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let Some(x) = ScheduleViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    if !acl_check_schedule(&req, &x) {
+        finish!(JsonResponse::new_code(403));
+    }
+    finish!(JsonResponse::new(MonitorResponse::Success).json(ScheduleRsp::from(x)))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "schedule_queue_validator"))]
+pub struct AddSchedulesReq {
+    #[validate(length(min = 1, max = 32))]
+    pub name: String,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub command: AddAgentCommandReq,
+    #[validate(nested)]
+    pub target: Target,
+    #[serde(default = "default_batch_concurrency")]
+    #[validate(range(min = 1, max = 256))]
+    pub concurrency: u32,
+    #[validate(
+        length(min = 1, max = 256),
+        custom(function = "scheduler::cron_validator")
+    )]
+    pub cron: String,
+    #[serde(default = "default_timezone")]
+    #[validate(custom(function = "scheduler::timezone_validator"))]
+    pub timezone: String,
+    #[serde(default)]
+    pub alert: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_timezone() -> String {
+    String::from("UTC")
+}
+
+const fn default_enabled() -> bool {
+    true
+}
+
+/// Schedules run as batches, reject `queue_ttl`.
+fn schedule_queue_validator(x: &AddSchedulesReq) -> Result<(), validator::ValidationError> {
+    if x.command.queue_ttl == 0 {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("queue is not supported"))
+    }
+}
+
+impl AddSchedulesReq {
+    fn model(&self, id: HyUuid, uid: Option<HyUuid>) -> Result<schedules::Model> {
+        Ok(schedules::Model {
+            id,
+            name: self.name.clone(),
+            cmd: self.command.cmd.clone(),
+            target: serde_json::to_string(&self.target)?,
+            option: serde_json::to_string(&self.command.option())?,
+            cron: self.cron.clone(),
+            timezone: self.timezone.clone(),
+            concurrency: self.concurrency.try_into()?,
+            alert: self.alert,
+            enabled: self.enabled,
+            uid,
+            ..Default::default()
+        })
+    }
+}
+
+pub async fn add_schedules(req: Request, param: Json<AddSchedulesReq>) -> RspResult<JsonResponse> {
+    if !acl_check_target(&req, &param.target, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if ScheduleViewer::find_by_name(&tx, &param.name)
+        .await?
+        .is_some()
+    {
+        finish!(JsonResponse::new(MonitorResponse::ScheduleExist));
+    }
+    let m = ScheduleViewer::create(&tx, param.model(HyUuid::default(), req.uid)?).await?;
+    tx.commit().await?;
+    PLUGIN_INSTANCE.scheduler.insert(m.clone())?;
+
+    info!(
+        success = true,
+        sid = %m.id,
+        name = param.name,
+        cmd = param.command.cmd,
+        target = ?param.target,
+        cron = param.cron,
+        timezone = param.timezone,
+        alert = param.alert,
+        enabled = param.enabled,
+        ip = %req.extension.real_ip.ip(),
+        "Add monitor schedule",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(m.id));
+}
+
+pub async fn put_schedule(
+    sid: Path<HyUuid>,
+    req: Request,
+    param: Json<AddSchedulesReq>,
+) -> RspResult<JsonResponse> {
+    if !acl_check_target(&req, &param.target, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if ScheduleViewer::find_by_id(&tx, &sid).await?.is_none() {
+        finish!(JsonResponse::not_found());
+    }
+    if let Some(x) = ScheduleViewer::find_by_name(&tx, &param.name).await?
+        && x.id != *sid
+    {
+        finish!(JsonResponse::new(MonitorResponse::ScheduleExist));
+    }
+    // Editor takes over the schedule, commands are issued as the editor from now on.
+    let m = ScheduleViewer::update(&tx, param.model(*sid, req.uid)?).await?;
+    tx.commit().await?;
+    PLUGIN_INSTANCE.scheduler.insert(m)?;
+
+    info!(
+        success = true,
+        sid = %sid,
+        name = param.name,
+        cmd = param.command.cmd,
+        target = ?param.target,
+        cron = param.cron,
+        timezone = param.timezone,
+        alert = param.alert,
+        enabled = param.enabled,
+        ip = %req.extension.real_ip.ip(),
+        "Put monitor schedule",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+pub async fn delete_schedule(sid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    let Some(x) = ScheduleViewer::find_by_id(&tx, &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    if !acl_check_schedule(&req, &x) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let rows = ScheduleViewer::delete(&tx, &[*sid]).await?;
+    tx.commit().await?;
+    PLUGIN_INSTANCE.scheduler.remove(&sid);
+    info!(
+        success = true,
+        sid = %sid,
+        ip = %req.extension.real_ip.ip(),
+        "Delete monitor schedule",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rows));
+}
+
+pub async fn run_schedule(sid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let Some(x) = ScheduleViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    if !acl_check_schedule(&req, &x) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let Some(bid) = Scheduler::fire(Arc::new(x)).await else {
+        finish!(JsonResponse::not_found());
+    };
+    info!(
+        success = true,
+        sid = %sid,
+        bid = %bid,
+        ip = %req.extension.real_ip.ip(),
+        "Run monitor schedule manually",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(bid))
+}
+
+#[common_req(schedule_runs::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetScheduleRunsReq {
+    pub rid: Option<HyUuid>,
+    pub aid: Option<HyUuid>,
+    #[validate(custom(function = "unique_validator"))]
+    pub status: Option<Vec<RunStatus>>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_schedule_runs(
+    sid: Path<HyUuid>,
+    req: Request,
+    param: QsQuery<GetScheduleRunsReq>,
+) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(flatten)]
+        run: schedule_runs::Model,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        agent_name: Option<String>,
+    }
+    let mut cond = param
+        .common_cond()
+        .add(schedule_runs::Column::Sid.eq(*sid))
+        .add_option(param.rid.map(|x| schedule_runs::Column::Rid.eq(x)))
+        .add_option(param.aid.map(|x| schedule_runs::Column::Aid.eq(x)))
+        .add_option(
+            param
+                .status
+                .as_ref()
+                .map(|x| schedule_runs::Column::Status.is_in(x.to_owned())),
+        );
+    if !PLUGIN_INSTANCE.acl.unrestricted(&req.perm) {
+        let aid: Vec<HyUuid> = PLUGIN_INSTANCE
+            .agent
+            .iter()
+            .filter(|x| acl_check(&req, &x.id, AclRight::Exec))
+            .map(|x| x.id)
+            .collect();
+        cond = cond.add(schedule_runs::Column::Aid.is_in(aid));
+    }
+    let data = ScheduleRunViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    let data = (
+        data.0
+            .into_iter()
+            .map(|x| Rsp {
+                agent_name: PLUGIN_INSTANCE.agent.get(&x.aid).map(|v| v.name.clone()),
+                run: x,
+            })
+            .collect(),
+        data.1,
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+#[derive(Serialize)]
+pub struct ScriptRsp {
+    id: HyUuid,
+    name: String,
+    description: String,
+    interpreter: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    params: Vec<ScriptParam>,
+    target: Target,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl ScriptRsp {
+    fn new(v: scripts::Model, body: bool) -> Self {
+        Self {
+            id: v.id,
+            params: serde_json::from_str(&v.params).unwrap_or_default(),
+            target: serde_json::from_str(&v.target).unwrap_or_default(),
+            name: v.name,
+            description: v.description,
+            interpreter: v.interpreter,
+            body: body.then_some(v.body),
+            created_at: v.created_at,
+            updated_at: v.updated_at,
+        }
+    }
+}
+
+#[common_req(scripts::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetScriptsReq {
+    pub text: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_scripts(param: QsQuery<GetScriptsReq>) -> RspResult<JsonResponse> {
+    let mut cond = param.common_cond();
+    if let Some(text) = &param.text {
+        cond = cond.add(
+            Condition::any()
+                .add(text.like_expr(scripts::Column::Id))
+                .add(text.like_expr(scripts::Column::Name))
+                .add(text.like_expr(scripts::Column::Description)),
+        );
+    }
+    let data = ScriptViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    let data: (Vec<ScriptRsp>, u64) = (
+        data.0
+            .into_iter()
+            .map(|x| ScriptRsp::new(x, false))
+            .collect(),
+        data.1,
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+pub async fn get_script(sid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    let Some(x) = ScriptViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    finish!(JsonResponse::new(MonitorResponse::Success).json(ScriptRsp::new(x, true)))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct AddScriptsReq {
+    #[validate(length(min = 1, max = 32))]
+    pub name: String,
+    #[serde(default)]
+    #[validate(length(max = 1024))]
+    pub description: String,
+    #[validate(length(min = 1, max = 256))]
+    pub interpreter: String,
+    #[validate(length(min = 1, max = 1_048_576))]
+    pub body: String,
+    #[serde(default)]
+    #[validate(
+        length(max = 64),
+        nested,
+        custom(function = "script::params_validator")
+    )]
+    pub params: Vec<ScriptParam>,
+    /// Allowed target set, all agents when `None`.
+    #[validate(nested)]
+    pub target: Option<Target>,
+}
+
+impl AddScriptsReq {
+    fn model(&self, id: HyUuid) -> Result<scripts::Model> {
+        Ok(scripts::Model {
+            id,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            interpreter: self.interpreter.clone(),
+            body: self.body.clone(),
+            params: serde_json::to_string(&self.params)?,
+            target: serde_json::to_string(&self.target.clone().unwrap_or_default())?,
+            ..Default::default()
+        })
+    }
+}
+
+pub async fn add_scripts(req: Request, param: Json<AddScriptsReq>) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if ScriptViewer::find_by_name(&tx, &param.name)
+        .await?
+        .is_some()
+    {
+        finish!(JsonResponse::new(MonitorResponse::ScriptExist));
+    }
+    let m = ScriptViewer::create(&tx, param.model(HyUuid::default())?).await?;
+    tx.commit().await?;
+
+    info!(
+        success = true,
+        sid = %m.id,
+        name = param.name,
+        interpreter = param.interpreter,
+        target = ?param.target,
+        ip = %req.extension.real_ip.ip(),
+        "Add monitor script",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(m.id));
+}
+
+pub async fn put_script(
+    sid: Path<HyUuid>,
+    req: Request,
+    param: Json<AddScriptsReq>,
+) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if ScriptViewer::find_by_id(&tx, &sid).await?.is_none() {
+        finish!(JsonResponse::not_found());
+    }
+    if let Some(x) = ScriptViewer::find_by_name(&tx, &param.name).await?
+        && x.id != *sid
+    {
+        finish!(JsonResponse::new(MonitorResponse::ScriptExist));
+    }
+    ScriptViewer::update(&tx, param.model(*sid)?).await?;
+    tx.commit().await?;
+
+    info!(
+        success = true,
+        sid = %sid,
+        name = param.name,
+        interpreter = param.interpreter,
+        target = ?param.target,
+        ip = %req.extension.real_ip.ip(),
+        "Put monitor script",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+pub async fn delete_script(sid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    let Some(x) = ScriptViewer::find_by_id(&tx, &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    // Scripts are shared, only users able to run it on all allowed agents can delete it.
+    if !PLUGIN_INSTANCE.acl.unrestricted(&req.perm)
+        && !serde_json::from_str::<Target>(&x.target)
+            .is_ok_and(|x| acl_check_target(&req, &x, AclRight::Exec))
+    {
+        finish!(JsonResponse::new_code(403));
+    }
+    let rows = ScriptViewer::delete(&tx, &[*sid]).await?;
+    tx.commit().await?;
+    info!(
+        success = true,
+        sid = %sid,
+        ip = %req.extension.real_ip.ip(),
+        "Delete monitor script",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rows));
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct RunScriptReq {
+    #[validate(nested)]
+    pub target: Target,
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+    #[serde(default = "default_batch_concurrency")]
+    #[validate(range(min = 1, max = 256))]
+    pub concurrency: u32,
+    #[validate(length(min = 1, max = 1024))]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub timeout: u32,
+    #[validate(length(min = 1, max = 256))]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub output_limit: u64,
+}
+
+pub async fn run_script(
+    sid: Path<HyUuid>,
+    req: Request,
+    param: Json<RunScriptReq>,
+) -> RspResult<JsonResponse> {
+    let Some(x) = ScriptViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &sid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    let params: Vec<ScriptParam> = serde_json::from_str(&x.params)?;
+    let allowed: Target = serde_json::from_str(&x.target)?;
+    let env = match script::resolve_params(&params, &param.params) {
+        Ok(x) => x,
+        Err(e) => finish!(JsonResponse::bad_request(e)),
+    };
+    let aid: Vec<HyUuid> = param
+        .target
+        .resolve()
+        .into_iter()
+        .filter(|x| {
+            PLUGIN_INSTANCE
+                .agent
+                .get(x)
+                .is_some_and(|x| allowed.matches(&x))
+                && acl_check(&req, x, AclRight::Exec)
+        })
+        .collect();
+    if aid.is_empty() {
+        finish!(JsonResponse::not_found());
+    }
+    let task = Task::Script {
+        name: x.name.clone(),
+        interpreter: x.interpreter,
+        body: Arc::new(x.body.into_bytes()),
+        option: CommandOption {
+            cwd: param.cwd.clone(),
+            env,
+            timeout: param.timeout,
+            user: param.user.clone(),
+            output_limit: param.output_limit,
+            ..Default::default()
+        },
+    };
+    let bid = batch::run(aid, req.uid, task, param.concurrency, None);
+    info!(
+        success = true,
+        sid = %sid,
+        bid = %bid,
+        name = x.name,
+        target = ?param.target,
+        params = ?param.params,
+        ip = %req.extension.real_ip.ip(),
+        "Run monitor script",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(bid))
+}
+
+#[common_req(jobs::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetJobsReq {
+    pub aid: Option<HyUuid>,
+    #[validate(custom(function = "unique_validator"))]
+    pub kind: Option<Vec<JobKind>>,
+    #[validate(custom(function = "unique_validator"))]
+    pub status: Option<Vec<JobStatus>>,
+    pub text: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_jobs(param: QsQuery<GetJobsReq>) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        id: HyUuid,
+        aid: HyUuid,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        agent_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        uid: Option<HyUuid>,
+        kind: JobKind,
+        status: JobStatus,
+        cmd: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<i32>,
+        timed_out: bool,
+        truncated: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expire_at: Option<i64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        finished_at: Option<i64>,
+        created_at: i64,
+        updated_at: i64,
+    }
+    let mut cond = param
+        .common_cond()
+        .add_option(param.aid.map(|x| jobs::Column::Aid.eq(x)))
+        .add_option(
+            param
+                .kind
+                .as_ref()
+                .map(|x| jobs::Column::Kind.is_in(x.to_owned())),
+        )
+        .add_option(
+            param
+                .status
+                .as_ref()
+                .map(|x| jobs::Column::Status.is_in(x.to_owned())),
+        );
+    if let Some(text) = &param.text {
+        cond = cond.add(
+            Condition::any()
+                .add(text.like_expr(jobs::Column::Id))
+                .add(text.like_expr(jobs::Column::Aid))
+                .add(text.like_expr(jobs::Column::Cmd)),
+        );
+    }
+    let data = JobViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    let data = (
+        data.0
+            .into_iter()
+            .map(|x| Rsp {
+                id: x.id,
+                aid: x.aid,
+                agent_name: PLUGIN_INSTANCE.agent.get(&x.aid).map(|v| v.name.clone()),
+                uid: x.uid,
+                kind: x.kind,
+                status: x.status,
+                cmd: x.cmd,
+                code: x.code,
+                timed_out: x.timed_out,
+                truncated: x.truncated,
+                expire_at: x.expire_at,
+                finished_at: x.finished_at,
+                created_at: x.created_at,
+                updated_at: x.updated_at,
+            })
+            .collect(),
+        data.1,
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+pub async fn get_job(jid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(flatten)]
+        job: jobs::Model,
+        output: String,
+    }
+    let Some(mut job) = JobViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &jid).await?
+    else {
+        finish!(JsonResponse::not_found());
+    };
+    if job.status == JobStatus::Queued
+        && job
+            .expire_at
+            .is_some_and(|x| x <= Utc::now().timestamp_millis())
+    {
+        job.status = JobStatus::Expired;
+    }
+    // Running jobs are only updated in memory.
+    if job.status == JobStatus::Running {
+        match job.kind {
+            JobKind::Command => {
+                if let Some((_, Some(x))) = PLUGIN_INSTANCE.command.get(&job.aid, &jid) {
+                    job.code = x.code;
+                    job.output = x.output;
+                    job.timed_out = x.timed_out;
+                    job.truncated = x.truncated;
+                }
+            }
+            JobKind::File => {
+                if let Some((_, Some(x))) = PLUGIN_INSTANCE.file.get(&job.aid, &jid) {
+                    job.code = x.code.try_into().ok();
+                    job.output = x.message.into_bytes();
+                }
+            }
+        }
+    }
+    let output = String::from_utf8_lossy(&job.output).into_owned();
+    job.output.clear();
+    finish!(JsonResponse::new(MonitorResponse::Success).json(Rsp { job, output }))
+}
+
+pub async fn delete_job_queue(jid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let db = PLUGIN_INSTANCE.db.get().unwrap();
+    let Some(job) = JobViewer::find_by_id(db, &jid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    // Only the issuer or users able to run commands on the agent can cancel.
+    if (req.uid.is_none() || job.uid != req.uid) && !acl_check(&req, &job.aid, AclRight::Exec) {
+        finish!(JsonResponse::new_code(403));
+    }
+    if !JobViewer::cancel_queued(db, &jid).await? {
+        finish!(JsonResponse::not_found());
+    }
+    info!(
+        success = true,
+        jid = %jid,
+        ip = %req.extension.real_ip.ip(),
+        "Cancel monitor queued job",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+#[common_req(shell_recordings::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetRecordingsReq {
+    pub aid: Option<HyUuid>,
+    pub uid: Option<HyUuid>,
+    pub text: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_recordings(param: QsQuery<GetRecordingsReq>) -> RspResult<JsonResponse> {
+    let mut cond = param
+        .common_cond()
+        .add_option(param.aid.map(|x| shell_recordings::Column::Aid.eq(x)))
+        .add_option(param.uid.map(|x| shell_recordings::Column::Uid.eq(x)));
+    if let Some(text) = &param.text {
+        cond = cond.add(
+            Condition::any()
+                .add(text.like_expr(shell_recordings::Column::Id))
+                .add(text.like_expr(shell_recordings::Column::Token))
+                .add(text.like_expr(shell_recordings::Column::Cmd)),
+        );
+    }
+    let data = ShellRecordingViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+pub async fn get_recording(rid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    let Some(x) = ShellRecordingViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &rid).await?
+    else {
+        finish!(JsonResponse::not_found());
+    };
+    finish!(JsonResponse::new(MonitorResponse::Success).json(x))
+}
+
+async fn read_recording(rid: &HyUuid) -> Result<Option<Vec<u8>>> {
+    if ShellRecordingViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), rid)
+        .await?
+        .is_none()
+    {
+        return Ok(None);
+    }
+    let Some(path) = PLUGIN_INSTANCE.recording.file(rid) else {
+        return Ok(None);
+    };
+    Ok(fs::read(path).await.ok())
+}
+
+pub async fn download_recording(rid: Path<HyUuid>) -> RspResult<HttpResponse> {
+    let Some(data) = read_recording(&rid).await? else {
+        finish!(HttpResponse::NotFound().finish());
+    };
+    finish!(JsonResponse::file(format!("{rid}.cast"), data))
+}
+
+pub async fn replay_recording(rid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    let Some(data) = read_recording(&rid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    let cast = Cast::parse(&String::from_utf8_lossy(&data))?;
+    finish!(JsonResponse::new(MonitorResponse::Success).json(cast))
+}
+
+pub async fn delete_recording(rid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    let Some(x) = ShellRecordingViewer::find_by_id(&tx, &rid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    if x.finished_at.is_none() && PLUGIN_INSTANCE.recording.is_active(&x.token) {
+        finish!(JsonResponse::bad_request("Recording is running"));
+    }
+    let rows = ShellRecordingViewer::delete(&tx, &[*rid]).await?;
+    if let Some(path) = PLUGIN_INSTANCE.recording.file(&rid) {
+        let _ = fs::remove_file(path).await;
+    }
+    tx.commit().await?;
+
+    info!(
+        success = true,
+        rid = %rid,
+        ip = %req.extension.real_ip.ip(),
+        "Delete monitor shell recording",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rows))
+}
+
+/// Fill live transferred bytes of unfinished session `x`.
+fn live_session(mut x: shell_sessions::Model) -> shell_sessions::Model {
+    if x.finished_at.is_none()
+        && let Some((bytes_in, bytes_out)) = PLUGIN_INSTANCE.session.bytes(&x.id)
+    {
+        x.bytes_in = bytes_in.try_into().unwrap_or(i64::MAX);
+        x.bytes_out = bytes_out.try_into().unwrap_or(i64::MAX);
+    }
+    x
+}
+
+#[common_req(shell_sessions::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetShellsReq {
+    pub aid: Option<HyUuid>,
+    pub uid: Option<HyUuid>,
+    pub active: Option<bool>,
+    pub text: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_shells(param: QsQuery<GetShellsReq>) -> RspResult<JsonResponse> {
+    let mut cond = param
+        .common_cond()
+        .add_option(param.aid.map(|x| shell_sessions::Column::Aid.eq(x)))
+        .add_option(param.uid.map(|x| shell_sessions::Column::Uid.eq(x)))
+        .add_option(param.active.map(|x| {
+            if x {
+                shell_sessions::Column::FinishedAt.is_null()
+            } else {
+                shell_sessions::Column::FinishedAt.is_not_null()
+            }
+        }));
+    if let Some(text) = &param.text {
+        cond = cond.add(
+            Condition::any()
+                .add(text.like_expr(shell_sessions::Column::Id))
+                .add(text.like_expr(shell_sessions::Column::Token))
+                .add(text.like_expr(shell_sessions::Column::Cmd))
+                .add(text.like_expr(shell_sessions::Column::Ip)),
+        );
+    }
+    let data = ShellSessionViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    let data: (Vec<shell_sessions::Model>, u64) =
+        (data.0.into_iter().map(live_session).collect(), data.1);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+pub async fn get_shell(sid: Path<HyUuid>) -> RspResult<JsonResponse> {
+    let Some(x) = ShellSessionViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &sid).await?
+    else {
+        finish!(JsonResponse::not_found());
+    };
+    finish!(JsonResponse::new(MonitorResponse::Success).json(live_session(x)))
+}
+
+pub async fn terminate_shell(sid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let Some(token) = PLUGIN_INSTANCE.session.token(&sid) else {
+        finish!(JsonResponse::not_found());
+    };
+    if !ShellService::terminate(&token, "Session terminated by administrator").await {
+        finish!(JsonResponse::not_found());
+    }
+
+    info!(
+        success = true,
+        sid = %sid,
+        ip = %req.extension.real_ip.ip(),
+        "Terminate monitor shell",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+#[derive(Serialize)]
+pub struct AclRsp {
+    pub id: HyUuid,
+    pub uid: HyUuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aid: Option<HyUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub rights: Vec<AclRight>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl From<acls::Model> for AclRsp {
+    fn from(value: acls::Model) -> Self {
+        Self {
+            id: value.id,
+            uid: value.uid,
+            aid: value.aid,
+            label: value.label,
+            rights: AclRight::decode(value.perm),
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+/// Reload cached acl entries after changes.
+async fn reload_acl() -> Result<()> {
+    PLUGIN_INSTANCE
+        .acl
+        .load(AclViewer::find_all(PLUGIN_INSTANCE.db.get().unwrap()).await?);
+    Ok(())
+}
+
+#[common_req(acls::Column)]
+#[derive(Debug, Validate, Deserialize)]
+pub struct GetAclsReq {
+    pub uid: Option<HyUuid>,
+    pub aid: Option<HyUuid>,
+    pub label: Option<String>,
+
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub page: PaginationParam,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub time: TimeParam,
+}
+
+pub async fn get_acls(param: QsQuery<GetAclsReq>) -> RspResult<JsonResponse> {
+    let cond = param
+        .common_cond()
+        .add_option(param.uid.map(|x| acls::Column::Uid.eq(x)))
+        .add_option(param.aid.map(|x| acls::Column::Aid.eq(x)))
+        .add_option(param.label.as_ref().map(|x| acls::Column::Label.eq(x)));
+    let data = AclViewer::find(PLUGIN_INSTANCE.db.get().unwrap(), cond).await?;
+    let data: (Vec<AclRsp>, u64) = (data.0.into_iter().map(Into::into).collect(), data.1);
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PageData::new(data)));
+}
+
+fn acl_target_validator(x: &AddAclsReq) -> Result<(), validator::ValidationError> {
+    if x.aid.is_some() == x.label.is_some() {
+        Err(validator::ValidationError::new("either aid or label"))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "acl_target_validator"))]
+pub struct AddAclsReq {
+    pub uid: HyUuid,
+    pub aid: Option<HyUuid>,
+    #[validate(length(min = 1, max = 32))]
+    pub label: Option<String>,
+    #[validate(
+        custom(function = "unique_validator"),
+        custom(function = "acl::rights_validator")
+    )]
+    pub rights: Vec<AclRight>,
+}
+
+pub async fn add_acls(req: Request, param: Json<AddAclsReq>) -> RspResult<JsonResponse> {
+    let x = AclViewer::create(
+        PLUGIN_INSTANCE.db.get().unwrap(),
+        acls::Model {
+            uid: param.uid,
+            aid: param.aid,
+            label: param.label.clone(),
+            perm: AclRight::encode(&param.rights),
+            ..Default::default()
+        },
+    )
+    .await?;
+    reload_acl().await?;
+
+    info!(
+        success = true,
+        id = %x.id,
+        uid = %param.uid,
+        aid = ?param.aid,
+        label = param.label,
+        rights = ?param.rights,
+        ip = %req.extension.real_ip.ip(),
+        "Add monitor acl",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(x.id))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct PutAclReq {
+    #[validate(
+        custom(function = "unique_validator"),
+        custom(function = "acl::rights_validator")
+    )]
+    pub rights: Vec<AclRight>,
+}
+
+pub async fn put_acl(
+    id: Path<HyUuid>,
+    req: Request,
+    param: Json<PutAclReq>,
+) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if AclViewer::find_by_id(&tx, &id).await?.is_none() {
+        finish!(JsonResponse::not_found());
+    }
+    AclViewer::update(&tx, &id, AclRight::encode(&param.rights)).await?;
+    tx.commit().await?;
+    reload_acl().await?;
+
+    info!(
+        success = true,
+        id = %id,
+        rights = ?param.rights,
+        ip = %req.extension.real_ip.ip(),
+        "Put monitor acl",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+pub async fn delete_acl(id: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let rows = AclViewer::delete(PLUGIN_INSTANCE.db.get().unwrap(), &[*id]).await?;
+    if rows == 0 {
+        finish!(JsonResponse::not_found());
+    }
+    reload_acl().await?;
+
+    info!(
+        success = true,
+        id = %id,
+        ip = %req.extension.real_ip.ip(),
+        "Delete monitor acl",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rows))
+}
+
+/// Whether `req` can manage tunnel `x`.
+fn tunnel_owner(req: &Request, x: &TunnelInfo) -> bool {
+    x.uid == req.uid || Acl::is_manager(&req.perm)
+}
+
+pub async fn get_tunnels(req: Request) -> RspResult<JsonResponse> {
+    let data: Vec<TunnelInfo> = PLUGIN_INSTANCE
+        .tunnel
+        .list()
+        .into_iter()
+        .filter(|x| tunnel_owner(&req, x))
+        .collect();
+    finish!(JsonResponse::new(MonitorResponse::Success).json(data))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct AddTunnelsReq {
+    #[validate(length(min = 1, max = 253))]
+    pub host: String,
+    #[validate(range(min = 1))]
+    pub port: u16,
+    /// Local listen address, default to a random loopback port.
+    pub bind: Option<SocketAddr>,
+}
+
+pub async fn add_tunnels(
+    aid: Path<HyUuid>,
+    req: Request,
+    param: Json<AddTunnelsReq>,
+) -> RspResult<JsonResponse> {
+    if PLUGIN_INSTANCE.agent.get(&aid).is_none() {
+        finish!(JsonResponse::not_found());
+    }
+    if !acl_check(&req, &aid, AclRight::Forward) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let bind = param
+        .bind
+        .unwrap_or_else(|| SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+    // Only managers can expose tunnels beyond the server itself.
+    if !bind.ip().is_loopback() && !Acl::is_manager(&req.perm) {
+        finish!(JsonResponse::new_code(403));
+    }
+    let tunnel = match PLUGIN_INSTANCE
+        .tunnel
+        .open(&aid, req.uid, &param.host, param.port, bind)
+        .await
+    {
+        Ok(x) => x,
+        Err(e) => finish!(JsonResponse::bad_request(e.to_string())),
+    };
+
+    info!(
+        success = true,
+        tid = %tunnel.id,
+        aid = %aid,
+        host = param.host,
+        port = param.port,
+        listen = %tunnel.listen,
+        ip = %req.extension.real_ip.ip(),
+        "Open monitor tunnel",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(tunnel))
+}
+
+pub async fn delete_tunnel(tid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let Some(x) = PLUGIN_INSTANCE.tunnel.get(&tid) else {
+        finish!(JsonResponse::not_found());
+    };
+    if !tunnel_owner(&req, &x) {
+        finish!(JsonResponse::new_code(403));
+    }
+    PLUGIN_INSTANCE.tunnel.close(&tid);
+
+    info!(
+        success = true,
+        tid = %tid,
+        aid = %x.aid,
+        ip = %req.extension.real_ip.ip(),
+        "Close monitor tunnel",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success))
+}
+
+async fn reload_socks() -> Result<()> {
+    PLUGIN_INSTANCE
+        .socks
+        .load(SocksUserViewer::find_all(PLUGIN_INSTANCE.db.get().unwrap()).await?);
+    Ok(())
+}
+
+pub async fn get_socks_users() -> RspResult<JsonResponse> {
+    finish!(
+        JsonResponse::new(MonitorResponse::Success)
+            .json(SocksUserViewer::find_all(PLUGIN_INSTANCE.db.get().unwrap()).await?)
+    )
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct PutSocksUserReq {
+    #[validate(
+        length(min = 1, max = 32),
+        custom(function = "socks::username_validator")
+    )]
+    pub username: String,
+    pub enable: bool,
+}
+
+pub async fn put_socks_user(
+    uid: Path<HyUuid>,
+    req: Request,
+    param: Json<PutSocksUserReq>,
+) -> RspResult<JsonResponse> {
+    #[derive(Serialize)]
+    struct Rsp {
+        #[serde(flatten)]
+        user: socks_users::Model,
+        /// Generated password, only returned when created.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    }
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    if SocksUserViewer::find_by_username(&tx, &param.username)
+        .await?
+        .is_some_and(|x| x.uid != *uid)
+    {
+        finish!(JsonResponse::new(MonitorResponse::SocksUserExist));
+    }
+    let rsp = if let Some(x) = SocksUserViewer::find_by_uid(&tx, &uid).await? {
+        Rsp {
+            user: SocksUserViewer::update(&tx, &x.id, &param.username, param.enable).await?,
+            password: None,
+        }
+    } else {
+        let password = Password::generate();
+        Rsp {
+            user: SocksUserViewer::create(
+                &tx,
+                &uid,
+                &param.username,
+                &password.hash,
+                &password.salt,
+                param.enable,
+            )
+            .await?,
+            password: Some(password.password),
+        }
+    };
+    tx.commit().await?;
+    reload_socks().await?;
+
+    info!(
+        success = true,
+        uid = %uid,
+        username = param.username,
+        enable = param.enable,
+        ip = %req.extension.real_ip.ip(),
+        "Put monitor socks user",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rsp))
+}
+
+pub async fn delete_socks_user(uid: Path<HyUuid>, req: Request) -> RspResult<JsonResponse> {
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    let Some(x) = SocksUserViewer::find_by_uid(&tx, &uid).await? else {
+        finish!(JsonResponse::not_found());
+    };
+    let rows = SocksUserViewer::delete(&tx, &[x.id]).await?;
+    tx.commit().await?;
+    reload_socks().await?;
+
+    info!(
+        success = true,
+        uid = %uid,
+        ip = %req.extension.real_ip.ip(),
+        "Delete monitor socks user",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(rows))
+}
+
+/// Reset socks password of the current user, only available when enabled.
+pub async fn reset_socks_password(req: Request) -> RspResult<JsonResponse> {
+    let Some(uid) = req.uid else {
+        finish!(JsonResponse::new_code(403));
+    };
+    let tx = PLUGIN_INSTANCE.db.get().unwrap().begin().await?;
+    let Some(x) = SocksUserViewer::find_by_uid(&tx, &uid)
+        .await?
+        .filter(|x| x.enable)
+    else {
+        finish!(JsonResponse::new_code(403));
+    };
+    let password = Password::generate();
+    SocksUserViewer::set_password(&tx, &x.id, &password.hash, &password.salt).await?;
+    tx.commit().await?;
+    reload_socks().await?;
+
+    info!(
+        success = true,
+        uid = %uid,
+        ip = %req.extension.real_ip.ip(),
+        "Reset monitor socks password",
+    );
+    finish!(JsonResponse::new(MonitorResponse::Success).json(password.password))
+}
+
+pub async fn get_socks_traffic() -> RspResult<JsonResponse> {
+    finish!(JsonResponse::new(MonitorResponse::Success).json(PLUGIN_INSTANCE.socks.traffic()))
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, sync::Arc};
+
+use actix_cloud::{
+    chrono::Utc,
+    tokio::{spawn, sync::Semaphore},
+};
+use serde::{Deserialize, Serialize};
+use serde_repr::Serialize_repr;
+use skynet_api::{HyUuid, anyhow, request::unique_validator, service::SResult};
+use skynet_api_monitor::{Agent, AgentStatus, CommandOption, entity::schedules};
+use validator::{Validate, ValidationError};
+
+use crate::{PLUGIN_INSTANCE, scheduler, script};
+
+const MAX_BATCH_JOB: usize = 64;
+
+/// # Errors
+/// Will return `Err` when `x` has empty or too long labels.
+pub fn label_validator(x: &[String]) -> Result<(), ValidationError> {
+    if x.iter().all(|x| !x.is_empty() && x.chars().count() <= 32) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid label"))
+    }
+}
+
+fn target_validator(x: &Target) -> Result<(), ValidationError> {
+    if x.id.is_empty()
+        && x.os.is_empty()
+        && x.arch.is_empty()
+        && x.status.is_empty()
+        && x.label.is_empty()
+    {
+        Err(ValidationError::new("empty target"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Agent target set, all non-empty criteria should be matched.
+#[derive(Debug, Clone, Default, Validate, Serialize, Deserialize)]
+#[validate(schema(function = "target_validator"))]
+#[serde(default)]
+pub struct Target {
+    #[validate(length(max = 1024), custom(function = "unique_validator"))]
+    pub id: Vec<HyUuid>,
+    pub os: Vec<String>,
+    pub arch: Vec<String>,
+    #[validate(custom(function = "unique_validator"))]
+    pub status: Vec<AgentStatus>,
+    /// Agents should have all labels.
+    #[validate(custom(function = "label_validator"))]
+    pub label: Vec<String>,
+}
+
+impl Target {
+    pub fn matches(&self, agent: &Agent) -> bool {
+        let opt = |v: &[String], x: &Option<String>| {
+            v.is_empty() || x.as_ref().is_some_and(|x| v.contains(x))
+        };
+        (self.id.is_empty() || self.id.contains(&agent.id))
+            && opt(&self.os, &agent.os)
+            && opt(&self.arch, &agent.arch)
+            && (self.status.is_empty() || self.status.contains(&agent.status))
+            && self.label.iter().all(|x| agent.labels.contains(x))
+    }
+
+    /// Get matched agent ids.
+    pub fn resolve(&self) -> Vec<HyUuid> {
+        PLUGIN_INSTANCE
+            .agent
+            .iter()
+            .filter(|x| self.matches(x))
+            .map(|x| x.id)
+            .collect()
+    }
+}
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum BatchStatus {
+    #[default]
+    Queued = 0,
+    Running,
+    Success,
+    Failed,
+    Offline,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct BatchResult {
+    pub status: BatchStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<HyUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub offline: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BatchJob {
+    pub id: HyUuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<HyUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<HyUuid>, // schedule triggering this batch
+    pub cmd: String,
+    pub concurrency: u32,
+    pub result: HashMap<HyUuid, BatchResult>,
+    pub created_at: i64,
+}
+
+impl BatchJob {
+    pub fn summary(&self) -> BatchSummary {
+        let mut ret = BatchSummary {
+            total: self.result.len(),
+            ..Default::default()
+        };
+        for i in self.result.values() {
+            *match i.status {
+                BatchStatus::Queued => &mut ret.queued,
+                BatchStatus::Running => &mut ret.running,
+                BatchStatus::Success => &mut ret.success,
+                BatchStatus::Failed => &mut ret.failed,
+                BatchStatus::Offline => &mut ret.offline,
+            } += 1;
+        }
+        ret
+    }
+
+    fn update(id: &HyUuid, aid: &HyUuid, result: BatchResult) {
+        if let Some(mut x) = PLUGIN_INSTANCE.batch.get_mut(id) {
+            x.result.insert(*aid, result);
+        }
+    }
+}
+
+/// Work done on each agent of a batch.
+#[derive(Debug, Clone)]
+pub enum Task {
+    Command {
+        cmd: String,
+        option: CommandOption,
+    },
+    /// Upload script `body` then run it with `interpreter`.
+    Script {
+        name: String,
+        interpreter: String,
+        body: Arc<Vec<u8>>,
+        option: CommandOption,
+    },
+}
+
+impl Task {
+    fn describe(&self) -> String {
+        match self {
+            Self::Command { cmd, .. } => cmd.to_owned(),
+            Self::Script {
+                name, interpreter, ..
+            } => format!("{interpreter} <{name}>"),
+        }
+    }
+
+    /// Start task on agent `aid` issued by user `uid`, return command id.
+    async fn start(&self, aid: &HyUuid, uid: Option<HyUuid>) -> SResult<HyUuid> {
+        match self {
+            Self::Command { cmd, option } => {
+                PLUGIN_INSTANCE
+                    .send_command(aid, uid, cmd, option.clone(), None)
+                    .await
+            }
+            Self::Script {
+                interpreter,
+                body,
+                option,
+                ..
+            } => script::start(aid, uid, interpreter, body, option.clone()).await,
+        }
+    }
+}
+
+/// Run `task` issued by `uid` on agents `aid` with at most `concurrency` tasks running.
+///
+/// Final results are recorded to `schedule` when set. Return batch id.
+pub fn run(
+    aid: Vec<HyUuid>,
+    uid: Option<HyUuid>,
+    task: Task,
+    concurrency: u32,
+    schedule: Option<Arc<schedules::Model>>,
+) -> HyUuid {
+    let mut job = BatchJob {
+        id: HyUuid::new(),
+        uid,
+        sid: schedule.as_ref().map(|x| x.id),
+        cmd: task.describe(),
+        concurrency,
+        result: HashMap::new(),
+        created_at: Utc::now().timestamp_millis(),
+    };
+    let mut online = Vec::new();
+    for i in aid {
+        let status = if PLUGIN_INSTANCE
+            .agent
+            .get(&i)
+            .is_some_and(|x| x.message.is_some())
+        {
+            online.push(i);
+            BatchStatus::Queued
+        } else {
+            BatchStatus::Offline
+        };
+        job.result.insert(
+            i,
+            BatchResult {
+                status,
+                ..Default::default()
+            },
+        );
+    }
+    let id = job.id;
+    if let Some(schedule) = &schedule {
+        let offline: Vec<(HyUuid, BatchResult)> = job
+            .result
+            .iter()
+            .filter(|x| x.1.status == BatchStatus::Offline)
+            .map(|(k, v)| (*k, v.clone()))
+            .collect();
+        let schedule = schedule.clone();
+        spawn(async move {
+            for (aid, result) in offline {
+                scheduler::record(&schedule, &id, &aid, &result).await;
+            }
+        });
+    }
+    PLUGIN_INSTANCE.batch.insert(id, job);
+    if PLUGIN_INSTANCE.batch.len() > MAX_BATCH_JOB {
+        let oldest = PLUGIN_INSTANCE
+            .batch
+            .iter()
+            .min_by_key(|x| x.created_at)
+            .map(|x| x.id);
+        if let Some(x) = oldest {
+            PLUGIN_INSTANCE.batch.remove(&x);
+        }
+    }
+
+    let semaphore = Arc::new(Semaphore::new(concurrency as usize));
+    for aid in online {
+        let semaphore = semaphore.clone();
+        let task = task.clone();
+        let schedule = schedule.clone();
+        spawn(async move {
+            let Ok(_permit) = semaphore.acquire().await else {
+                return;
+            };
+            let cid = match task.start(&aid, uid).await {
+                Ok(x) => x,
+                Err(e) => {
+                    let status = if PLUGIN_INSTANCE
+                        .agent
+                        .get(&aid)
+                        .is_some_and(|x| x.message.is_some())
+                    {
+                        BatchStatus::Failed
+                    } else {
+                        BatchStatus::Offline
+                    };
+                    let result = BatchResult {
+                        status,
+                        message: anyhow::Error::from(e).to_string(),
+                        ..Default::default()
+                    };
+                    if let Some(schedule) = &schedule {
+                        scheduler::record(schedule, &id, &aid, &result).await;
+                    }
+                    BatchJob::update(&id, &aid, result);
+                    return;
+                }
+            };
+            BatchJob::update(
+                &id,
+                &aid,
+                BatchResult {
+                    status: BatchStatus::Running,
+                    cid: Some(cid),
+                    ..Default::default()
+                },
+            );
+            let result = match PLUGIN_INSTANCE.command.wait(&cid, None).await {
+                Ok(x) => BatchResult {
+                    status: if x.code == Some(0) {
+                        BatchStatus::Success
+                    } else {
+                        BatchStatus::Failed
+                    },
+                    cid: Some(cid),
+                    code: x.code,
+                    message: String::new(),
+                },
+                Err(e) => BatchResult {
+                    status: BatchStatus::Failed,
+                    cid: Some(cid),
+                    code: None,
+                    message: e.to_string(),
+                },
+            };
+            if let Some(schedule) = &schedule {
+                scheduler::record(schedule, &id, &aid, &result).await;
+            }
+            BatchJob::update(&id, &aid, result);
+        });
+    }
+    id
+}
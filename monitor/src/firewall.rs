@@ -0,0 +1,350 @@
+use std::{
+    fmt,
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use derivative::Derivative;
+use ipnet::IpNet;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::proxy::{cidr_contains, cidr_validator, parse_cidr};
+
+#[derive(Derivative, Serialize, Deserialize, Validate, Clone, Debug)]
+#[derivative(Default(new = "true"))]
+#[serde(default)]
+pub struct FirewallSetting {
+    /// Allowed CIDR list, empty to allow all.
+    #[validate(custom(function = "cidr_validator"))]
+    pub allow: Vec<String>,
+    /// Denied CIDR list, checked before `allow`.
+    #[validate(custom(function = "cidr_validator"))]
+    pub deny: Vec<String>,
+    /// Max concurrent connections per IP, 0 for unlimited.
+    pub max_conn: u32,
+    /// Max failed handshakes per IP in `handshake_window`, 0 for unlimited.
+    pub handshake_limit: u32,
+    /// Failed handshake window, unit seconds.
+    #[derivative(Default(value = "60"))]
+    #[validate(range(min = 1))]
+    pub handshake_window: u32,
+    /// Ban time when exceeding `handshake_limit`, unit seconds.
+    #[derivative(Default(value = "600"))]
+    pub ban_time: u32,
+}
+
+#[derive(Default, Serialize, Clone, Debug)]
+pub struct FirewallCounter {
+    pub accepted: u64,
+    pub denied: u64,
+    pub limited: u64,
+    pub banned: u64,
+    pub handshake_failed: u64,
+    pub banned_ip: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reject {
+    Denied,
+    Limited,
+    Banned,
+}
+
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Denied => "IP denied",
+            Self::Limited => "Too many connections",
+            Self::Banned => "IP banned",
+        })
+    }
+}
+
+struct IpState {
+    conn: u32,
+    window_start: Instant,
+    failed: u32,
+    banned_until: Option<Instant>,
+}
+
+impl IpState {
+    fn new(now: Instant) -> Self {
+        Self {
+            conn: 0,
+            window_start: now,
+            failed: 0,
+            banned_until: None,
+        }
+    }
+
+    fn is_banned(&self, now: Instant) -> bool {
+        self.banned_until.is_some_and(|x| x > now)
+    }
+}
+
+struct Rule {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    setting: FirewallSetting,
+}
+
+impl From<FirewallSetting> for Rule {
+    fn from(setting: FirewallSetting) -> Self {
+        Self {
+            allow: setting.allow.iter().filter_map(|x| parse_cidr(x)).collect(),
+            deny: setting.deny.iter().filter_map(|x| parse_cidr(x)).collect(),
+            setting,
+        }
+    }
+}
+
+/// Connection guard for agent listener.
+pub struct Firewall {
+    rule: RwLock<Rule>,
+    state: DashMap<IpAddr, IpState>,
+    accepted: AtomicU64,
+    denied: AtomicU64,
+    limited: AtomicU64,
+    banned: AtomicU64,
+    handshake_failed: AtomicU64,
+}
+
+impl Default for Firewall {
+    fn default() -> Self {
+        Self {
+            rule: RwLock::new(FirewallSetting::new().into()),
+            state: Default::default(),
+            accepted: Default::default(),
+            denied: Default::default(),
+            limited: Default::default(),
+            banned: Default::default(),
+            handshake_failed: Default::default(),
+        }
+    }
+}
+
+impl Firewall {
+    pub fn set(&self, setting: FirewallSetting) {
+        *self.rule.write() = setting.into();
+    }
+
+    /// Check whether `ip` is denied or banned, without counting the connection.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Reject> {
+        let rule = self.rule.read();
+        self.check_rule(&rule, ip)?;
+        let now = Instant::now();
+        if self.state.get(&ip).is_some_and(|x| x.is_banned(now)) {
+            self.banned.fetch_add(1, Ordering::Relaxed);
+            return Err(Reject::Banned);
+        }
+        Ok(())
+    }
+
+    fn check_rule(&self, rule: &Rule, ip: IpAddr) -> Result<(), Reject> {
+        if cidr_contains(&rule.deny, &ip)
+            || (!rule.allow.is_empty() && !cidr_contains(&rule.allow, &ip))
+        {
+            self.denied.fetch_add(1, Ordering::Relaxed);
+            return Err(Reject::Denied);
+        }
+        Ok(())
+    }
+
+    /// Check incoming connection from `ip`.
+    ///
+    /// The connection is counted until returned guard is dropped.
+    pub fn accept(&self, ip: IpAddr) -> Result<ConnGuard<'_>, Reject> {
+        let rule = self.rule.read();
+        self.check_rule(&rule, ip)?;
+
+        let now = Instant::now();
+        let mut state = self.state.entry(ip).or_insert_with(|| IpState::new(now));
+        if state.is_banned(now) {
+            self.banned.fetch_add(1, Ordering::Relaxed);
+            return Err(Reject::Banned);
+        }
+        let setting = &rule.setting;
+        if setting.max_conn != 0 && state.conn >= setting.max_conn {
+            self.limited.fetch_add(1, Ordering::Relaxed);
+            return Err(Reject::Limited);
+        }
+        state.conn += 1;
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+        Ok(ConnGuard { firewall: self, ip })
+    }
+
+    /// Handshake from `ip` failed, `ip` is banned when exceeding `handshake_limit`.
+    pub fn handshake_failed(&self, ip: IpAddr) {
+        self.handshake_failed.fetch_add(1, Ordering::Relaxed);
+        let setting = &self.rule.read().setting;
+        if setting.handshake_limit == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.entry(ip).or_insert_with(|| IpState::new(now));
+        let window = Duration::from_secs(setting.handshake_window.into());
+        if now.duration_since(state.window_start) >= window {
+            state.window_start = now;
+            state.failed = 0;
+        }
+        state.failed += 1;
+        if state.failed > setting.handshake_limit && !state.is_banned(now) {
+            state.banned_until = Some(now + Duration::from_secs(setting.ban_time.into()));
+        }
+    }
+
+    /// Remove idle IP states.
+    pub fn purge(&self) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.rule.read().setting.handshake_window.into());
+        self.state.retain(|_, v| {
+            v.conn != 0 || v.is_banned(now) || now.duration_since(v.window_start) < window
+        });
+    }
+
+    /// Unban all IPs.
+    pub fn unban(&self) {
+        for mut i in self.state.iter_mut() {
+            i.banned_until = None;
+        }
+    }
+
+    pub fn counter(&self) -> FirewallCounter {
+        let now = Instant::now();
+        FirewallCounter {
+            accepted: self.accepted.load(Ordering::Relaxed),
+            denied: self.denied.load(Ordering::Relaxed),
+            limited: self.limited.load(Ordering::Relaxed),
+            banned: self.banned.load(Ordering::Relaxed),
+            handshake_failed: self.handshake_failed.load(Ordering::Relaxed),
+            banned_ip: self
+                .state
+                .iter()
+                .filter(|x| x.is_banned(now))
+                .map(|x| x.key().to_string())
+                .collect(),
+        }
+    }
+}
+
+pub struct ConnGuard<'a> {
+    firewall: &'a Firewall,
+    ip: IpAddr,
+}
+
+impl Drop for ConnGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(mut x) = self.firewall.state.get_mut(&self.ip) {
+            x.conn = x.conn.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firewall(setting: FirewallSetting) -> Firewall {
+        let ret = Firewall::default();
+        ret.set(setting);
+        ret
+    }
+
+    #[test]
+    fn cidr() {
+        let fw = firewall(FirewallSetting {
+            allow: vec!["10.0.0.0/8".to_owned()],
+            deny: vec!["10.0.0.1".to_owned()],
+            ..Default::default()
+        });
+        assert!(fw.accept("10.1.1.1".parse().unwrap()).is_ok());
+        assert_eq!(
+            fw.accept("10.0.0.1".parse().unwrap()).err(),
+            Some(Reject::Denied)
+        );
+        assert_eq!(
+            fw.accept("192.168.0.1".parse().unwrap()).err(),
+            Some(Reject::Denied)
+        );
+        let counter = fw.counter();
+        assert_eq!(counter.accepted, 1);
+        assert_eq!(counter.denied, 2);
+    }
+
+    #[test]
+    fn max_conn() {
+        let fw = firewall(FirewallSetting {
+            max_conn: 2,
+            ..Default::default()
+        });
+        let ip = "10.0.0.1".parse().unwrap();
+        let a = fw.accept(ip).unwrap();
+        let _b = fw.accept(ip).unwrap();
+        assert_eq!(fw.accept(ip).err(), Some(Reject::Limited));
+        assert!(fw.accept("10.0.0.2".parse().unwrap()).is_ok());
+        drop(a);
+        assert!(fw.accept(ip).is_ok());
+    }
+
+    #[test]
+    fn handshake_ban() {
+        let fw = firewall(FirewallSetting {
+            handshake_limit: 2,
+            ..Default::default()
+        });
+        let ip = "10.0.0.1".parse().unwrap();
+        // Successful connections are not counted.
+        for _ in 0..5 {
+            assert!(fw.accept(ip).is_ok());
+        }
+        fw.handshake_failed(ip);
+        fw.handshake_failed(ip);
+        assert!(fw.accept(ip).is_ok());
+        fw.handshake_failed(ip);
+        assert_eq!(fw.accept(ip).err(), Some(Reject::Banned));
+        assert!(fw.accept("10.0.0.2".parse().unwrap()).is_ok());
+        let counter = fw.counter();
+        assert_eq!(counter.handshake_failed, 3);
+        assert_eq!(counter.banned, 1);
+        assert_eq!(counter.banned_ip, vec!["10.0.0.1".to_owned()]);
+        fw.unban();
+        assert!(fw.accept(ip).is_ok());
+    }
+
+    #[test]
+    fn check() {
+        let fw = firewall(FirewallSetting {
+            deny: vec!["10.0.0.1".to_owned()],
+            max_conn: 1,
+            handshake_limit: 1,
+            ..Default::default()
+        });
+        let ip = "10.0.0.2".parse().unwrap();
+        let _a = fw.accept(ip).unwrap();
+        // Connections are not counted by check.
+        assert!(fw.check(ip).is_ok());
+        assert_eq!(
+            fw.check("10.0.0.1".parse().unwrap()).err(),
+            Some(Reject::Denied)
+        );
+        fw.handshake_failed(ip);
+        fw.handshake_failed(ip);
+        assert_eq!(fw.check(ip).err(), Some(Reject::Banned));
+        assert!(fw.check("10.0.0.3".parse().unwrap()).is_ok());
+        assert_eq!(fw.counter().accepted, 1);
+    }
+
+    #[test]
+    fn handshake_unlimited() {
+        let fw = Firewall::default();
+        let ip = "10.0.0.1".parse().unwrap();
+        for _ in 0..100 {
+            fw.handshake_failed(ip);
+        }
+        assert!(fw.accept(ip).is_ok());
+    }
+}
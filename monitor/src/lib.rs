@@ -1,8 +1,10 @@
 use std::{
+    collections::HashMap,
     path::PathBuf,
     sync::{Arc, OnceLock},
 };
 
+use acl::Acl;
 use actix_cloud::{
     actix_web::web::Data,
     i18n::{Locale, i18n},
@@ -12,12 +14,21 @@ use actix_cloud::{
     tokio,
     tokio::spawn,
 };
+use batch::BatchJob;
 use dashmap::DashMap;
 use ecies::utils::generate_keypair;
+use firewall::{Firewall, FirewallSetting};
+use ipnet::IpNet;
+use limit::Limiter;
+use migrate::MigrateJob;
 use migration::migrator::Migrator;
 use parking_lot::RwLock;
+use recording::Recording;
+use rpc::Rpc;
+use scheduler::Scheduler;
 use sea_orm_migration::MigratorTrait;
 use server::Server;
+use session::{Sessions, ShellLimit};
 use skynet_api::{
     HyUuid, MenuItem, Skynet,
     ffi_rpc::{
@@ -41,13 +52,37 @@ use skynet_api::{
     viewer::permissions::PermissionViewer,
 };
 use skynet_api_agent::semver::VersionReq;
-use skynet_api_monitor::{Agent, ID};
-use ws::ShellService;
+use skynet_api_monitor::{
+    Agent, AgentCommand, AgentFile, ID, MigrateRspMessage,
+    viewer::{
+        acls::AclViewer, jobs::JobViewer, shell_recordings::ShellRecordingViewer,
+        shell_sessions::ShellSessionViewer, socks_users::SocksUserViewer,
+    },
+};
+use socks::{Socks, SocksSetting};
+use stream::Streams;
+use tunnel::Tunnels;
+use ws::{ShellBinding, ShellService};
 
+mod acl;
 mod api;
+mod batch;
+mod firewall;
+mod limit;
+mod migrate;
 mod migration;
+mod proxy;
+mod recording;
+mod rpc;
+mod scheduler;
+mod script;
 mod server;
 mod service;
+mod session;
+mod socks;
+mod stream;
+mod tunnel;
+mod utils;
 mod ws;
 
 include!(concat!(env!("OUT_DIR"), "/response.rs"));
@@ -62,10 +97,31 @@ static WEBPUSH_ALERT: HyUuid = HyUuid(uuid!("725e32b0-910d-4b33-81e2-13a851bef41
     agent: Default::default(),
     view_id: Default::default(),
     manage_id: Default::default(),
+    exec_id: Default::default(),
     db: Default::default(),
     state: Default::default(),
     msg_timeout: RwLock::new(0),
     alert_timeout: RwLock::new(0),
+    proxy_protocol: RwLock::new(false),
+    proxy_trusted: Default::default(),
+    firewall: Default::default(),
+    migrate: Default::default(),
+    command: Default::default(),
+    limiter: Default::default(),
+    recording: Default::default(),
+    session: Default::default(),
+    acl: Default::default(),
+    file: Default::default(),
+    migrate_rpc: Default::default(),
+    job_retention: RwLock::new(0),
+    shell_grace: RwLock::new(0),
+    shell_prog: Default::default(),
+    agent_shell: Default::default(),
+    stream: Default::default(),
+    tunnel: Default::default(),
+    socks: Default::default(),
+    batch: Default::default(),
+    scheduler: Scheduler::new(),
 })]
 #[plugin_impl_root]
 #[plugin_impl_call(skynet_api::plugin::api::PluginApi, skynet_api_monitor::Service)]
@@ -73,14 +129,35 @@ struct Plugin {
     server: Server,
     agent_api: OnceLock<skynet_api_agent::AgentService>,
     shell: DashMap<HyUuid, ShellService>,
-    shell_binding: DashMap<HyUuid, HyUuid>,
+    shell_binding: DashMap<HyUuid, ShellBinding>,
     agent: DashMap<HyUuid, Agent>,
     view_id: OnceLock<HyUuid>,
     manage_id: OnceLock<HyUuid>,
+    exec_id: OnceLock<HyUuid>,
     db: OnceLock<DatabaseConnection>,
     state: OnceLock<Data<GlobalState>>,
     msg_timeout: RwLock<u32>,
     alert_timeout: RwLock<u32>,
+    proxy_protocol: RwLock<bool>,
+    proxy_trusted: RwLock<Vec<IpNet>>,
+    firewall: Firewall,
+    migrate: DashMap<HyUuid, MigrateJob>,
+    command: Rpc<AgentCommand>,
+    limiter: Limiter,
+    recording: Recording,
+    session: Sessions,
+    acl: Acl,
+    file: Rpc<AgentFile>,
+    migrate_rpc: Rpc<MigrateRspMessage>,
+    job_retention: RwLock<u32>,
+    shell_grace: RwLock<u32>,
+    shell_prog: RwLock<Vec<String>>,
+    agent_shell: DashMap<HyUuid, Vec<String>>,
+    stream: Streams,
+    tunnel: Tunnels,
+    socks: Socks,
+    batch: DashMap<HyUuid, BatchJob>,
+    scheduler: Scheduler,
 }
 
 #[plugin_impl_trait]
@@ -89,7 +166,7 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
         &self,
         reg: &Registry,
         mut skynet: Skynet,
-        _runtime_path: PathBuf,
+        runtime_path: PathBuf,
     ) -> SResult<Skynet> {
         let skynet_service: Service = reg.get(SKYNET_SERVICE).unwrap().into();
         self.server.init(skynet_service.clone());
@@ -120,18 +197,19 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
             Plugin::set_setting_address(&tx, ret).await?;
             ret.to_string()
         };
-        if Plugin::get_setting_shell(&tx).await?.is_none() {
+        let shell = if let Some(x) = Plugin::get_setting_shell(&tx).await? {
+            x
+        } else {
             info!("Shell program not found, using default");
-            Plugin::set_setting_shell(
-                &tx,
-                &[
-                    String::from("/bin/bash"),
-                    String::from("/bin/sh"),
-                    String::from("C:\\Windows\\System32\\cmd.exe"),
-                ],
-            )
-            .await?;
-        }
+            let ret = vec![
+                String::from("/bin/bash"),
+                String::from("/bin/sh"),
+                String::from("C:\\Windows\\System32\\cmd.exe"),
+            ];
+            Plugin::set_setting_shell(&tx, &ret).await?;
+            ret
+        };
+        *self.shell_prog.write() = shell;
         let key = if let Some(x) = Plugin::get_setting_certificate(&tx).await? {
             x
         } else {
@@ -154,6 +232,97 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
             30
         };
         *self.msg_timeout.write() = timeout;
+        let enable = if let Some(x) = Plugin::get_setting_proxy_protocol(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_proxy_protocol(&tx, false).await?;
+            false
+        };
+        *self.proxy_protocol.write() = enable;
+        let trusted = if let Some(x) = Plugin::get_setting_proxy_trusted(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_proxy_trusted(&tx, &[]).await?;
+            Vec::new()
+        };
+        *self.proxy_trusted.write() = trusted
+            .iter()
+            .filter_map(|x| proxy::parse_cidr(x))
+            .collect();
+        let firewall = if let Some(x) = Plugin::get_setting_firewall(&tx).await? {
+            x
+        } else {
+            let ret = FirewallSetting::new();
+            Plugin::set_setting_firewall(&tx, &ret).await?;
+            ret
+        };
+        self.firewall.set(firewall);
+        let retention = if let Some(x) = Plugin::get_setting_job_retention(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_job_retention(&tx, 30).await?;
+            30
+        };
+        *self.job_retention.write() = retention;
+        let record = if let Some(x) = Plugin::get_setting_shell_record(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_shell_record(&tx, false).await?;
+            false
+        };
+        self.recording.set_enable(record);
+        self.recording.init(runtime_path.join("recordings"))?;
+        let grace = if let Some(x) = Plugin::get_setting_shell_grace(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_shell_grace(&tx, 0).await?;
+            0
+        };
+        *self.shell_grace.write() = grace;
+        let limit = if let Some(x) = Plugin::get_setting_shell_limit(&tx).await? {
+            x
+        } else {
+            let ret = ShellLimit::default();
+            Plugin::set_setting_shell_limit(&tx, &ret).await?;
+            ret
+        };
+        self.session.set_limit(limit);
+        let limit = if let Some(x) = Plugin::get_setting_command_group_limit(&tx).await? {
+            x
+        } else {
+            let ret = HashMap::new();
+            Plugin::set_setting_command_group_limit(&tx, &ret).await?;
+            ret
+        };
+        self.limiter.set_group_limit(limit);
+        let enable = if let Some(x) = Plugin::get_setting_acl(&tx).await? {
+            x
+        } else {
+            Plugin::set_setting_acl(&tx, false).await?;
+            false
+        };
+        self.acl.set_enable(enable);
+        self.acl.load(AclViewer::find_all(&tx).await?);
+        let socks = if let Some(x) = Plugin::get_setting_socks(&tx).await? {
+            x
+        } else {
+            let ret = SocksSetting::new();
+            Plugin::set_setting_socks(&tx, &ret).await?;
+            ret
+        };
+        self.socks.load(SocksUserViewer::find_all(&tx).await?);
+        let rows = JobViewer::abort_running(&tx).await?;
+        if rows != 0 {
+            warn!(plugin = %ID, rows, "Running jobs lost, marked as disconnected");
+        }
+        let rows = ShellSessionViewer::abort_running(&tx).await?;
+        if rows != 0 {
+            warn!(plugin = %ID, rows, "Shell sessions lost, marked as finished");
+        }
+        let rows = ShellRecordingViewer::abort_running(&tx).await?;
+        if rows != 0 {
+            warn!(plugin = %ID, rows, "Shell recordings lost, marked as finished");
+        }
         let _ = self.view_id.set(
             PermissionViewer::find_or_init(&tx, &format!("view.{ID}"), "plugin monitor viewer")
                 .await?
@@ -164,6 +333,11 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
                 .await?
                 .id,
         );
+        let _ = self.exec_id.set(
+            PermissionViewer::find_or_init(&tx, &format!("exec.{ID}"), "plugin monitor executor")
+                .await?
+                .id,
+        );
         self.init_agent(&tx).await?;
         tx.commit().await?;
 
@@ -208,6 +382,20 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
                 .await
                 .map_err(|e| error!(address=addr, error=%e, "Failed to start server"))
         });
+        spawn(async move {
+            PLUGIN_INSTANCE
+                .socks
+                .start(&socks)
+                .await
+                .map_err(|e| error!(address=%socks.address, error=%e, "Failed to start socks"))
+        });
+        spawn(async move {
+            PLUGIN_INSTANCE
+                .scheduler
+                .start()
+                .await
+                .map_err(|e| error!(error=%e, "Failed to start scheduler"))
+        });
 
         let locale = Locale::new(skynet.config.lang.clone()).add_locale(i18n!("locales"));
         let state = GlobalState {
@@ -225,6 +413,7 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
     async fn on_register(&self, _: &Registry, _skynet: Skynet, mut r: Vec<Router>) -> Vec<Router> {
         let view_id = *self.view_id.get().unwrap();
         let manage_id = *self.manage_id.get().unwrap();
+        let exec_id = *self.exec_id.get().unwrap();
         r.extend(vec![
             Router {
                 path: format!("/plugins/{ID}/ws"),
@@ -307,6 +496,34 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
                 checker: PermChecker::new_entry(manage_id, PERM_WRITE),
                 csrf: CSRFType::Header,
             },
+            Router {
+                path: format!("/plugins/{ID}/agents/{{aid}}/commands"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_agent_commands")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/agents/{{aid}}/commands"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_agent_command")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/agents/{{aid}}/commands/{{cid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_agent_command")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/agents/{{aid}}/commands/{{cid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_agent_command")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
             Router {
                 path: format!("/plugins/{ID}/agents/{{aid}}/reconnect"),
                 method: Method::Post,
@@ -314,6 +531,307 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
                 checker: PermChecker::new_entry(manage_id, PERM_WRITE),
                 csrf: CSRFType::Header,
             },
+            Router {
+                path: format!("/plugins/{ID}/migrations"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_migrations")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/migrations"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_migrations")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/migrations/{{mid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_migration")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/jobs"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_jobs")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/jobs/{{jid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_job")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/jobs/{{jid}}/queue"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_job_queue")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/batches"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_batches")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/batches"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_batches")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/batches/{{bid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_batch")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/batches/{{bid}}/output"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_batch_output")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_schedules")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_schedules")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules/{{sid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_schedule")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules/{{sid}}"),
+                method: Method::Put,
+                route: RouterType::Http(ID, String::from("api::put_schedule")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules/{{sid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_schedule")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules/{{sid}}/run"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::run_schedule")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/schedules/{{sid}}/runs"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_schedule_runs")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_scripts")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_scripts")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts/{{sid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_script")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts/{{sid}}"),
+                method: Method::Put,
+                route: RouterType::Http(ID, String::from("api::put_script")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts/{{sid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_script")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/scripts/{{sid}}/run"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::run_script")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/recordings"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_recordings")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/recordings/{{rid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_recording")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/recordings/{{rid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_recording")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/recordings/{{rid}}/download"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::download_recording")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/recordings/{{rid}}/replay"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::replay_recording")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/shells"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_shells")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/shells/{{sid}}"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_shell")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/shells/{{sid}}/terminate"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::terminate_shell")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/acls"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_acls")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/acls"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_acls")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/acls/{{id}}"),
+                method: Method::Put,
+                route: RouterType::Http(ID, String::from("api::put_acl")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/acls/{{id}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_acl")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/tunnels"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_tunnels")),
+                checker: PermChecker::new_entry(exec_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/agents/{{aid}}/tunnels"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::add_tunnels")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/tunnels/{{tid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_tunnel")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/socks/users"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_socks_users")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/socks/users/{{uid}}"),
+                method: Method::Put,
+                route: RouterType::Http(ID, String::from("api::put_socks_user")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/socks/users/{{uid}}"),
+                method: Method::Delete,
+                route: RouterType::Http(ID, String::from("api::delete_socks_user")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/socks/password"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::reset_socks_password")),
+                checker: PermChecker::new_entry(exec_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
+            Router {
+                path: format!("/plugins/{ID}/socks/traffic"),
+                method: Method::Get,
+                route: RouterType::Http(ID, String::from("api::get_socks_traffic")),
+                checker: PermChecker::new_entry(manage_id, PERM_READ),
+                csrf: CSRFType::Header,
+            },
             Router {
                 path: format!("/plugins/{ID}/settings"),
                 method: Method::Get,
@@ -335,6 +853,13 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
                 checker: PermChecker::new_entry(view_id, PERM_READ),
                 csrf: CSRFType::Header,
             },
+            Router {
+                path: format!("/plugins/{ID}/settings/firewall/unban"),
+                method: Method::Post,
+                route: RouterType::Http(ID, String::from("api::unban_settings_firewall")),
+                checker: PermChecker::new_entry(manage_id, PERM_WRITE),
+                csrf: CSRFType::Header,
+            },
             Router {
                 path: format!("/plugins/{ID}/settings/certificate"),
                 method: Method::Get,
@@ -374,9 +899,57 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
             "api::put_agent" => api::put_agent,
             "api::delete_agent" => api::delete_agent,
             "api::reconnect_agent" => api::reconnect_agent,
+            "api::get_migrations" => api::get_migrations,
+            "api::add_migrations" => api::add_migrations,
+            "api::get_migration" => api::get_migration,
+            "api::get_jobs" => api::get_jobs,
+            "api::get_job" => api::get_job,
+            "api::delete_job_queue" => api::delete_job_queue,
+            "api::add_agent_command" => api::add_agent_command,
+            "api::get_agent_commands" => api::get_agent_commands,
+            "api::get_agent_command" => api::get_agent_command,
+            "api::delete_agent_command" => api::delete_agent_command,
+            "api::get_batches" => api::get_batches,
+            "api::add_batches" => api::add_batches,
+            "api::get_batch" => api::get_batch,
+            "api::get_batch_output" => api::get_batch_output,
+            "api::get_schedules" => api::get_schedules,
+            "api::add_schedules" => api::add_schedules,
+            "api::get_schedule" => api::get_schedule,
+            "api::put_schedule" => api::put_schedule,
+            "api::delete_schedule" => api::delete_schedule,
+            "api::run_schedule" => api::run_schedule,
+            "api::get_schedule_runs" => api::get_schedule_runs,
+            "api::get_scripts" => api::get_scripts,
+            "api::add_scripts" => api::add_scripts,
+            "api::get_script" => api::get_script,
+            "api::put_script" => api::put_script,
+            "api::delete_script" => api::delete_script,
+            "api::run_script" => api::run_script,
+            "api::get_recordings" => api::get_recordings,
+            "api::get_recording" => api::get_recording,
+            "api::delete_recording" => api::delete_recording,
+            "api::download_recording" => api::download_recording,
+            "api::replay_recording" => api::replay_recording,
+            "api::get_shells" => api::get_shells,
+            "api::get_shell" => api::get_shell,
+            "api::terminate_shell" => api::terminate_shell,
+            "api::get_acls" => api::get_acls,
+            "api::add_acls" => api::add_acls,
+            "api::put_acl" => api::put_acl,
+            "api::delete_acl" => api::delete_acl,
+            "api::get_tunnels" => api::get_tunnels,
+            "api::add_tunnels" => api::add_tunnels,
+            "api::delete_tunnel" => api::delete_tunnel,
+            "api::get_socks_users" => api::get_socks_users,
+            "api::put_socks_user" => api::put_socks_user,
+            "api::delete_socks_user" => api::delete_socks_user,
+            "api::reset_socks_password" => api::reset_socks_password,
+            "api::get_socks_traffic" => api::get_socks_traffic,
             "api::get_settings" => api::get_settings,
             "api::put_settings" => api::put_settings,
             "api::get_settings_shell" => api::get_settings_shell,
+            "api::unban_settings_firewall" => api::unban_settings_firewall,
             "api::get_settings_certificate" => api::get_settings_certificate,
             "api::new_settings_certificate" => api::new_settings_certificate,
             "api::post_server" => api::post_server,
@@ -389,7 +962,19 @@ impl skynet_api::plugin::api::PluginApi for Plugin {
 
     async fn on_unload(&self, _: &Registry, _status: PluginStatus) {
         self.server.stop();
+        self.socks.stop().await;
+        self.tunnel.clear();
+        self.scheduler.stop();
         self.shell.clear();
+        self.shell_binding.clear();
+        self.session.clear();
+        self.recording.clear();
+        self.command.clear();
+        self.file.clear();
+        self.migrate_rpc.clear();
         self.agent.clear();
+        self.stream.clear();
+        self.migrate.clear();
+        self.batch.clear();
     }
 }
@@ -0,0 +1,381 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use actix_cloud::chrono::Utc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use skynet_api::HyUuid;
+use skynet_api_monitor::CommandOption;
+use validator::ValidationError;
+
+use crate::batch::label_validator;
+
+const MAX_LIMIT: u32 = 1024;
+
+/// # Errors
+/// Will return `Err` when `x` has invalid labels or limits.
+pub fn group_limit_validator(x: &HashMap<String, u32>) -> Result<(), ValidationError> {
+    let label: Vec<String> = x.keys().cloned().collect();
+    label_validator(&label)?;
+    if x.len() > 256 || x.values().any(|x| *x > MAX_LIMIT) {
+        return Err(ValidationError::new("invalid limit"));
+    }
+    Ok(())
+}
+
+/// Command waiting for a free slot.
+#[derive(Debug, Clone)]
+pub struct Pending {
+    pub cid: HyUuid,
+    pub cmd: String,
+    pub option: CommandOption,
+    /// Expired after `timeout` since dispatched.
+    pub timeout: Option<Duration>,
+    pub created_at: i64,
+}
+
+impl Pending {
+    pub fn new(cid: HyUuid, cmd: &str, option: CommandOption, timeout: Option<Duration>) -> Self {
+        Self {
+            cid,
+            cmd: cmd.to_owned(),
+            option,
+            timeout,
+            created_at: Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PendingInfo {
+    pub cid: HyUuid,
+    pub cmd: String,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LimitStatus {
+    pub limit: u32,
+    /// Limits of agent labels, only limited labels are included.
+    pub group: HashMap<String, u32>,
+    pub running: Vec<HyUuid>,
+    pub queued: Vec<PendingInfo>,
+}
+
+#[derive(Default)]
+struct Slot {
+    /// Running commands and the labels they are counted in.
+    running: Vec<(HyUuid, Vec<String>)>,
+    queue: VecDeque<Pending>,
+}
+
+#[derive(Default)]
+struct State {
+    limit: HashMap<HyUuid, u32>,
+    group_limit: HashMap<String, u32>,
+    label: HashMap<HyUuid, Vec<String>>,
+    group_running: HashMap<String, usize>,
+    slot: HashMap<HyUuid, Slot>,
+}
+
+impl State {
+    fn limit(&self, aid: &HyUuid) -> u32 {
+        self.limit.get(aid).copied().unwrap_or_default()
+    }
+
+    /// Limited labels of agent `aid`.
+    fn group(&self, aid: &HyUuid) -> Vec<String> {
+        self.label
+            .get(aid)
+            .map(|x| {
+                x.iter()
+                    .filter(|x| self.group_limit.contains_key(*x))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn is_free(&self, aid: &HyUuid, group: &[String]) -> bool {
+        let limit = self.limit(aid) as usize;
+        let running = self.slot.get(aid).map_or(0, |x| x.running.len());
+        (limit == 0 || running < limit)
+            && group.iter().all(|x| {
+                self.group_running.get(x).copied().unwrap_or_default()
+                    < self.group_limit.get(x).copied().unwrap_or_default() as usize
+            })
+    }
+
+    fn start(&mut self, aid: &HyUuid, cid: HyUuid, group: Vec<String>) {
+        for i in &group {
+            *self.group_running.entry(i.clone()).or_default() += 1;
+        }
+        self.slot
+            .entry(*aid)
+            .or_default()
+            .running
+            .push((cid, group));
+    }
+
+    fn stop(&mut self, group: &[String]) {
+        for i in group {
+            if let Some(x) = self.group_running.get_mut(i) {
+                *x = x.saturating_sub(1);
+                if *x == 0 {
+                    self.group_running.remove(i);
+                }
+            }
+        }
+    }
+
+    /// Start queued commands taking free slots in order.
+    fn drain(&mut self) -> Vec<(HyUuid, Pending)> {
+        let mut ret = Vec::new();
+        let aid: Vec<HyUuid> = self
+            .slot
+            .iter()
+            .filter(|x| !x.1.queue.is_empty())
+            .map(|x| *x.0)
+            .collect();
+        for i in aid {
+            loop {
+                let group = self.group(&i);
+                if !self.is_free(&i, &group) {
+                    break;
+                }
+                let Some(x) = self.slot.get_mut(&i).and_then(|x| x.queue.pop_front()) else {
+                    break;
+                };
+                self.start(&i, x.cid, group);
+                ret.push((i, x));
+            }
+        }
+        ret
+    }
+}
+
+/// Per-agent and per-label concurrent command limiter, excess commands are queued in order.
+///
+/// Commands returned by this limiter take slots and should be started by the caller.
+#[derive(Default)]
+pub struct Limiter {
+    state: Mutex<State>,
+}
+
+impl Limiter {
+    /// Set max concurrent commands of agent `aid`, 0 for unlimited.
+    pub fn set_limit(&self, aid: &HyUuid, limit: u32) -> Vec<(HyUuid, Pending)> {
+        let mut state = self.state.lock();
+        if limit == 0 {
+            state.limit.remove(aid);
+        } else {
+            state.limit.insert(*aid, limit);
+        }
+        state.drain()
+    }
+
+    /// Replace label limits with `limit`, 0 for unlimited.
+    pub fn set_group_limit(&self, limit: HashMap<String, u32>) -> Vec<(HyUuid, Pending)> {
+        let mut state = self.state.lock();
+        state.group_limit = limit.into_iter().filter(|x| x.1 != 0).collect();
+        state.drain()
+    }
+
+    /// Set labels of agent `aid`, running commands are still counted in old labels.
+    pub fn set_label(&self, aid: &HyUuid, label: &[String]) -> Vec<(HyUuid, Pending)> {
+        let mut state = self.state.lock();
+        state.label.insert(*aid, label.to_vec());
+        state.drain()
+    }
+
+    /// Acquire a slot on agent `aid` for command `x`.
+    ///
+    /// Return `x` when it can run now, otherwise it is queued.
+    pub fn acquire(&self, aid: &HyUuid, x: Pending) -> Option<Pending> {
+        let mut state = self.state.lock();
+        let group = state.group(aid);
+        if state.slot.get(aid).is_none_or(|x| x.queue.is_empty()) && state.is_free(aid, &group) {
+            state.start(aid, x.cid, group);
+            Some(x)
+        } else {
+            state.slot.entry(*aid).or_default().queue.push_back(x);
+            None
+        }
+    }
+
+    /// Release slot of finished command `cid` on agent `aid`.
+    pub fn release(&self, aid: &HyUuid, cid: &HyUuid) -> Vec<(HyUuid, Pending)> {
+        let mut state = self.state.lock();
+        let Some(slot) = state.slot.get_mut(aid) else {
+            return Vec::new();
+        };
+        let Some(pos) = slot.running.iter().position(|x| x.0 == *cid) else {
+            return Vec::new();
+        };
+        let (_, group) = slot.running.swap_remove(pos);
+        state.stop(&group);
+        state.drain()
+    }
+
+    /// Remove queued command `cid` of agent `aid`.
+    pub fn cancel(&self, aid: &HyUuid, cid: &HyUuid) -> bool {
+        self.state.lock().slot.get_mut(aid).is_some_and(|x| {
+            let len = x.queue.len();
+            x.queue.retain(|x| x.cid != *cid);
+            x.queue.len() != len
+        })
+    }
+
+    /// Drop all slots of agent `aid`.
+    pub fn clear(&self, aid: &HyUuid) -> Vec<(HyUuid, Pending)> {
+        let mut state = self.state.lock();
+        if let Some(x) = state.slot.remove(aid) {
+            for (_, group) in x.running {
+                state.stop(&group);
+            }
+        }
+        state.drain()
+    }
+
+    pub fn remove_agent(&self, aid: &HyUuid) -> Vec<(HyUuid, Pending)> {
+        let ret = self.clear(aid);
+        let mut state = self.state.lock();
+        state.limit.remove(aid);
+        state.label.remove(aid);
+        ret
+    }
+
+    pub fn status(&self, aid: &HyUuid) -> LimitStatus {
+        let state = self.state.lock();
+        let mut ret = LimitStatus {
+            limit: state.limit(aid),
+            group: state
+                .group(aid)
+                .into_iter()
+                .map(|x| {
+                    let limit = state.group_limit.get(&x).copied().unwrap_or_default();
+                    (x, limit)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        if let Some(x) = state.slot.get(aid) {
+            ret.running = x.running.iter().map(|x| x.0).collect();
+            ret.queued = x
+                .queue
+                .iter()
+                .map(|x| PendingInfo {
+                    cid: x.cid,
+                    cmd: x.cmd.clone(),
+                    created_at: x.created_at,
+                })
+                .collect();
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(cid: HyUuid) -> Pending {
+        Pending::new(cid, "true", CommandOption::new(), None)
+    }
+
+    fn cid(x: &[(HyUuid, Pending)]) -> Vec<HyUuid> {
+        x.iter().map(|x| x.1.cid).collect()
+    }
+
+    #[test]
+    fn agent_limit() {
+        let limiter = Limiter::default();
+        let aid = HyUuid::new();
+        let c: Vec<HyUuid> = (0..4).map(|_| HyUuid::new()).collect();
+        assert!(limiter.acquire(&aid, pending(c[0])).is_some());
+        limiter.set_limit(&aid, 2);
+        assert!(limiter.acquire(&aid, pending(c[1])).is_some());
+        assert!(limiter.acquire(&aid, pending(c[2])).is_none());
+        assert!(limiter.acquire(&aid, pending(c[3])).is_none());
+        let status = limiter.status(&aid);
+        assert_eq!(status.running, vec![c[0], c[1]]);
+        assert_eq!(status.queued.len(), 2);
+
+        // Queued commands run in order.
+        assert_eq!(cid(&limiter.release(&aid, &c[0])), vec![c[2]]);
+        assert!(limiter.release(&aid, &c[0]).is_empty());
+        assert!(limiter.cancel(&aid, &c[3]));
+        assert!(!limiter.cancel(&aid, &c[3]));
+        assert!(limiter.release(&aid, &c[1]).is_empty());
+        assert_eq!(limiter.status(&aid).running, vec![c[2]]);
+    }
+
+    #[test]
+    fn raise_limit() {
+        let limiter = Limiter::default();
+        let aid = HyUuid::new();
+        let c: Vec<HyUuid> = (0..3).map(|_| HyUuid::new()).collect();
+        limiter.set_limit(&aid, 1);
+        for i in &c {
+            limiter.acquire(&aid, pending(*i));
+        }
+        assert_eq!(cid(&limiter.set_limit(&aid, 2)), vec![c[1]]);
+        assert_eq!(cid(&limiter.set_limit(&aid, 0)), vec![c[2]]);
+        assert!(limiter.status(&aid).queued.is_empty());
+    }
+
+    #[test]
+    fn group_limit() {
+        let limiter = Limiter::default();
+        let (a, b, other) = (HyUuid::new(), HyUuid::new(), HyUuid::new());
+        let label = vec![String::from("db")];
+        limiter.set_label(&a, &label);
+        limiter.set_label(&b, &label);
+        limiter.set_group_limit(HashMap::from([(String::from("db"), 1)]));
+        assert_eq!(limiter.status(&a).group.get("db"), Some(&1));
+        let c: Vec<HyUuid> = (0..4).map(|_| HyUuid::new()).collect();
+        assert!(limiter.acquire(&a, pending(c[0])).is_some());
+        assert!(limiter.acquire(&b, pending(c[1])).is_none());
+        assert!(limiter.acquire(&a, pending(c[2])).is_none());
+        assert!(limiter.acquire(&other, pending(c[3])).is_some());
+
+        // Slot of `a` is released to the first waiting agent.
+        let ready = limiter.release(&a, &c[0]);
+        assert_eq!(ready.len(), 1);
+        assert!(ready[0].1.cid == c[1] || ready[0].1.cid == c[2]);
+
+        // Logout of the running agent releases its slots.
+        let (running, _) = if ready[0].0 == a { (a, b) } else { (b, a) };
+        assert_eq!(limiter.clear(&running).len(), 1);
+    }
+
+    #[test]
+    fn group_relabel() {
+        let limiter = Limiter::default();
+        let aid = HyUuid::new();
+        let c: Vec<HyUuid> = (0..2).map(|_| HyUuid::new()).collect();
+        limiter.set_group_limit(HashMap::from([(String::from("web"), 1)]));
+        limiter.set_label(&aid, &[String::from("web")]);
+        assert!(limiter.acquire(&aid, pending(c[0])).is_some());
+        assert!(limiter.acquire(&aid, pending(c[1])).is_none());
+        assert_eq!(cid(&limiter.set_label(&aid, &[])), vec![c[1]]);
+        limiter.set_label(&aid, &[String::from("web")]);
+        assert!(limiter.release(&aid, &c[0]).is_empty());
+        assert!(limiter.release(&aid, &c[1]).is_empty());
+        assert!(
+            limiter
+                .set_group_limit(HashMap::from([(String::from("web"), 0)]))
+                .is_empty()
+        );
+        assert!(limiter.status(&aid).group.is_empty());
+    }
+
+    #[test]
+    fn validator() {
+        assert!(group_limit_validator(&HashMap::from([(String::from("a"), 8)])).is_ok());
+        assert!(group_limit_validator(&HashMap::from([(String::new(), 8)])).is_err());
+        assert!(group_limit_validator(&HashMap::from([(String::from("a"), 4096)])).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_cloud::chrono::Utc;
+use derivative::Derivative;
+use serde::Serialize;
+use serde_repr::Serialize_repr;
+use skynet_api::HyUuid;
+
+/// Extra time waiting for agent response after migrate timeout, unit seconds.
+const MIGRATE_GRACE_TIME: u64 = 30;
+
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum MigrateStatus {
+    #[default]
+    Pending = 0,
+    Success,
+    RolledBack,
+    Offline,
+    Timeout,
+}
+
+#[derive(Serialize, Debug, Clone, Derivative)]
+#[derivative(Default(new = "true"))]
+pub struct MigrateResult {
+    pub status: MigrateStatus,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MigrateSummary {
+    pub pending: usize,
+    pub success: usize,
+    pub rolled_back: usize,
+    pub offline: usize,
+    pub timeout: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MigrateJob {
+    pub id: HyUuid,
+    pub endpoint: String,
+    pub timeout: u32,
+    pub result: HashMap<HyUuid, MigrateResult>,
+    pub created_at: i64,
+}
+
+impl MigrateJob {
+    pub fn new(endpoint: &str, timeout: u32) -> Self {
+        Self {
+            id: HyUuid::new(),
+            endpoint: endpoint.to_owned(),
+            timeout,
+            result: HashMap::new(),
+            created_at: Utc::now().timestamp_millis(),
+        }
+    }
+
+    /// Set agent `aid` migration result.
+    pub fn set(&mut self, aid: &HyUuid, status: MigrateStatus, message: &str) {
+        self.result.insert(
+            *aid,
+            MigrateResult {
+                status,
+                message: message.to_owned(),
+                updated_at: Utc::now().timestamp_millis(),
+            },
+        );
+    }
+
+    /// Time waiting for agent response.
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(u64::from(self.timeout) + MIGRATE_GRACE_TIME)
+    }
+
+    pub fn summary(&self) -> MigrateSummary {
+        let mut ret = MigrateSummary::default();
+        for i in self.result.values() {
+            *match i.status {
+                MigrateStatus::Pending => &mut ret.pending,
+                MigrateStatus::Success => &mut ret.success,
+                MigrateStatus::RolledBack => &mut ret.rolled_back,
+                MigrateStatus::Offline => &mut ret.offline,
+                MigrateStatus::Timeout => &mut ret.timeout,
+            } += 1;
+        }
+        ret
+    }
+}
@@ -0,0 +1,78 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum PassiveAgents {
+    Table,
+    RetryMaxTime,
+    RetryMultiplier,
+    RetryJitter,
+    RetryLimit,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite does not support multiple alter options in one statement.
+        for col in [
+            ColumnDef::new(PassiveAgents::RetryMaxTime)
+                .integer()
+                .not_null()
+                .default(0)
+                .to_owned(),
+            ColumnDef::new(PassiveAgents::RetryMultiplier)
+                .double()
+                .not_null()
+                .default(1.0)
+                .to_owned(),
+            ColumnDef::new(PassiveAgents::RetryJitter)
+                .double()
+                .not_null()
+                .default(0.0)
+                .to_owned(),
+            ColumnDef::new(PassiveAgents::RetryLimit)
+                .integer()
+                .not_null()
+                .default(0)
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table_prefix(&PassiveAgents::Table))
+                        .add_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for col in [
+            PassiveAgents::RetryMaxTime,
+            PassiveAgents::RetryMultiplier,
+            PassiveAgents::RetryJitter,
+            PassiveAgents::RetryLimit,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table_prefix(&PassiveAgents::Table))
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
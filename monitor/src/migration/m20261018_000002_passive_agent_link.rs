@@ -0,0 +1,42 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum PassiveAgents {
+    Table,
+    Aid,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&PassiveAgents::Table))
+                    .add_column(ColumnDef::new(PassiveAgents::Aid).char_len(36))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&PassiveAgents::Table))
+                    .drop_column(PassiveAgents::Aid)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,92 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, ForeignKey, ForeignKeyAction, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum Agents {
+    Table,
+    ID,
+}
+
+#[derive(Iden)]
+enum Jobs {
+    Table,
+    ID,
+    Aid,
+    Uid,
+    Kind,
+    Status,
+    Cmd,
+    Code,
+    Output,
+    TimedOut,
+    Truncated,
+    FinishedAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&Jobs::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Jobs::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Jobs::Aid).char_len(36).not_null())
+                    .col(ColumnDef::new(Jobs::Uid).char_len(36))
+                    .col(ColumnDef::new(Jobs::Kind).integer().not_null())
+                    .col(ColumnDef::new(Jobs::Status).integer().not_null())
+                    .col(ColumnDef::new(Jobs::Cmd).text().not_null())
+                    .col(ColumnDef::new(Jobs::Code).integer())
+                    .col(ColumnDef::new(Jobs::Output).blob().not_null())
+                    .col(ColumnDef::new(Jobs::TimedOut).boolean().not_null())
+                    .col(ColumnDef::new(Jobs::Truncated).boolean().not_null())
+                    .col(ColumnDef::new(Jobs::FinishedAt).big_integer())
+                    .col(ColumnDef::new(Jobs::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(Jobs::UpdatedAt).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .to(table_prefix(&Agents::Table), Agents::ID)
+                            .from_col(Jobs::Aid)
+                            .on_update(ForeignKeyAction::Restrict)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_jobs_1")
+                    .table(table_prefix(&Jobs::Table))
+                    .col(Jobs::Aid)
+                    .col(Jobs::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(table_prefix(&Jobs::Table)).to_owned())
+            .await?;
+        Ok(())
+    }
+}
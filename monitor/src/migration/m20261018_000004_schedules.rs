@@ -0,0 +1,171 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, ForeignKey, ForeignKeyAction, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum Agents {
+    Table,
+    ID,
+}
+
+#[derive(Iden)]
+enum Schedules {
+    Table,
+    ID,
+    Name,
+    Cmd,
+    Target,
+    Option,
+    Cron,
+    Timezone,
+    Concurrency,
+    Alert,
+    Enabled,
+    Uid,
+    LastRunAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum ScheduleRuns {
+    Table,
+    ID,
+    Sid,
+    Rid,
+    Aid,
+    Cid,
+    Status,
+    Code,
+    Message,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&Schedules::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Schedules::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Schedules::Name)
+                            .string_len(32)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Schedules::Cmd).text().not_null())
+                    .col(ColumnDef::new(Schedules::Target).text().not_null())
+                    .col(ColumnDef::new(Schedules::Option).text().not_null())
+                    .col(ColumnDef::new(Schedules::Cron).string().not_null())
+                    .col(ColumnDef::new(Schedules::Timezone).string().not_null())
+                    .col(ColumnDef::new(Schedules::Concurrency).integer().not_null())
+                    .col(ColumnDef::new(Schedules::Alert).boolean().not_null())
+                    .col(ColumnDef::new(Schedules::Enabled).boolean().not_null())
+                    .col(ColumnDef::new(Schedules::Uid).char_len(36))
+                    .col(ColumnDef::new(Schedules::LastRunAt).big_integer())
+                    .col(
+                        ColumnDef::new(Schedules::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Schedules::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&ScheduleRuns::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ScheduleRuns::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ScheduleRuns::Sid).char_len(36).not_null())
+                    .col(ColumnDef::new(ScheduleRuns::Rid).char_len(36).not_null())
+                    .col(ColumnDef::new(ScheduleRuns::Aid).char_len(36).not_null())
+                    .col(ColumnDef::new(ScheduleRuns::Cid).char_len(36))
+                    .col(ColumnDef::new(ScheduleRuns::Status).integer().not_null())
+                    .col(ColumnDef::new(ScheduleRuns::Code).integer())
+                    .col(ColumnDef::new(ScheduleRuns::Message).text().not_null())
+                    .col(
+                        ColumnDef::new(ScheduleRuns::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ScheduleRuns::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .to(table_prefix(&Schedules::Table), Schedules::ID)
+                            .from_col(ScheduleRuns::Sid)
+                            .on_update(ForeignKeyAction::Restrict)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .to(table_prefix(&Agents::Table), Agents::ID)
+                            .from_col(ScheduleRuns::Aid)
+                            .on_update(ForeignKeyAction::Restrict)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_schedule_runs_1")
+                    .table(table_prefix(&ScheduleRuns::Table))
+                    .col(ScheduleRuns::Sid)
+                    .col(ScheduleRuns::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&ScheduleRuns::Table))
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&Schedules::Table))
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
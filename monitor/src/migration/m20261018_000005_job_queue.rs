@@ -0,0 +1,59 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum Jobs {
+    Table,
+    Payload,
+    ExpireAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&Jobs::Table))
+                    .add_column(ColumnDef::new(Jobs::Payload).blob())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&Jobs::Table))
+                    .add_column(ColumnDef::new(Jobs::ExpireAt).big_integer())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&Jobs::Table))
+                    .drop_column(Jobs::ExpireAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table_prefix(&Jobs::Table))
+                    .drop_column(Jobs::Payload)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,68 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum Scripts {
+    Table,
+    ID,
+    Name,
+    Description,
+    Interpreter,
+    Body,
+    Params,
+    Target,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&Scripts::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Scripts::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Scripts::Name)
+                            .string_len(32)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Scripts::Description).text().not_null())
+                    .col(ColumnDef::new(Scripts::Interpreter).string().not_null())
+                    .col(ColumnDef::new(Scripts::Body).text().not_null())
+                    .col(ColumnDef::new(Scripts::Params).text().not_null())
+                    .col(ColumnDef::new(Scripts::Target).text().not_null())
+                    .col(ColumnDef::new(Scripts::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(Scripts::UpdatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&Scripts::Table))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,95 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum ShellRecordings {
+    Table,
+    ID,
+    Aid,
+    Uid,
+    Token,
+    Cmd,
+    Size,
+    Truncated,
+    FinishedAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&ShellRecordings::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShellRecordings::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ShellRecordings::Aid).char_len(36).not_null())
+                    .col(ColumnDef::new(ShellRecordings::Uid).char_len(36))
+                    .col(
+                        ColumnDef::new(ShellRecordings::Token)
+                            .char_len(36)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ShellRecordings::Cmd).text().not_null())
+                    .col(
+                        ColumnDef::new(ShellRecordings::Size)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ShellRecordings::Truncated)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ShellRecordings::FinishedAt).big_integer())
+                    .col(
+                        ColumnDef::new(ShellRecordings::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ShellRecordings::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shell_recordings_1")
+                    .table(table_prefix(&ShellRecordings::Table))
+                    .col(ShellRecordings::Aid)
+                    .col(ShellRecordings::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&ShellRecordings::Table))
+                    .to_owned(),
+            )
+            .await
+    }
+}
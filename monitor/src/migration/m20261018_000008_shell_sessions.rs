@@ -0,0 +1,93 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum ShellSessions {
+    Table,
+    ID,
+    Aid,
+    Uid,
+    Token,
+    Cmd,
+    Ip,
+    BytesIn,
+    BytesOut,
+    FinishedAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&ShellSessions::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShellSessions::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ShellSessions::Aid).char_len(36).not_null())
+                    .col(ColumnDef::new(ShellSessions::Uid).char_len(36))
+                    .col(ColumnDef::new(ShellSessions::Token).char_len(36).not_null())
+                    .col(ColumnDef::new(ShellSessions::Cmd).text().not_null())
+                    .col(ColumnDef::new(ShellSessions::Ip).string().not_null())
+                    .col(
+                        ColumnDef::new(ShellSessions::BytesIn)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ShellSessions::BytesOut)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ShellSessions::FinishedAt).big_integer())
+                    .col(
+                        ColumnDef::new(ShellSessions::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ShellSessions::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shell_sessions_1")
+                    .table(table_prefix(&ShellSessions::Table))
+                    .col(ShellSessions::Aid)
+                    .col(ShellSessions::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&ShellSessions::Table))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,64 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum Acls {
+    Table,
+    ID,
+    Uid,
+    Aid,
+    Label,
+    Perm,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&Acls::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Acls::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Acls::Uid).char_len(36).not_null())
+                    .col(ColumnDef::new(Acls::Aid).char_len(36))
+                    .col(ColumnDef::new(Acls::Label).string_len(32))
+                    .col(ColumnDef::new(Acls::Perm).integer().not_null())
+                    .col(ColumnDef::new(Acls::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(Acls::UpdatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_acls_1")
+                    .table(table_prefix(&Acls::Table))
+                    .col(Acls::Uid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(table_prefix(&Acls::Table)).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,97 @@
+use actix_cloud::async_trait;
+use sea_orm_migration::{MigrationTrait, SchemaManager};
+use skynet_api::sea_orm::{
+    DbErr, DeriveMigrationName,
+    sea_query::{self, ColumnDef, Iden, Index, Table},
+};
+
+use super::migrator::table_prefix;
+
+#[derive(Iden)]
+enum SocksUsers {
+    Table,
+    ID,
+    Uid,
+    Username,
+    Password,
+    Salt,
+    Enable,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table_prefix(&SocksUsers::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SocksUsers::ID)
+                            .char_len(36)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SocksUsers::Uid).char_len(36).not_null())
+                    .col(
+                        ColumnDef::new(SocksUsers::Username)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SocksUsers::Password)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SocksUsers::Salt).string_len(32).not_null())
+                    .col(ColumnDef::new(SocksUsers::Enable).boolean().not_null())
+                    .col(
+                        ColumnDef::new(SocksUsers::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SocksUsers::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .unique()
+                    .name("idx_socks_users_1")
+                    .table(table_prefix(&SocksUsers::Table))
+                    .col(SocksUsers::Uid)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .unique()
+                    .name("idx_socks_users_2")
+                    .table(table_prefix(&SocksUsers::Table))
+                    .col(SocksUsers::Username)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(table_prefix(&SocksUsers::Table))
+                    .to_owned(),
+            )
+            .await
+    }
+}
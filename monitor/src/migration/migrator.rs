@@ -1,4 +1,12 @@
-use crate::{ID, migration::m20230101_000001_create_table};
+use crate::{
+    ID,
+    migration::{
+        m20230101_000001_create_table, m20261018_000001_passive_backoff,
+        m20261018_000002_passive_agent_link, m20261018_000003_jobs, m20261018_000004_schedules,
+        m20261018_000005_job_queue, m20261018_000006_scripts, m20261018_000007_shell_recordings,
+        m20261018_000008_shell_sessions, m20261018_000009_acls, m20261018_000010_socks_users,
+    },
+};
 use actix_cloud::async_trait;
 use sea_orm_migration::{MigrationTrait, MigratorTrait};
 use skynet_api::sea_orm::{
@@ -11,7 +19,19 @@ pub struct Migrator;
 #[async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20230101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20230101_000001_create_table::Migration),
+            Box::new(m20261018_000001_passive_backoff::Migration),
+            Box::new(m20261018_000002_passive_agent_link::Migration),
+            Box::new(m20261018_000003_jobs::Migration),
+            Box::new(m20261018_000004_schedules::Migration),
+            Box::new(m20261018_000005_job_queue::Migration),
+            Box::new(m20261018_000006_scripts::Migration),
+            Box::new(m20261018_000007_shell_recordings::Migration),
+            Box::new(m20261018_000008_shell_sessions::Migration),
+            Box::new(m20261018_000009_acls::Migration),
+            Box::new(m20261018_000010_socks_users::Migration),
+        ]
     }
 
     fn migration_table_name() -> DynIden {
@@ -1,2 +1,12 @@
 mod m20230101_000001_create_table;
+mod m20261018_000001_passive_backoff;
+mod m20261018_000002_passive_agent_link;
+mod m20261018_000003_jobs;
+mod m20261018_000004_schedules;
+mod m20261018_000005_job_queue;
+mod m20261018_000006_scripts;
+mod m20261018_000007_shell_recordings;
+mod m20261018_000008_shell_sessions;
+mod m20261018_000009_acls;
+mod m20261018_000010_socks_users;
 pub mod migrator;
@@ -0,0 +1,208 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str,
+};
+
+use actix_cloud::tokio::io::{AsyncRead, AsyncReadExt};
+use ipnet::IpNet;
+use skynet_api::{Result, bail};
+use validator::ValidationError;
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_SIZE: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_MAX_SIZE: u16 = 536;
+
+/// Parse `x` as CIDR, single IP address is treated as host network.
+pub fn parse_cidr(x: &str) -> Option<IpNet> {
+    x.parse::<IpNet>()
+        .ok()
+        .or_else(|| x.parse::<IpAddr>().ok().map(Into::into))
+}
+
+/// # Errors
+/// Will return `Err` when `x` has invalid CIDR.
+pub fn cidr_validator(x: &[String]) -> Result<(), ValidationError> {
+    if x.iter().all(|x| parse_cidr(x).is_some()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid cidr"))
+    }
+}
+
+/// Whether `addr` is inside any network of `list`.
+pub fn cidr_contains(list: &[IpNet], addr: &IpAddr) -> bool {
+    let addr = match addr {
+        IpAddr::V6(x) => x.to_ipv4_mapped().map_or(*addr, IpAddr::V4),
+        IpAddr::V4(_) => *addr,
+    };
+    list.iter().any(|x| x.contains(&addr))
+}
+
+/// Read PROXY protocol v1/v2 header from `io`.
+///
+/// Return source address in header, `None` when the header is `UNKNOWN`/`LOCAL`.
+/// Data after the header is not consumed.
+pub async fn read_header<R>(io: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = vec![0; V2_SIGNATURE.len()];
+    io.read_exact(&mut buf).await?;
+    if buf == V2_SIGNATURE {
+        read_v2(io).await
+    } else if buf.starts_with(V1_PREFIX) {
+        read_v1(io, buf).await
+    } else {
+        bail!("Invalid PROXY protocol header")
+    }
+}
+
+async fn read_v1<R>(io: &mut R, mut buf: Vec<u8>) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    while !buf.ends_with(b"\r\n") {
+        if buf.len() >= V1_MAX_SIZE {
+            bail!("PROXY protocol v1 header too long");
+        }
+        buf.push(io.read_u8().await?);
+    }
+    let line = str::from_utf8(&buf[V1_PREFIX.len()..buf.len() - 2])?;
+    let part: Vec<&str> = line.split(' ').collect();
+    match part.as_slice() {
+        ["UNKNOWN", ..] => Ok(None),
+        [proto @ ("TCP4" | "TCP6"), src, _dst, sport, _dport] => {
+            let ip: IpAddr = src.parse()?;
+            if ip.is_ipv4() != (*proto == "TCP4") {
+                bail!("PROXY protocol v1 address family mismatch");
+            }
+            Ok(Some(SocketAddr::new(ip, sport.parse()?)))
+        }
+        _ => bail!("Invalid PROXY protocol v1 header"),
+    }
+}
+
+async fn read_v2<R>(io: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let ver_cmd = io.read_u8().await?;
+    let family = io.read_u8().await?;
+    let len = io.read_u16().await?;
+    if ver_cmd >> 4 != 2 {
+        bail!("Invalid PROXY protocol v2 version");
+    }
+    if len > V2_MAX_SIZE {
+        bail!("PROXY protocol v2 header too long");
+    }
+    let mut buf = vec![0; len.into()];
+    io.read_exact(&mut buf).await?;
+    match ver_cmd & 0x0F {
+        // LOCAL, health check from proxy itself.
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => (),
+        _ => bail!("Invalid PROXY protocol v2 command"),
+    }
+    let short = || io::Error::from(io::ErrorKind::UnexpectedEof);
+    match family >> 4 {
+        // AF_INET
+        0x1 => {
+            let b: &[u8; 12] = buf.get(..12).ok_or_else(short)?.try_into()?;
+            let ip = Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+            let port = u16::from_be_bytes([b[8], b[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        0x2 => {
+            let b: &[u8; 36] = buf.get(..36).ok_or_else(short)?.try_into()?;
+            let ip: [u8; 16] = b[..16].try_into()?;
+            let port = u16::from_be_bytes([b[32], b[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        // AF_UNSPEC, AF_UNIX
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::runtime::Builder;
+
+    use super::*;
+
+    fn header(data: &[u8]) -> Result<Option<SocketAddr>> {
+        let mut io = data;
+        Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(read_header(&mut io))
+    }
+
+    fn v2(ver_cmd: u8, family: u8, addr: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(ver_cmd);
+        buf.push(family);
+        buf.extend_from_slice(&u16::try_from(addr.len()).unwrap().to_be_bytes());
+        buf.extend_from_slice(addr);
+        buf
+    }
+
+    #[test]
+    fn v1() {
+        assert_eq!(
+            header(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET").unwrap(),
+            Some("192.168.0.1:56324".parse().unwrap())
+        );
+        assert_eq!(
+            header(b"PROXY TCP6 ::1 ::2 1234 443\r\n").unwrap(),
+            Some("[::1]:1234".parse().unwrap())
+        );
+        assert_eq!(header(b"PROXY UNKNOWN\r\n").unwrap(), None);
+        assert!(header(b"PROXY TCP4 ::1 ::2 1234 443\r\n").is_err());
+        assert!(header(b"PROXY TCP4 1.1.1.1\r\n").is_err());
+        assert!(header(&[b"PROXY ".as_slice(), &[b'A'; 200]].concat()).is_err());
+        assert!(header(b"GET / HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn v2_header() {
+        let ipv4 = [10, 0, 0, 1, 10, 0, 0, 2, 0x1F, 0x90, 0x01, 0xBB];
+        assert_eq!(
+            header(&v2(0x21, 0x11, &ipv4)).unwrap(),
+            Some("10.0.0.1:8080".parse().unwrap())
+        );
+        let mut ipv6 = [0; 36];
+        ipv6[15] = 1;
+        ipv6[32..34].copy_from_slice(&443u16.to_be_bytes());
+        assert_eq!(
+            header(&v2(0x21, 0x21, &ipv6)).unwrap(),
+            Some("[::1]:443".parse().unwrap())
+        );
+        assert_eq!(header(&v2(0x20, 0x00, &[])).unwrap(), None);
+        assert_eq!(header(&v2(0x21, 0x31, &[0; 216])).unwrap(), None);
+        assert!(header(&v2(0x11, 0x11, &ipv4)).is_err());
+        assert!(header(&v2(0x22, 0x11, &ipv4)).is_err());
+        assert!(header(&v2(0x21, 0x11, &ipv4[..8])).is_err());
+        assert!(header(&v2(0x21, 0x11, &[0; 600])).is_err());
+    }
+
+    #[test]
+    fn cidr() {
+        let list: Vec<IpNet> = ["10.0.0.0/8", "::1", "192.168.1.1"]
+            .iter()
+            .map(|x| parse_cidr(x).unwrap())
+            .collect();
+        assert!(parse_cidr("10.0.0.0/33").is_none());
+        assert!(parse_cidr("localhost").is_none());
+        assert!(cidr_contains(&list, &"10.1.2.3".parse().unwrap()));
+        assert!(cidr_contains(&list, &"::ffff:10.1.2.3".parse().unwrap()));
+        assert!(cidr_contains(&list, &"::1".parse().unwrap()));
+        assert!(cidr_contains(&list, &"192.168.1.1".parse().unwrap()));
+        assert!(!cidr_contains(&list, &"192.168.1.2".parse().unwrap()));
+        assert!(cidr_validator(&["10.0.0.0/8".to_owned()]).is_ok());
+        assert!(cidr_validator(&["10.0.0.0/8".to_owned(), "x".to_owned()]).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use actix_cloud::{
+    chrono::Utc,
+    tokio::{
+        self,
+        io::{AsyncWriteExt, BufWriter},
+        spawn,
+        sync::mpsc::{Receiver, Sender, channel},
+        task::JoinHandle,
+        time::Instant,
+    },
+    tracing::error,
+};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use skynet_api::{HyUuid, Result, anyhow::anyhow};
+use skynet_api_monitor::{
+    ID, entity::shell_recordings, viewer::shell_recordings::ShellRecordingViewer,
+};
+
+use crate::{PLUGIN_INSTANCE, utils::utf8_boundary};
+
+/// Max recording file size, unit bytes.
+const MAX_RECORDING: u64 = 64 * 1024 * 1024;
+
+/// Max recording lines waiting to be written.
+const MAX_PENDING: usize = 1024;
+
+/// Per-agent recording mode, `Inherit` follows the global setting.
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum RecordMode {
+    #[default]
+    Inherit = 0,
+    Enabled,
+    Disabled,
+}
+
+/// Parsed asciicast v2 recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Cast {
+    pub header: Value,
+    pub events: Vec<(f64, String, String)>,
+}
+
+impl Cast {
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut lines = data.lines().filter(|x| !x.is_empty());
+        let header = serde_json::from_str(lines.next().ok_or(anyhow!("Empty recording"))?)?;
+        // The last event may be cut off when the session is still running.
+        let events = lines.filter_map(|x| serde_json::from_str(x).ok()).collect();
+        Ok(Self { header, events })
+    }
+}
+
+pub struct Recorder {
+    id: HyUuid,
+    start: Instant,
+    truncated: bool,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    tx: Sender<String>,
+    writer: JoinHandle<(u64, bool)>,
+    created: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// Queue `line` to the writer, the recording is truncated when the writer falls behind.
+    fn write(&mut self, mut line: String) {
+        if self.truncated {
+            return;
+        }
+        line.push('\n');
+        if self.tx.try_send(line).is_err() {
+            self.truncated = true;
+        }
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let time = self.start.elapsed().as_secs_f64();
+        self.write(json!([time, kind, data]).to_string());
+    }
+
+    /// Record `data` of stream `kind`, incomplete UTF-8 sequences are kept until next chunk.
+    fn stream(&mut self, kind: &str, data: &[u8]) {
+        let buf = if kind == "i" {
+            &mut self.input
+        } else {
+            &mut self.output
+        };
+        buf.extend_from_slice(data);
+        let len = utf8_boundary(buf);
+        let text = String::from_utf8_lossy(&buf[..len]).into_owned();
+        buf.drain(..len);
+        if !text.is_empty() {
+            self.event(kind, &text);
+        }
+    }
+}
+
+/// Write lines from `rx` to `file` of recording `id` until closed, return file size and whether truncated.
+async fn writer(id: HyUuid, file: File, mut rx: Receiver<String>) -> (u64, bool) {
+    let mut file = BufWriter::new(tokio::fs::File::from_std(file));
+    let mut size = 0;
+    let mut truncated = false;
+    while let Some(line) = rx.recv().await {
+        if truncated {
+            continue;
+        }
+        if size + line.len() as u64 > MAX_RECORDING {
+            truncated = true;
+            continue;
+        }
+        if let Err(e) = file.write_all(line.as_bytes()).await {
+            error!(plugin = %ID, rid = %id, error = %e, "Failed to write shell recording");
+            truncated = true;
+            continue;
+        }
+        size += line.len() as u64;
+    }
+    if let Err(e) = file.flush().await {
+        error!(plugin = %ID, rid = %id, error = %e, "Failed to write shell recording");
+    }
+    (size, truncated)
+}
+
+/// Shell session recorder in asciicast v2 format.
+#[derive(Default)]
+pub struct Recording {
+    path: OnceLock<PathBuf>,
+    enable: RwLock<bool>,
+    mode: DashMap<HyUuid, RecordMode>,
+    active: DashMap<HyUuid, Recorder>,
+}
+
+impl Recording {
+    /// Save recordings in `path`, created when not exist.
+    pub fn init(&self, path: PathBuf) -> Result<()> {
+        fs::create_dir_all(&path)?;
+        let _ = self.path.set(path);
+        Ok(())
+    }
+
+    /// Get recording file path of `id`.
+    pub fn file(&self, id: &HyUuid) -> Option<PathBuf> {
+        self.path.get().map(|x| x.join(format!("{id}.cast")))
+    }
+
+    pub fn set_enable(&self, enable: bool) {
+        *self.enable.write() = enable;
+    }
+
+    pub fn mode(&self, aid: &HyUuid) -> RecordMode {
+        self.mode.get(aid).map(|x| *x).unwrap_or_default()
+    }
+
+    pub fn set_mode(&self, aid: &HyUuid, mode: RecordMode) {
+        if mode == RecordMode::Inherit {
+            self.mode.remove(aid);
+        } else {
+            self.mode.insert(*aid, mode);
+        }
+    }
+
+    pub fn remove_agent(&self, aid: &HyUuid) {
+        self.mode.remove(aid);
+    }
+
+    /// Whether shells on agent `aid` should be recorded.
+    pub fn enabled(&self, aid: &HyUuid) -> bool {
+        match self.mode(aid) {
+            RecordMode::Inherit => *self.enable.read(),
+            RecordMode::Enabled => true,
+            RecordMode::Disabled => false,
+        }
+    }
+
+    pub fn is_active(&self, token: &HyUuid) -> bool {
+        self.active.contains_key(token)
+    }
+
+    /// Create recorder of shell `token` running `cmd` on agent `aid`, opened by user `uid`.
+    ///
+    /// The recorder is not active until [`Recording::start`] is called.
+    pub fn create(
+        &self,
+        token: &HyUuid,
+        aid: &HyUuid,
+        uid: Option<HyUuid>,
+        cmd: &str,
+        rows: u32,
+        cols: u32,
+    ) -> Result<Recorder> {
+        let id = HyUuid::new();
+        let path = self
+            .file(&id)
+            .ok_or(anyhow!("Recording is not initialized"))?;
+        let model = shell_recordings::Model {
+            id,
+            aid: *aid,
+            uid,
+            token: *token,
+            cmd: cmd.to_owned(),
+            ..Default::default()
+        };
+        // Create the file first, so that no recording is saved without file.
+        let file = File::create(path)?;
+        let (tx, rx) = channel(MAX_PENDING);
+        let writer = spawn(writer(id, file, rx));
+        let created = spawn(async move {
+            if let Err(e) =
+                ShellRecordingViewer::create(PLUGIN_INSTANCE.db.get().unwrap(), model).await
+            {
+                error!(plugin = %ID, rid = %id, error = %e, "Failed to save shell recording");
+            }
+        });
+        let mut recorder = Recorder {
+            id,
+            start: Instant::now(),
+            truncated: false,
+            input: Vec::new(),
+            output: Vec::new(),
+            tx,
+            writer,
+            created,
+        };
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": Utc::now().timestamp(),
+            "title": format!("{aid} {cmd}"),
+            "env": {"SHELL": cmd},
+        });
+        recorder.write(header.to_string());
+        Ok(recorder)
+    }
+
+    /// Start recording shell `token` with `recorder`, the previous one is finished.
+    pub fn start(&self, token: &HyUuid, recorder: Recorder) {
+        if let Some(x) = self.active.insert(*token, recorder) {
+            Self::finish(x);
+        }
+    }
+
+    pub fn input(&self, token: &HyUuid, data: &[u8]) {
+        if let Some(mut x) = self.active.get_mut(token) {
+            x.stream("i", data);
+        }
+    }
+
+    pub fn output(&self, token: &HyUuid, data: &[u8]) {
+        if let Some(mut x) = self.active.get_mut(token) {
+            x.stream("o", data);
+        }
+    }
+
+    pub fn resize(&self, token: &HyUuid, rows: u32, cols: u32) {
+        if let Some(mut x) = self.active.get_mut(token) {
+            x.event("r", &format!("{cols}x{rows}"));
+        }
+    }
+
+    /// Stop recording shell `token`.
+    pub fn stop(&self, token: &HyUuid) {
+        if let Some((_, x)) = self.active.remove(token) {
+            Self::finish(x);
+        }
+    }
+
+    /// Stop all recordings.
+    pub fn clear(&self) {
+        let token: Vec<HyUuid> = self.active.iter().map(|x| *x.key()).collect();
+        for i in token {
+            self.stop(&i);
+        }
+    }
+
+    fn finish(x: Recorder) {
+        let Recorder {
+            id,
+            truncated,
+            tx,
+            writer,
+            created,
+            ..
+        } = x;
+        // Writer stops after pending lines are written.
+        drop(tx);
+        spawn(async move {
+            let (size, dropped) = writer.await.unwrap_or_default();
+            let _ = created.await;
+            if let Err(e) = ShellRecordingViewer::finish(
+                PLUGIN_INSTANCE.db.get().unwrap(),
+                &id,
+                size.try_into().unwrap_or(i64::MAX),
+                truncated || dropped,
+            )
+            .await
+            {
+                error!(plugin = %ID, rid = %id, error = %e, "Failed to save shell recording");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::runtime::Builder;
+
+    use super::*;
+
+    #[test]
+    fn stream() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let _guard = rt.enter();
+        let (tx, mut rx) = channel(2);
+        let mut x = Recorder {
+            id: HyUuid::new(),
+            start: Instant::now(),
+            truncated: false,
+            input: Vec::new(),
+            output: Vec::new(),
+            tx,
+            writer: spawn(async { (0, false) }),
+            created: spawn(async {}),
+        };
+        // Incomplete UTF-8 sequence is kept until next chunk.
+        let data = "你".as_bytes();
+        x.stream("o", &data[..2]);
+        assert!(rx.try_recv().is_err());
+        x.stream("o", &data[2..]);
+        let line: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(line[1], "o");
+        assert_eq!(line[2], "你");
+
+        // Writer falls behind.
+        x.event("r", "80x24");
+        x.event("r", "80x24");
+        assert!(!x.truncated);
+        x.event("r", "80x24");
+        assert!(x.truncated);
+    }
+
+    #[test]
+    fn write() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        let path = std::env::temp_dir().join(format!("monitor_recording_{}.cast", HyUuid::new()));
+        let ret = rt.block_on(async {
+            let (tx, rx) = channel(MAX_PENDING);
+            let x = spawn(writer(HyUuid::new(), File::create(&path).unwrap(), rx));
+            tx.send(String::from("a\n")).await.unwrap();
+            tx.send(String::from("b\n")).await.unwrap();
+            drop(tx);
+            x.await.unwrap()
+        });
+        assert_eq!(ret, (4, false));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    error, fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use actix_cloud::tokio::{
+    sync::watch,
+    time::{Instant, timeout_at},
+};
+use dashmap::DashMap;
+use skynet_api::HyUuid;
+use skynet_api_monitor::entity::jobs::JobStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcStatus {
+    Pending,
+    Done,
+    Timeout,
+    Cancelled,
+    Disconnected,
+}
+
+impl RpcStatus {
+    pub const fn is_pending(self) -> bool {
+        matches!(self, Self::Pending)
+    }
+}
+
+impl From<RpcStatus> for JobStatus {
+    fn from(value: RpcStatus) -> Self {
+        match value {
+            RpcStatus::Pending => Self::Running,
+            RpcStatus::Done => Self::Finished,
+            RpcStatus::Timeout => Self::Timeout,
+            RpcStatus::Cancelled => Self::Cancelled,
+            RpcStatus::Disconnected => Self::Disconnected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    NotFound,
+    Timeout,
+    Cancelled,
+    Disconnected,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotFound => "Request not found",
+            Self::Timeout => "Request timeout",
+            Self::Cancelled => "Request cancelled",
+            Self::Disconnected => "Agent disconnected",
+        })
+    }
+}
+
+impl error::Error for RpcError {}
+
+/// Finished requests are kept at least for a while before evicted by budget,
+/// so that they can be persisted.
+const EVICT_GRACE: Duration = Duration::from_secs(10);
+
+struct Entry<T> {
+    aid: HyUuid,
+    value: Option<T>,
+    size: usize,
+    deadline: Option<Instant>,
+    finished_at: Option<Instant>,
+    status: watch::Sender<RpcStatus>,
+}
+
+impl<T> Entry<T> {
+    fn status(&self) -> RpcStatus {
+        *self.status.borrow()
+    }
+
+    fn finish(&mut self, status: RpcStatus) -> bool {
+        if self.status().is_pending() {
+            self.finished_at = Some(Instant::now());
+            self.status.send_replace(status);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Correlated requests sent to agents, keyed by request id.
+///
+/// Responses are applied by `update` and waiters are woken once the request is finished,
+/// timed out, cancelled or the agent disconnects.
+pub struct Rpc<T> {
+    entry: DashMap<HyUuid, Entry<T>>,
+    bytes: AtomicUsize,
+}
+
+impl<T> Default for Rpc<T> {
+    fn default() -> Self {
+        Self {
+            entry: DashMap::new(),
+            bytes: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: Clone + Default> Rpc<T> {
+    /// Register request `id` sent to agent `aid`, expired after `timeout` when set.
+    pub fn register(&self, aid: &HyUuid, id: &HyUuid, timeout: Option<Duration>) {
+        self.entry.insert(
+            *id,
+            Entry {
+                aid: *aid,
+                value: None,
+                size: 0,
+                deadline: timeout.map(|x| Instant::now() + x),
+                finished_at: None,
+                status: watch::Sender::new(RpcStatus::Pending),
+            },
+        );
+    }
+
+    /// Start deadline of pending request `id`, expired after `timeout` from now.
+    pub fn set_deadline(&self, id: &HyUuid, timeout: Duration) {
+        if let Some(mut x) = self.entry.get_mut(id) {
+            x.deadline = Some(Instant::now() + timeout);
+        }
+    }
+
+    /// Apply response from agent `aid` to request `id`, `f` returns true when the request is finished.
+    ///
+    /// Return false when `id` is not found, belongs to other agents or already finished.
+    pub fn update<F>(&self, aid: &HyUuid, id: &HyUuid, f: F) -> bool
+    where
+        F: FnOnce(&mut T) -> bool,
+    {
+        if let Some(mut x) = self.entry.get_mut(id)
+            && x.aid == *aid
+            && x.status().is_pending()
+        {
+            if f(x.value.get_or_insert_default()) {
+                x.finish(RpcStatus::Done);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Apply response with `size` bytes from agent `aid` to request `id`, limited by `budget`
+    /// bytes of all requests in memory. `f` gets the allowed size and returns true when the
+    /// request is finished.
+    ///
+    /// Oldest finished requests are removed to make room when exceeding `budget`.
+    /// Return false when `id` is not found, belongs to other agents or already finished.
+    pub fn update_within<F>(
+        &self,
+        aid: &HyUuid,
+        id: &HyUuid,
+        size: usize,
+        budget: usize,
+        f: F,
+    ) -> bool
+    where
+        F: FnOnce(&mut T, usize) -> bool,
+    {
+        if self.bytes.load(Ordering::Relaxed) + size > budget {
+            self.evict(budget.saturating_sub(size));
+        }
+        if let Some(mut x) = self.entry.get_mut(id)
+            && x.aid == *aid
+            && x.status().is_pending()
+        {
+            let used = self
+                .bytes
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| {
+                    Some(b + size.min(budget.saturating_sub(b)))
+                })
+                .unwrap_or_default();
+            let allowed = size.min(budget.saturating_sub(used));
+            x.size += allowed;
+            if f(x.value.get_or_insert_default(), allowed) {
+                x.finish(RpcStatus::Done);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Remove oldest finished requests until at most `target` bytes are in memory.
+    fn evict(&self, target: usize) {
+        let now = Instant::now();
+        let mut finished: Vec<(Instant, HyUuid)> = self
+            .entry
+            .iter()
+            .filter(|x| x.size != 0)
+            .filter_map(|x| x.finished_at.map(|t| (t, *x.key())))
+            .filter(|x| now.duration_since(x.0) >= EVICT_GRACE)
+            .collect();
+        finished.sort_unstable_by_key(|x| x.0);
+        for (_, id) in finished {
+            if self.bytes.load(Ordering::Relaxed) <= target {
+                break;
+            }
+            self.remove(&id);
+        }
+    }
+
+    fn remove(&self, id: &HyUuid) {
+        if let Some((_, x)) = self.entry.remove(id) {
+            self.bytes.fetch_sub(x.size, Ordering::Relaxed);
+        }
+    }
+
+    /// Get request `id` of agent `aid`, value is `None` before any response.
+    pub fn get(&self, aid: &HyUuid, id: &HyUuid) -> Option<(RpcStatus, Option<T>)> {
+        self.with(aid, id, |status, value| (status, value.cloned()))
+    }
+
+    /// Inspect request `id` of agent `aid` without cloning the value.
+    pub fn with<F, R>(&self, aid: &HyUuid, id: &HyUuid, f: F) -> Option<R>
+    where
+        F: FnOnce(RpcStatus, Option<&T>) -> R,
+    {
+        self.entry
+            .get(id)
+            .filter(|x| x.aid == *aid)
+            .map(|x| f(x.status(), x.value.as_ref()))
+    }
+
+    /// Wait until request `id` is finished, at most `timeout` when set.
+    ///
+    /// Return the final value, or the reason why the request is not done.
+    pub async fn wait(&self, id: &HyUuid, timeout: Option<Duration>) -> Result<T, RpcError> {
+        let (mut rx, deadline) = {
+            let x = self.entry.get(id).ok_or(RpcError::NotFound)?;
+            (x.status.subscribe(), x.deadline)
+        };
+        let wait = timeout.map(|x| Instant::now() + x);
+        let (until, expire) = match (deadline, wait) {
+            (Some(d), Some(w)) => (Some(d.min(w)), d <= w),
+            (Some(d), None) => (Some(d), true),
+            (None, w) => (w, false),
+        };
+        let status = if let Some(until) = until {
+            match timeout_at(until, rx.wait_for(|x| !x.is_pending())).await {
+                Ok(x) => x.map(|x| *x).map_err(|_| RpcError::NotFound)?,
+                Err(_) => {
+                    if expire {
+                        self.finish(id, RpcStatus::Timeout);
+                    }
+                    return Err(RpcError::Timeout);
+                }
+            }
+        } else {
+            rx.wait_for(|x| !x.is_pending())
+                .await
+                .map(|x| *x)
+                .map_err(|_| RpcError::NotFound)?
+        };
+        match status {
+            RpcStatus::Done => Ok(self
+                .entry
+                .get(id)
+                .and_then(|x| x.value.clone())
+                .unwrap_or_default()),
+            RpcStatus::Pending | RpcStatus::Timeout => Err(RpcError::Timeout),
+            RpcStatus::Cancelled => Err(RpcError::Cancelled),
+            RpcStatus::Disconnected => Err(RpcError::Disconnected),
+        }
+    }
+
+    /// Wait until request `id` is finished, return the final status.
+    ///
+    /// Return `None` when `id` is not found or removed.
+    pub async fn finished(&self, id: &HyUuid) -> Option<RpcStatus> {
+        let mut rx = self.entry.get(id)?.status.subscribe();
+        rx.wait_for(|x| !x.is_pending()).await.ok().map(|x| *x)
+    }
+
+    fn finish(&self, id: &HyUuid, status: RpcStatus) -> bool {
+        self.entry.get_mut(id).is_some_and(|mut x| x.finish(status))
+    }
+
+    /// Cancel pending request `id` of agent `aid`, waiters will get `RpcError::Cancelled`.
+    pub fn cancel(&self, aid: &HyUuid, id: &HyUuid) -> bool {
+        self.entry
+            .get_mut(id)
+            .filter(|x| x.aid == *aid)
+            .is_some_and(|mut x| x.finish(RpcStatus::Cancelled))
+    }
+
+    /// Finish all pending requests of agent `aid` when connection lost.
+    pub fn disconnect(&self, aid: &HyUuid) {
+        for mut i in self.entry.iter_mut() {
+            if i.aid == *aid {
+                i.finish(RpcStatus::Disconnected);
+            }
+        }
+    }
+
+    /// Remove all requests of agent `aid`.
+    pub fn remove_agent(&self, aid: &HyUuid) {
+        self.entry.retain(|_, v| {
+            if v.aid == *aid {
+                self.bytes.fetch_sub(v.size, Ordering::Relaxed);
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Cancel all pending requests and remove all requests.
+    pub fn clear(&self) {
+        for mut i in self.entry.iter_mut() {
+            i.finish(RpcStatus::Cancelled);
+        }
+        self.entry.clear();
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+
+    /// Expire pending requests reaching deadline, remove finished requests older than `retention`.
+    ///
+    /// Oldest finished requests are also removed when exceeding `cap`.
+    pub fn purge(&self, retention: Duration, cap: usize) {
+        let now = Instant::now();
+        self.entry.retain(|_, v| {
+            if v.deadline.is_some_and(|x| x <= now) {
+                v.finish(RpcStatus::Timeout);
+            }
+            if v.finished_at
+                .is_none_or(|x| now.duration_since(x) < retention)
+            {
+                return true;
+            }
+            self.bytes.fetch_sub(v.size, Ordering::Relaxed);
+            false
+        });
+        if self.entry.len() > cap {
+            let mut finished: Vec<(Instant, HyUuid)> = self
+                .entry
+                .iter()
+                .filter_map(|x| x.finished_at.map(|t| (t, *x.key())))
+                .collect();
+            finished.sort_unstable_by_key(|x| x.0);
+            for (_, id) in finished.iter().take(self.entry.len() - cap) {
+                self.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::{join, runtime::Builder};
+    use skynet_api_monitor::AgentCommand;
+
+    use super::*;
+
+    #[test]
+    fn wait_queued() {
+        let rpc: Rpc<AgentCommand> = Rpc::default();
+        let aid = HyUuid::new();
+        let id = HyUuid::new();
+        // Queued requests have no deadline until dispatched.
+        rpc.register(&aid, &id, None);
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let ret = rt.block_on(rpc.wait(&id, Some(Duration::from_millis(10))));
+        assert_eq!(ret.err(), Some(RpcError::Timeout));
+        // Caller deadline does not expire the request itself.
+        assert_eq!(rpc.get(&aid, &id).unwrap().0, RpcStatus::Pending);
+
+        rpc.set_deadline(&id, Duration::from_millis(10));
+        let ret = rt.block_on(rpc.wait(&id, Some(Duration::from_secs(60))));
+        assert_eq!(ret.err(), Some(RpcError::Timeout));
+        assert_eq!(rpc.get(&aid, &id).unwrap().0, RpcStatus::Timeout);
+    }
+
+    fn update(rpc: &Rpc<AgentCommand>, aid: &HyUuid, id: &HyUuid, data: &[u8], done: bool) {
+        let size = data.len();
+        assert!(rpc.update_within(aid, id, size, 10, |x, allowed| {
+            x.output.extend_from_slice(&data[..allowed]);
+            x.truncated |= allowed < size;
+            done
+        }));
+    }
+
+    #[test]
+    fn budget() {
+        let rpc: Rpc<AgentCommand> = Rpc::default();
+        let aid = HyUuid::new();
+        let (a, b) = (HyUuid::new(), HyUuid::new());
+        rpc.register(&aid, &a, None);
+        rpc.register(&aid, &b, None);
+        update(&rpc, &aid, &a, b"123456", false);
+        update(&rpc, &aid, &b, b"123456", false);
+        let x = rpc.get(&aid, &b).unwrap().1.unwrap();
+        assert_eq!(x.output, b"1234");
+        assert!(x.truncated);
+        assert_eq!(rpc.bytes.load(Ordering::Relaxed), 10);
+
+        // Recently finished requests are not evicted.
+        update(&rpc, &aid, &a, b"", true);
+        update(&rpc, &aid, &b, b"7", false);
+        assert!(rpc.get(&aid, &a).is_some());
+        assert_eq!(rpc.bytes.load(Ordering::Relaxed), 10);
+
+        rpc.entry.get_mut(&a).unwrap().finished_at = Instant::now().checked_sub(EVICT_GRACE);
+        update(&rpc, &aid, &b, b"789", true);
+        assert!(rpc.get(&aid, &a).is_none());
+        assert_eq!(rpc.get(&aid, &b).unwrap().1.unwrap().output, b"1234789");
+        assert_eq!(rpc.bytes.load(Ordering::Relaxed), 7);
+
+        rpc.remove_agent(&aid);
+        assert_eq!(rpc.bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn clear() {
+        let rpc: Rpc<AgentCommand> = Rpc::default();
+        let aid = HyUuid::new();
+        let id = HyUuid::new();
+        rpc.register(&aid, &id, None);
+        update(&rpc, &aid, &id, b"123", false);
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let (ret, ()) = rt.block_on(async { join!(rpc.wait(&id, None), async { rpc.clear() }) });
+        // Waiters are woken up.
+        assert_eq!(ret.err(), Some(RpcError::Cancelled));
+        assert!(rpc.get(&aid, &id).is_none());
+        assert_eq!(rpc.bytes.load(Ordering::Relaxed), 0);
+    }
+}
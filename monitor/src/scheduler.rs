@@ -0,0 +1,327 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use actix_cloud::{
+    chrono::{DateTime, Utc},
+    tokio::{
+        select, spawn,
+        sync::broadcast::{Sender, channel},
+        time::interval,
+    },
+    tracing::{error, info, warn},
+};
+use chrono_tz::Tz;
+use croner::Cron;
+use dashmap::DashMap;
+use derivative::Derivative;
+use parking_lot::RwLock;
+use skynet_api::{HyUuid, Result, anyhow::anyhow};
+use skynet_api_monitor::{
+    CommandOption, ID,
+    entity::{
+        schedule_runs::{self, RunStatus},
+        schedules,
+    },
+    viewer::{schedule_runs::ScheduleRunViewer, schedules::ScheduleViewer},
+};
+use validator::ValidationError;
+
+use crate::{
+    PLUGIN_INSTANCE,
+    acl::{Acl, AclRight},
+    batch::{self, BatchResult, BatchStatus, Target, Task},
+};
+
+/// Parse `cron` expression and `timezone` name.
+pub fn parse(cron: &str, timezone: &str) -> Result<(Cron, Tz)> {
+    let cron = Cron::new(cron).parse()?;
+    let tz = Tz::from_str(timezone).map_err(|e| anyhow!(e))?;
+    Ok((cron, tz))
+}
+
+/// # Errors
+/// Will return `Err` when `x` is not a valid cron expression.
+pub fn cron_validator(x: &str) -> Result<(), ValidationError> {
+    Cron::new(x)
+        .parse()
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("invalid cron"))
+}
+
+/// # Errors
+/// Will return `Err` when `x` is not a valid timezone name.
+pub fn timezone_validator(x: &str) -> Result<(), ValidationError> {
+    Tz::from_str(x)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("invalid timezone"))
+}
+
+/// Record agent `aid` `result` of run `rid` triggered by `schedule`, alert when failed and enabled.
+pub async fn record(schedule: &schedules::Model, rid: &HyUuid, aid: &HyUuid, result: &BatchResult) {
+    let status = match result.status {
+        BatchStatus::Success => RunStatus::Success,
+        BatchStatus::Offline => RunStatus::Offline,
+        _ => RunStatus::Failed,
+    };
+    if let Err(e) = ScheduleRunViewer::create(
+        PLUGIN_INSTANCE.db.get().unwrap(),
+        schedule_runs::Model {
+            sid: schedule.id,
+            rid: *rid,
+            aid: *aid,
+            cid: result.cid,
+            status,
+            code: result.code,
+            message: result.message.clone(),
+            ..Default::default()
+        },
+    )
+    .await
+    {
+        error!(plugin = %ID, sid = %schedule.id, aid = %aid, error = %e, "Failed to record schedule run");
+    }
+    if schedule.alert && status == RunStatus::Failed {
+        let name = PLUGIN_INSTANCE
+            .agent
+            .get(aid)
+            .map(|x| x.name.clone())
+            .unwrap_or_else(|| aid.to_string());
+        let reason = result
+            .code
+            .map_or_else(|| result.message.clone(), |x| format!("exit code {x}"));
+        PLUGIN_INSTANCE
+            .server
+            .alert(
+                format!(
+                    "Schedule `{}` failed on agent `{name}`: {reason}",
+                    schedule.name
+                ),
+                "view",
+            )
+            .await;
+    }
+}
+
+struct Entry {
+    model: Arc<schedules::Model>,
+    cron: Cron,
+    tz: Tz,
+    next: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    fn new(model: schedules::Model) -> Result<Self> {
+        let (cron, tz) = parse(&model.cron, &model.timezone)?;
+        let mut ret = Self {
+            model: Arc::new(model),
+            cron,
+            tz,
+            next: None,
+        };
+        ret.next = ret.find_next(&Utc::now());
+        Ok(ret)
+    }
+
+    fn find_next(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron
+            .find_next_occurrence(&time.with_timezone(&self.tz), false)
+            .ok()
+            .map(|x| x.with_timezone(&Utc))
+    }
+}
+
+/// Cron scheduler for enabled schedules.
+#[derive(Derivative)]
+#[derivative(Default(new = "true"))]
+pub struct Scheduler {
+    schedule: DashMap<HyUuid, Entry>,
+    shutdown_tx: RwLock<Option<Sender<()>>>,
+}
+
+impl Scheduler {
+    /// Add or replace schedule `model`, disabled schedules are removed.
+    pub fn insert(&self, model: schedules::Model) -> Result<()> {
+        if model.enabled {
+            self.schedule.insert(model.id, Entry::new(model)?);
+        } else {
+            self.schedule.remove(&model.id);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &HyUuid) {
+        self.schedule.remove(id);
+    }
+
+    /// Get next run time of schedule `id`, `None` when disabled.
+    pub fn next_run(&self, id: &HyUuid) -> Option<i64> {
+        self.schedule
+            .get(id)
+            .and_then(|x| x.next)
+            .map(|x| x.timestamp_millis())
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        let (tx, mut rx) = channel(1);
+        *self.shutdown_tx.write() = Some(tx);
+        for i in ScheduleViewer::find_enabled(PLUGIN_INSTANCE.db.get().unwrap()).await? {
+            let id = i.id;
+            if let Err(e) = self.insert(i) {
+                warn!(plugin = %ID, sid = %id, error = %e, "Invalid monitor schedule");
+            }
+        }
+
+        info!(plugin = %ID, "Monitor scheduler started");
+        let mut clock = interval(Duration::from_secs(1));
+        loop {
+            select! {
+                _ = clock.tick() => self.tick(),
+                _ = rx.recv() => break,
+            }
+        }
+        *self.shutdown_tx.write() = None;
+        self.schedule.clear();
+        info!(plugin = %ID, "Monitor scheduler stopped");
+        Ok(())
+    }
+
+    pub fn stop(&self) -> bool {
+        self.shutdown_tx
+            .read()
+            .as_ref()
+            .is_some_and(|x| x.send(()).is_ok())
+    }
+
+    fn tick(&self) {
+        let now = Utc::now();
+        let mut due = Vec::new();
+        for mut i in self.schedule.iter_mut() {
+            if i.next.is_some_and(|x| x <= now) {
+                due.push(i.model.clone());
+                i.next = i.find_next(&now);
+            }
+        }
+        for i in due {
+            spawn(async move {
+                Self::fire(i).await;
+            });
+        }
+    }
+
+    /// Run schedule `model` now as its owner, return batch id.
+    ///
+    /// Agents where the owner no longer has exec right are skipped.
+    pub async fn fire(model: Arc<schedules::Model>) -> Option<HyUuid> {
+        let target: Target = match serde_json::from_str(&model.target) {
+            Ok(x) => x,
+            Err(e) => {
+                error!(plugin = %ID, sid = %model.id, error = %e, "Invalid monitor schedule target");
+                return None;
+            }
+        };
+        let mut option: CommandOption = match serde_json::from_str(&model.option) {
+            Ok(x) => x,
+            Err(e) => {
+                error!(plugin = %ID, sid = %model.id, error = %e, "Invalid monitor schedule option");
+                return None;
+            }
+        };
+        // Offline agents are reported, never queued.
+        option.queue_ttl = 0;
+        let perm = if let Some(uid) = &model.uid {
+            match Acl::user_perm(PLUGIN_INSTANCE.db.get().unwrap(), uid).await {
+                Ok(x) => x,
+                Err(e) => {
+                    error!(plugin = %ID, sid = %model.id, error = %e, "Failed to load monitor schedule owner permission");
+                    return None;
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        let (aid, denied): (Vec<HyUuid>, Vec<HyUuid>) =
+            target.resolve().into_iter().partition(|x| {
+                PLUGIN_INSTANCE
+                    .acl
+                    .check(model.uid, &perm, x, AclRight::Exec, || {
+                        PLUGIN_INSTANCE.agent_labels(x)
+                    })
+            });
+        if !denied.is_empty() {
+            warn!(plugin = %ID, sid = %model.id, uid = ?model.uid, aid = ?denied, "Monitor schedule agents denied by ACL");
+        }
+        if aid.is_empty() {
+            warn!(plugin = %ID, sid = %model.id, name = model.name, "No agent matched monitor schedule");
+            return None;
+        }
+        let id = model.id;
+        let task = Task::Command {
+            cmd: model.cmd.clone(),
+            option,
+        };
+        let bid = batch::run(
+            aid,
+            model.uid,
+            task,
+            model.concurrency.try_into().unwrap_or(1),
+            Some(model.clone()),
+        );
+        info!(plugin = %ID, sid = %id, bid = %bid, name = model.name, "Run monitor schedule");
+        spawn(async move {
+            if let Err(e) = ScheduleViewer::touch(PLUGIN_INSTANCE.db.get().unwrap(), &id).await {
+                error!(plugin = %ID, sid = %id, error = %e, "Failed to update monitor schedule");
+            }
+        });
+        Some(bid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::chrono::TimeZone;
+
+    use super::*;
+
+    fn entry(cron: &str, timezone: &str) -> Result<Entry> {
+        Entry::new(schedules::Model {
+            cron: cron.to_owned(),
+            timezone: timezone.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn validator() {
+        assert!(cron_validator("*/5 * * * *").is_ok());
+        assert!(cron_validator("0 3 * * MON-FRI").is_ok());
+        assert!(cron_validator("* * *").is_err());
+        assert!(cron_validator("61 * * * *").is_err());
+        assert!(timezone_validator("UTC").is_ok());
+        assert!(timezone_validator("Asia/Shanghai").is_ok());
+        assert!(timezone_validator("Mars/Olympus").is_err());
+        assert!(parse("0 0 * * *", "UTC").is_ok());
+        assert!(parse("0 0 * * *", "").is_err());
+    }
+
+    #[test]
+    fn next() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 12, 30, 0).unwrap();
+        let x = entry("0 3 * * *", "UTC").unwrap();
+        assert_eq!(
+            x.find_next(&now),
+            Some(Utc.with_ymd_and_hms(2026, 10, 19, 3, 0, 0).unwrap())
+        );
+        // 03:00 in Shanghai is 19:00 UTC on the previous day.
+        let x = entry("0 3 * * *", "Asia/Shanghai").unwrap();
+        assert_eq!(
+            x.find_next(&now),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 19, 0, 0).unwrap())
+        );
+        let x = entry("*/15 * * * *", "UTC").unwrap();
+        assert_eq!(
+            x.find_next(&now),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 12, 45, 0).unwrap())
+        );
+        assert!(x.next.is_some());
+        assert!(entry("bad", "UTC").is_err());
+    }
+}
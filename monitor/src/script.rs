@@ -0,0 +1,334 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_cloud::tokio::spawn;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use skynet_api::{
+    HyUuid,
+    service::{SError, SResult},
+};
+use skynet_api_agent::System;
+use skynet_api_monitor::CommandOption;
+use validator::{Validate, ValidationError};
+
+use crate::PLUGIN_INSTANCE;
+
+/// Max time waiting for script upload.
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ParamKind {
+    #[default]
+    String = 0,
+    Integer,
+    Boolean,
+    Choice,
+}
+
+/// # Errors
+/// Will return `Err` when `x` is not a valid parameter name.
+pub fn param_name_validator(x: &str) -> Result<(), ValidationError> {
+    let mut chars = x.chars();
+    if chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid name"))
+    }
+}
+
+fn param_validator(x: &ScriptParam) -> Result<(), ValidationError> {
+    if x.kind == ParamKind::Choice && x.choices.is_empty() {
+        return Err(ValidationError::new("empty choices"));
+    }
+    if let Some(v) = &x.default
+        && x.check(v).is_err()
+    {
+        return Err(ValidationError::new("invalid default"));
+    }
+    Ok(())
+}
+
+/// # Errors
+/// Will return `Err` when `x` has duplicate parameter names.
+pub fn params_validator(x: &[ScriptParam]) -> Result<(), ValidationError> {
+    for (i, v) in x.iter().enumerate() {
+        if x[..i].iter().any(|p| p.name == v.name) {
+            return Err(ValidationError::new("duplicate name"));
+        }
+    }
+    Ok(())
+}
+
+/// Typed script parameter, passed to the script as environment variable `PARAM_<name>`.
+#[derive(Debug, Clone, Default, Validate, Serialize, Deserialize)]
+#[validate(schema(function = "param_validator"))]
+pub struct ScriptParam {
+    #[validate(length(min = 1, max = 32), custom(function = "param_name_validator"))]
+    pub name: String,
+    #[serde(default)]
+    #[validate(length(max = 256))]
+    pub description: String,
+    #[serde(default)]
+    pub kind: ParamKind,
+    #[serde(default)]
+    pub required: bool,
+    #[validate(length(max = 4096))]
+    pub default: Option<String>,
+    /// Allowed values for `ParamKind::Choice`.
+    #[serde(default)]
+    #[validate(length(max = 64))]
+    pub choices: Vec<String>,
+}
+
+impl ScriptParam {
+    /// Check whether `value` matches the parameter type.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let ok = match self.kind {
+            ParamKind::String => !value.contains('\0'),
+            ParamKind::Integer => value.parse::<i64>().is_ok(),
+            ParamKind::Boolean => value == "true" || value == "false",
+            ParamKind::Choice => self.choices.iter().any(|x| x == value),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Invalid value for parameter `{}`", self.name))
+        }
+    }
+}
+
+/// Validate `values` against `params`, return environment variables passed to the script.
+///
+/// Values are never substituted into the script body, so they cannot be interpreted as code.
+pub fn resolve_params(
+    params: &[ScriptParam],
+    values: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    if let Some(x) = values
+        .keys()
+        .find(|x| !params.iter().any(|p| p.name == **x))
+    {
+        return Err(format!("Unknown parameter `{x}`"));
+    }
+    let mut ret = HashMap::new();
+    for i in params {
+        let value = match values.get(&i.name).or(i.default.as_ref()) {
+            Some(x) => x,
+            None if i.required => return Err(format!("Missing parameter `{}`", i.name)),
+            None => continue,
+        };
+        i.check(value)?;
+        ret.insert(format!("PARAM_{}", i.name), value.to_owned());
+    }
+    Ok(ret)
+}
+
+/// Get script file extension for `interpreter`, empty when unknown.
+///
+/// Windows interpreters such as PowerShell and cmd refuse files without a proper extension.
+fn extension(interpreter: &str) -> &'static str {
+    let prog = interpreter.split_whitespace().next().unwrap_or_default();
+    let prog = prog.rsplit(['/', '\\']).next().unwrap_or_default();
+    let prog = prog.to_ascii_lowercase();
+    match prog.strip_suffix(".exe").unwrap_or(&prog) {
+        "powershell" | "pwsh" => ".ps1",
+        "cmd" => ".bat",
+        "cscript" | "wscript" => ".vbs",
+        "sh" | "bash" | "zsh" => ".sh",
+        "node" => ".js",
+        "perl" => ".pl",
+        "ruby" => ".rb",
+        x if x.starts_with("python") || x == "py" => ".py",
+        _ => "",
+    }
+}
+
+/// Get upload path of script `id` run with `interpreter` and the command removing it.
+fn upload_path(id: &HyUuid, interpreter: &str, windows: bool) -> (String, String) {
+    let name = format!("monitor_script_{id}{}", extension(interpreter));
+    if windows {
+        let path = format!("C:\\Windows\\Temp\\{name}");
+        let clean = format!("cmd /C del /F /Q {path}");
+        (path, clean)
+    } else {
+        let path = format!("/tmp/{name}");
+        let clean = format!("rm -f {path}");
+        (path, clean)
+    }
+}
+
+/// Upload script `body` to agent `aid` and run it with `interpreter`, issued by user `uid`.
+///
+/// The uploaded file is removed after the command is finished. Return command id.
+pub async fn start(
+    aid: &HyUuid,
+    uid: Option<HyUuid>,
+    interpreter: &str,
+    body: &[u8],
+    option: CommandOption,
+) -> SResult<HyUuid> {
+    let windows = PLUGIN_INSTANCE
+        .agent
+        .get(aid)
+        .and_then(|x| x.os.as_deref().and_then(System::parse))
+        .is_some_and(|x| x.is_windows());
+    let (path, clean) = upload_path(&HyUuid::new(), interpreter, windows);
+
+    let fid = PLUGIN_INSTANCE.send_file(aid, uid, &path, body, 0).await?;
+    let x = match PLUGIN_INSTANCE.file.wait(&fid, Some(UPLOAD_TIMEOUT)).await {
+        Ok(x) => x,
+        Err(e) => {
+            // The file may still be written after timeout.
+            spawn(cleanup(*aid, clean));
+            return Err(e.into());
+        }
+    };
+    if x.code != 0 {
+        return Err(SError::new(&format!("Upload script failed: {}", x.message)));
+    }
+    let cid = match PLUGIN_INSTANCE
+        .send_command(aid, uid, &format!("{interpreter} {path}"), option, None)
+        .await
+    {
+        Ok(x) => x,
+        Err(e) => {
+            spawn(cleanup(*aid, clean));
+            return Err(e);
+        }
+    };
+    let aid = *aid;
+    spawn(async move {
+        PLUGIN_INSTANCE.command.finished(&cid).await;
+        cleanup(aid, clean).await;
+    });
+    Ok(cid)
+}
+
+/// Run `clean` in agent `aid` to remove uploaded script, issued by the system.
+async fn cleanup(aid: HyUuid, clean: String) {
+    let _ = PLUGIN_INSTANCE
+        .send_command(&aid, None, &clean, CommandOption::default(), None)
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, kind: ParamKind) -> ScriptParam {
+        ScriptParam {
+            name: name.to_owned(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn values(x: &[(&str, &str)]) -> HashMap<String, String> {
+        x.iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn name() {
+        assert!(param_name_validator("host_1").is_ok());
+        assert!(param_name_validator("_x").is_ok());
+        assert!(param_name_validator("1x").is_err());
+        assert!(param_name_validator("a-b").is_err());
+        assert!(param_name_validator("").is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let params = vec![
+            ScriptParam {
+                required: true,
+                ..param("host", ParamKind::String)
+            },
+            ScriptParam {
+                default: Some(String::from("3")),
+                ..param("count", ParamKind::Integer)
+            },
+            param("force", ParamKind::Boolean),
+            ScriptParam {
+                choices: vec![String::from("a"), String::from("b")],
+                ..param("mode", ParamKind::Choice)
+            },
+        ];
+        assert_eq!(
+            resolve_params(&params, &values(&[("host", "$(reboot)")])).unwrap(),
+            values(&[("PARAM_host", "$(reboot)"), ("PARAM_count", "3")])
+        );
+        assert_eq!(
+            resolve_params(
+                &params,
+                &values(&[
+                    ("host", "x"),
+                    ("count", "-1"),
+                    ("force", "true"),
+                    ("mode", "b")
+                ])
+            )
+            .unwrap(),
+            values(&[
+                ("PARAM_host", "x"),
+                ("PARAM_count", "-1"),
+                ("PARAM_force", "true"),
+                ("PARAM_mode", "b")
+            ])
+        );
+        assert!(resolve_params(&params, &values(&[])).is_err());
+        assert!(resolve_params(&params, &values(&[("host", "x"), ("other", "1")])).is_err());
+        assert!(resolve_params(&params, &values(&[("host", "x"), ("count", "1.5")])).is_err());
+        assert!(resolve_params(&params, &values(&[("host", "x"), ("force", "yes")])).is_err());
+        assert!(resolve_params(&params, &values(&[("host", "x"), ("mode", "c")])).is_err());
+        assert!(resolve_params(&params, &values(&[("host", "a\0b")])).is_err());
+    }
+
+    #[test]
+    fn path() {
+        let id = HyUuid::new();
+        assert_eq!(
+            upload_path(&id, "powershell -ExecutionPolicy Bypass -File", true),
+            (
+                format!("C:\\Windows\\Temp\\monitor_script_{id}.ps1"),
+                format!("cmd /C del /F /Q C:\\Windows\\Temp\\monitor_script_{id}.ps1")
+            )
+        );
+        assert_eq!(
+            upload_path(&id, "bash", false),
+            (
+                format!("/tmp/monitor_script_{id}.sh"),
+                format!("rm -f /tmp/monitor_script_{id}.sh")
+            )
+        );
+        assert_eq!(extension("C:\\Windows\\System32\\CMD.EXE /C"), ".bat");
+        assert_eq!(extension("/usr/bin/python3"), ".py");
+        assert_eq!(extension("pwsh.exe"), ".ps1");
+        assert_eq!(extension("custom"), "");
+        assert_eq!(extension(""), "");
+    }
+
+    #[test]
+    fn validator() {
+        let x = vec![
+            param("a", ParamKind::String),
+            param("a", ParamKind::Integer),
+        ];
+        assert!(params_validator(&x).is_err());
+        assert!(params_validator(&x[..1]).is_ok());
+        assert!(param_validator(&param("a", ParamKind::Choice)).is_err());
+        assert!(
+            param_validator(&ScriptParam {
+                default: Some(String::from("x")),
+                ..param("a", ParamKind::Integer)
+            })
+            .is_err()
+        );
+    }
+}
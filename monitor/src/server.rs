@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::mem;
 use std::net::SocketAddr;
 use std::sync::{Arc, OnceLock};
 use std::time::Duration;
-use std::{collections::HashSet, mem};
 
 use actix::clock::{Instant, Interval, interval};
 use actix_cloud::{
@@ -20,12 +21,15 @@ use actix_cloud::{
     },
     tracing::{Instrument, Span, debug, error, field, info, info_span, warn},
 };
-use aes_gcm::aead::{Aead, OsRng};
+use aes_gcm::aead::{Aead, OsRng, rand_core::RngCore};
 use aes_gcm::{AeadCore, Aes256Gcm, KeyInit, Nonce};
+use dashmap::DashMap;
 use derivative::Derivative;
 use ecies::SecretKey;
 use miniz_oxide::deflate::compress_to_vec;
 use parking_lot::RwLock;
+use serde::Serialize;
+use serde_repr::Serialize_repr;
 use skynet_api::service::{self, Service};
 use skynet_api::{
     HyUuid, Result, anyhow::anyhow, bail, ffi_rpc::registry::Registry, request::Condition,
@@ -33,17 +37,41 @@ use skynet_api::{
 };
 use skynet_api_monitor::{
     AgentStatus, CommandRspMessage, FileRspMessage, FrontendMessage, HandshakeReqMessage,
-    HandshakeRspMessage, HandshakeStatus, ID, InfoMessage, Message, StatusReqMessage,
-    StatusRspMessage, UpdateMessage, frontend_message, message::Data, prost::Message as _,
-    viewer::passive_agents::PassiveAgentViewer,
+    HandshakeRspMessage, HandshakeStatus, ID, InfoMessage, Message, MigrateRspMessage,
+    StatusReqMessage, StatusRspMessage, UpdateMessage,
+    entity::passive_agents,
+    frontend_message,
+    message::Data,
+    prost::Message as _,
+    viewer::{
+        jobs::JobViewer, passive_agents::PassiveAgentViewer, schedule_runs::ScheduleRunViewer,
+    },
 };
 
-use crate::{PLUGIN_INSTANCE, WEBPUSH_ALERT};
+use crate::{PLUGIN_INSTANCE, WEBPUSH_ALERT, proxy, ws::ShellService};
 
 const MAX_MESSAGE_SIZE: u32 = 1024 * 1024 * 128;
 const AES256_KEY_SIZE: usize = 32;
 const SECRET_KEY_SIZE: usize = 32;
 const MAGIC_NUMBER: &[u8] = b"SKNT";
+/// Keep finished agent requests in memory for polling.
+const RPC_RETENTION: Duration = Duration::from_secs(3600);
+/// Max agent requests kept in memory, finished ones are still available in database.
+const RPC_CAP: usize = 1024;
+/// Max time to read PROXY protocol header when message timeout is disabled.
+const PROXY_TIMEOUT: u32 = 10;
+
+/// Authentication failure before handshake, counted by the firewall.
+#[derive(Debug)]
+struct AuthError(&'static str);
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for AuthError {}
 
 #[derive(Derivative)]
 #[derivative(Default(new = "true"))]
@@ -202,7 +230,8 @@ impl Frame {
             Message::decode(&buf[MAGIC_NUMBER.len()..]).map_err(Into::into)
         } else {
             // handshake
-            let data = ecies::decrypt(&self.sk, &buf).map_err(|e| anyhow!(e))?;
+            let data =
+                ecies::decrypt(&self.sk, &buf).map_err(|_| AuthError("Invalid handshake key"))?;
             if data.len() > AES256_KEY_SIZE {
                 let (key, uid) = data.split_at(AES256_KEY_SIZE);
                 self.cipher = Some(Aes256Gcm::new_from_slice(key)?);
@@ -213,7 +242,7 @@ impl Frame {
                     })),
                 })
             } else {
-                bail!("Invalid handshake data");
+                Err(AuthError("Invalid handshake data").into())
             }
         }
     }
@@ -237,13 +266,20 @@ struct Handler {
     trace_id: HyUuid,
     start_time: DateTime<Utc>,
     client_addr: SocketAddr,
+    apid: Option<HyUuid>,
     aid: Option<HyUuid>,
     status_clock: Option<Interval>,
     message: Option<UnboundedReceiver<Data>>,
 }
 
 impl Handler {
-    fn new(trace_id: HyUuid, client_addr: SocketAddr, shutdown_rx: Receiver<()>) -> Self {
+    /// Create connection handler, `apid` is set when connecting to passive agent.
+    fn new(
+        trace_id: HyUuid,
+        client_addr: SocketAddr,
+        apid: Option<HyUuid>,
+        shutdown_rx: Receiver<()>,
+    ) -> Self {
         Self {
             client_seq: 0,
             server_seq: 0,
@@ -251,6 +287,7 @@ impl Handler {
             trace_id,
             start_time: Utc::now(),
             client_addr,
+            apid,
             aid: None,
             status_clock: None,
             message: None,
@@ -266,6 +303,7 @@ impl Handler {
         ret
     }
 
+    #[allow(clippy::collapsible_if)]
     async fn handshake(&mut self, frame: &mut Frame, msg: Message) -> Result<()> {
         if msg.seq == 0 && self.client_seq == 0 {
             if let Some(Data::HandshakeReq(data)) = msg.data {
@@ -288,6 +326,11 @@ impl Handler {
                         bail!("Already login");
                     },
                 );
+                if let Some(apid) = &self.apid {
+                    PLUGIN_INSTANCE
+                        .link_passive(&tx, apid, &self.aid.unwrap())
+                        .await?;
+                }
                 tx.commit().await?;
 
                 self.message = Some(PLUGIN_INSTANCE.bind_message(&self.aid.unwrap()));
@@ -307,7 +350,7 @@ impl Handler {
                     .await;
             }
         }
-        bail!("Invalid handshake message")
+        Err(AuthError("Invalid handshake message").into())
     }
 
     fn handle_status(&mut self, _frame: &mut Frame, data: StatusRspMessage) -> Result<()> {
@@ -315,6 +358,7 @@ impl Handler {
         Ok(())
     }
 
+    #[allow(clippy::collapsible_if)]
     async fn handle_info(&mut self, frame: &mut Frame, data: InfoMessage) -> Result<()> {
         let aid = self.aid.unwrap();
         let sys = data.os.clone().unwrap_or_default();
@@ -381,13 +425,19 @@ impl Handler {
         Ok(())
     }
 
+    fn handle_migrate(&mut self, _frame: &mut Frame, data: MigrateRspMessage) -> Result<()> {
+        let aid = self.aid.unwrap();
+        if data.success {
+            info!(%aid, rid = data.id, "Agent migrated");
+        } else {
+            warn!(%aid, rid = data.id, error = data.message, "Agent migration rolled back");
+        }
+        PLUGIN_INSTANCE.update_migrate_result(&aid, &HyUuid::parse(&data.id)?, data);
+        Ok(())
+    }
+
     fn handle_command(&mut self, _frame: &mut Frame, data: CommandRspMessage) -> Result<()> {
-        PLUGIN_INSTANCE.update_command_output(
-            &self.aid.unwrap(),
-            &HyUuid::parse(&data.id)?,
-            data.code,
-            data.output,
-        );
+        PLUGIN_INSTANCE.update_command_output(&self.aid.unwrap(), &HyUuid::parse(&data.id)?, data);
         Ok(())
     }
 
@@ -405,38 +455,61 @@ impl Handler {
                 match data {
                     Data::Info(data) => self.handle_info(frame, data).await,
                     Data::StatusRsp(data) => self.handle_status(frame, data),
-                    Data::ShellOutput(mut data) => {
-                        let id = HyUuid::parse(&data.token.unwrap_or_default())?;
-                        data.token = None;
-                        if let Some(id) = PLUGIN_INSTANCE.shell_binding.get(&id) {
-                            if let Some(inst) = PLUGIN_INSTANCE.shell.get(&id) {
-                                let _ = inst
-                                    .send(FrontendMessage {
-                                        id: None,
-                                        data: Some(frontend_message::Data::ShellOutput(data)),
-                                    })
-                                    .await;
-                            }
-                        }
+                    Data::ShellOutput(data) => {
+                        let id = HyUuid::parse(data.token.as_deref().unwrap_or_default())?;
+                        PLUGIN_INSTANCE.recording.output(&id, &data.data);
+                        PLUGIN_INSTANCE.session.output(&id, &data.data);
+                        ShellService::broadcast(
+                            &id,
+                            FrontendMessage {
+                                id: None,
+                                data: Some(frontend_message::Data::ShellOutput(data)),
+                            },
+                        )
+                        .await;
                         Ok(())
                     }
-                    Data::ShellError(mut data) => {
-                        let id = HyUuid::parse(&data.token.unwrap_or_default())?;
-                        data.token = None;
-                        if let Some(id) = PLUGIN_INSTANCE.shell_binding.get(&id) {
-                            if let Some(inst) = PLUGIN_INSTANCE.shell.get(&id) {
-                                let _ = inst
-                                    .send(FrontendMessage {
-                                        id: None,
-                                        data: Some(frontend_message::Data::ShellError(data)),
-                                    })
-                                    .await;
-                            }
-                        }
+                    Data::ShellError(data) => {
+                        let id = HyUuid::parse(data.token.as_deref().unwrap_or_default())?;
+                        ShellService::broadcast(
+                            &id,
+                            FrontendMessage {
+                                id: None,
+                                data: Some(frontend_message::Data::ShellError(data)),
+                            },
+                        )
+                        .await;
                         Ok(())
                     }
                     Data::FileRsp(data) => self.handle_file(frame, data),
                     Data::CommandRsp(data) => self.handle_command(frame, data),
+                    Data::MigrateRsp(data) => self.handle_migrate(frame, data),
+                    Data::StreamOpenRsp(data) => {
+                        let id = HyUuid::parse(&data.id)?;
+                        PLUGIN_INSTANCE
+                            .stream
+                            .opened(&self.aid.unwrap(), &id, data.error);
+                        Ok(())
+                    }
+                    Data::StreamDataRsp(data) => {
+                        let id = HyUuid::parse(&data.id)?;
+                        PLUGIN_INSTANCE
+                            .stream
+                            .data(&self.aid.unwrap(), &id, data.data);
+                        Ok(())
+                    }
+                    Data::StreamAckRsp(data) => {
+                        let id = HyUuid::parse(&data.id)?;
+                        PLUGIN_INSTANCE
+                            .stream
+                            .ack(&self.aid.unwrap(), &id, data.size);
+                        Ok(())
+                    }
+                    Data::StreamCloseRsp(data) => {
+                        let id = HyUuid::parse(&data.id)?;
+                        PLUGIN_INSTANCE.stream.closed(&self.aid.unwrap(), &id);
+                        Ok(())
+                    }
                     _ => bail!("Invalid message type"),
                 }
             } else {
@@ -467,8 +540,12 @@ impl Handler {
             .await;
     }
 
-    async fn process(&mut self, stream: TcpStream, key: SecretKey) {
+    /// Process connection until closed.
+    ///
+    /// Return agent id when handshake succeeded, otherwise the error before handshake.
+    async fn process(&mut self, stream: TcpStream, key: SecretKey) -> Result<HyUuid> {
         let mut frame = Frame::new(stream, key);
+        let mut error = None;
         loop {
             select! {
                 msg = frame.read_msg_timeout(*PLUGIN_INSTANCE.msg_timeout.read()) => {
@@ -478,6 +555,7 @@ impl Handler {
                                 if let Err(e) = self.handshake(&mut frame, msg).await {
                                     debug!(error = %e, "Error handshake");
                                     frame.close().await;
+                                    error = Some(e);
                                 }
                             } else if let Err(e) = self.handle_msg(&mut frame, msg).await {
                                 debug!(error = %e, "Error handle message");
@@ -489,6 +567,7 @@ impl Handler {
                                 let time = (end_time - self.start_time).num_microseconds().unwrap_or(0);
                                 info!(_time = end_time.timestamp_micros(), alive_time = time, error = %e, "Connection lost");
                             }
+                            error.get_or_insert(e);
                             break;
                         }
                     }
@@ -515,23 +594,59 @@ impl Handler {
         if let Some(aid) = self.aid {
             PLUGIN_INSTANCE.logout(&aid);
             self.message = None;
+            Ok(aid)
+        } else {
+            // Passive agents are connected by us, only count incoming connections.
+            if self.apid.is_none() && error.as_ref().is_some_and(|e| e.is::<AuthError>()) {
+                PLUGIN_INSTANCE
+                    .firewall
+                    .handshake_failed(self.client_addr.ip());
+            }
+            Err(error.unwrap_or_else(|| anyhow!("Connection closed before handshake")))
         }
     }
 }
 
+#[derive(Serialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum PassiveStatus {
+    #[default]
+    Inactive = 0,
+    Active,
+    Waiting,
+    Failed,
+}
+
+impl PassiveStatus {
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Active | Self::Waiting)
+    }
+}
+
+/// Passive agent connection health.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PassiveState {
+    pub status: PassiveStatus,
+    pub last_attempt: Option<i64>,
+    pub last_error: Option<String>,
+    pub failures: u32,
+}
+
 struct Listener {
     listener: TcpListener,
     passive_rx: UnboundedReceiver<HyUuid>,
-    passive_agent: Arc<RwLock<HashSet<HyUuid>>>,
+    passive_agent: Arc<DashMap<HyUuid, PassiveState>>,
     shutdown_rx: Receiver<()>,
     alert_clock: Interval,
+    job_clock: Interval,
+    queue_clock: Interval,
 }
 
 impl Listener {
     async fn new(
         addr: &str,
         passive_rx: UnboundedReceiver<HyUuid>,
-        passive_agent: Arc<RwLock<HashSet<HyUuid>>>,
+        passive_agent: Arc<DashMap<HyUuid, PassiveState>>,
         shutdown_rx: Receiver<()>,
     ) -> Result<Self> {
         let listener = TcpListener::bind(&addr).await?;
@@ -541,45 +656,151 @@ impl Listener {
             passive_agent,
             shutdown_rx,
             alert_clock: interval(Duration::from_secs(5)),
+            job_clock: interval(Duration::from_secs(3600)),
+            queue_clock: interval(Duration::from_secs(60)),
         })
     }
 
-    async fn passive(addr: &str, key: SecretKey, rx: Receiver<()>) -> Result<()> {
+    async fn passive(
+        apid: &HyUuid,
+        addr: &str,
+        key: SecretKey,
+        rx: Receiver<()>,
+    ) -> Result<HyUuid> {
         info!(plugin = %ID, "Monitor connecting to {}...", addr);
         let stream = TcpStream::connect(addr).await?;
         let addr = stream.peer_addr()?;
         let trace_id = HyUuid::new();
-        Handler::new(trace_id, addr, rx)
+        Handler::new(trace_id, addr, Some(*apid), rx)
             .process(stream, key)
             .instrument(info_span!("Agent connection", plugin = %ID, trace_id = %trace_id, ip = addr.to_string(), aid = field::Empty))
-            .await;
-        Ok(())
+            .await
+    }
+
+    /// Get retry delay of passive agent `m` after `failures` consecutive failures.
+    fn backoff(m: &passive_agents::Model, failures: u32) -> Duration {
+        let mut delay = f64::from(m.retry_time);
+        if failures > 1 {
+            delay *= m
+                .retry_multiplier
+                .powi(i32::try_from(failures - 1).unwrap_or(i32::MAX));
+        }
+        if m.retry_max_time > 0 {
+            delay = delay.min(m.retry_max_time.into());
+        }
+        if m.retry_jitter > 0.0 {
+            let r = f64::from(OsRng.next_u32()) / f64::from(u32::MAX);
+            delay *= 1.0 + m.retry_jitter * (2.0 * r - 1.0);
+        }
+        Duration::try_from_secs_f64(delay.clamp(0.0, u32::MAX.into())).unwrap_or_default()
     }
 
-    async fn passive_loop(key: SecretKey, rx: Receiver<()>, apid: HyUuid) -> Result<()> {
+    async fn passive_loop(
+        key: SecretKey,
+        mut rx: Receiver<()>,
+        apid: HyUuid,
+        state: Arc<DashMap<HyUuid, PassiveState>>,
+    ) -> Result<()> {
+        let mut failures = 0;
         loop {
             let m =
                 PassiveAgentViewer::find_by_id(PLUGIN_INSTANCE.db.get().unwrap(), &apid).await?;
-            if let Some(m) = m {
-                if let Err(e) = Self::passive(&m.address, key, rx.resubscribe()).await {
-                    info!(plugin = %ID, error = %e, apid = %apid, address = m.address, "Monitor connect error");
-                }
-                if m.retry_time != 0 {
-                    sleep(Duration::from_secs(m.retry_time.try_into()?)).await;
-                } else {
-                    return Ok(());
-                }
+            let Some(m) = m else {
+                state.remove(&apid);
+                return Ok(());
+            };
+            if let Some(mut x) = state.get_mut(&apid) {
+                x.status = PassiveStatus::Active;
+                x.last_attempt = Some(Utc::now().timestamp_millis());
+            }
+            let ret = Self::passive(&apid, &m.address, key, rx.resubscribe()).await;
+            if let Err(e) = &ret {
+                failures += 1;
+                info!(plugin = %ID, error = %e, apid = %apid, address = m.address, failures, "Monitor connect error");
             } else {
+                failures = 0;
+            }
+            if let Some(mut x) = state.get_mut(&apid) {
+                x.last_error = ret.err().map(|e| e.to_string());
+                x.failures = failures;
+            }
+
+            if m.retry_time == 0 {
+                return Ok(());
+            }
+            if m.retry_limit > 0 && failures >= m.retry_limit.unsigned_abs() {
+                warn!(plugin = %ID, apid = %apid, address = m.address, failures, "Monitor passive agent retry limit reached");
+                if let Some(mut x) = state.get_mut(&apid) {
+                    x.status = PassiveStatus::Failed;
+                }
                 return Ok(());
             }
+            if let Some(mut x) = state.get_mut(&apid) {
+                x.status = PassiveStatus::Waiting;
+            }
+            select! {
+                _ = sleep(Self::backoff(&m, failures)) => {},
+                _ = rx.recv() => return Ok(()),
+            }
+        }
+    }
+
+    /// Resolve real client address of `stream` connected from `addr`.
+    ///
+    /// PROXY protocol header is only accepted from trusted sources when enabled.
+    async fn resolve_addr(stream: &mut TcpStream, addr: SocketAddr) -> Result<SocketAddr> {
+        if !*PLUGIN_INSTANCE.proxy_protocol.read()
+            || !proxy::cidr_contains(&PLUGIN_INSTANCE.proxy_trusted.read(), &addr.ip())
+        {
+            return Ok(addr);
         }
+        let sec = match *PLUGIN_INSTANCE.msg_timeout.read() {
+            0 => PROXY_TIMEOUT,
+            x => x,
+        };
+        let header =
+            match timeout(Duration::from_secs(sec.into()), proxy::read_header(stream)).await {
+                Ok(x) => x?,
+                Err(_) => bail!("Read PROXY protocol header timeout"),
+            };
+        Ok(header.unwrap_or(addr))
     }
 
+    #[allow(clippy::collapsible_if)]
     async fn run(&mut self, key: SecretKey, service: Service) {
         let mut alerted: HashMap<HyUuid, i64> = HashMap::new();
         loop {
             select! {
+                _ = self.job_clock.tick() => {
+                    let retention = *PLUGIN_INSTANCE.job_retention.read();
+                    if retention != 0 {
+                        let time = Utc::now().timestamp_millis() - i64::from(retention) * 86_400_000;
+                        match JobViewer::delete_before(PLUGIN_INSTANCE.db.get().unwrap(), time).await {
+                            Ok(x) if x != 0 => info!(plugin = %ID, rows = x, "Monitor jobs purged"),
+                            Ok(_) => {}
+                            Err(e) => error!(plugin = %ID, error = %e, "Failed to purge monitor jobs"),
+                        }
+                        match ScheduleRunViewer::delete_before(PLUGIN_INSTANCE.db.get().unwrap(), time).await {
+                            Ok(x) if x != 0 => info!(plugin = %ID, rows = x, "Monitor schedule runs purged"),
+                            Ok(_) => {}
+                            Err(e) => error!(plugin = %ID, error = %e, "Failed to purge monitor schedule runs"),
+                        }
+                    }
+                },
+                _ = self.queue_clock.tick() => {
+                    match JobViewer::expire_queued(PLUGIN_INSTANCE.db.get().unwrap()).await {
+                        Ok(x) if x != 0 => info!(plugin = %ID, rows = x, "Monitor queued jobs expired"),
+                        Ok(_) => {}
+                        Err(e) => error!(plugin = %ID, error = %e, "Failed to expire monitor queued jobs"),
+                    }
+                },
                 _ = self.alert_clock.tick() => {
+                    PLUGIN_INSTANCE.firewall.purge();
+                    PLUGIN_INSTANCE.socks.purge();
+                    ShellService::purge().await;
+                    PLUGIN_INSTANCE.command.purge(RPC_RETENTION, RPC_CAP);
+                    PLUGIN_INSTANCE.file.purge(RPC_RETENTION, RPC_CAP);
+                    PLUGIN_INSTANCE.migrate_rpc.purge(RPC_RETENTION, RPC_CAP);
                     let now = Utc::now().timestamp_millis();
                     let timeout = *PLUGIN_INSTANCE.alert_timeout.read();
                     if timeout != 0 {
@@ -601,11 +822,29 @@ impl Listener {
                 },
                 c = self.listener.accept() => {
                     match c {
-                        Ok((stream, addr)) => {
+                        Ok((mut stream, addr)) => {
+                            if let Err(e) = PLUGIN_INSTANCE.firewall.check(addr.ip()) {
+                                debug!(plugin = %ID, ip = %addr, reason = %e, "Connection rejected");
+                                continue;
+                            }
                             let rx = self.shutdown_rx.resubscribe();
                             spawn(async move {
+                                let addr = match Self::resolve_addr(&mut stream, addr).await {
+                                    Ok(x) => x,
+                                    Err(e) => {
+                                        debug!(plugin = %ID, ip = %addr, error = %e, "Error PROXY protocol");
+                                        return;
+                                    }
+                                };
+                                let _guard = match PLUGIN_INSTANCE.firewall.accept(addr.ip()) {
+                                    Ok(x) => x,
+                                    Err(e) => {
+                                        debug!(plugin = %ID, ip = %addr, reason = %e, "Connection rejected");
+                                        return;
+                                    }
+                                };
                                 let trace_id = HyUuid::new();
-                                Handler::new(trace_id, addr, rx)
+                                let _ = Handler::new(trace_id, addr, None, rx)
                                     .process(stream, key)
                                     .instrument(info_span!("Agent connection", plugin = %ID, trace_id = %trace_id, ip = addr.to_string(), aid = field::Empty))
                                     .await;
@@ -618,11 +857,22 @@ impl Listener {
                     let rx = self.shutdown_rx.resubscribe();
                     let passive_agent = self.passive_agent.clone();
                     spawn(async move {
-                        passive_agent.write().insert(apid);
-                        if let Err(e) =Self::passive_loop(key, rx, apid).await{
+                        {
+                            let mut x = passive_agent.entry(apid).or_default();
+                            if x.status.is_running() {
+                                return;
+                            }
+                            x.status = PassiveStatus::Active;
+                            x.failures = 0;
+                        }
+                        if let Err(e) = Self::passive_loop(key, rx, apid, passive_agent.clone()).await {
                             error!(plugin = %ID, error = %e, apid = %apid, "Monitor passive agent error");
                         }
-                        passive_agent.write().remove(&apid);
+                        if let Some(mut x) = passive_agent.get_mut(&apid)
+                            && x.status.is_running()
+                        {
+                            x.status = PassiveStatus::Inactive;
+                        }
                     });
                 }
                 _ = self.shutdown_rx.recv() => {
@@ -639,7 +889,7 @@ pub struct Server {
     running: RwLock<bool>,
     service: OnceLock<Service>,
     passive_channel: RwLock<Option<UnboundedSender<HyUuid>>>,
-    passive_agent: Arc<RwLock<HashSet<HyUuid>>>,
+    passive_agent: Arc<DashMap<HyUuid, PassiveState>>,
     shutdown_tx: RwLock<Option<Sender<()>>>,
 }
 
@@ -683,6 +933,22 @@ impl Server {
         *self.running.read()
     }
 
+    /// Send alert webpush, `page` is the plugin page to open.
+    pub async fn alert(&self, body: String, page: &str) {
+        if let Some(x) = self.service.get() {
+            x.webpush_send(
+                &Registry::default(),
+                &WEBPUSH_ALERT,
+                &service::Message {
+                    title: String::from("Warning"),
+                    body,
+                    url: format!("/plugin/{ID}/{page}"),
+                },
+            )
+            .await;
+        }
+    }
+
     pub fn stop(&self) -> bool {
         self.shutdown_tx
             .read()
@@ -703,9 +969,17 @@ impl Server {
 
     pub fn connecting(&self) -> Vec<HyUuid> {
         self.passive_agent
-            .read()
             .iter()
-            .map(ToOwned::to_owned)
+            .filter(|x| x.status.is_running())
+            .map(|x| *x.key())
             .collect()
     }
+
+    /// Get passive agent `apid` connection state.
+    pub fn passive_state(&self, apid: &HyUuid) -> PassiveState {
+        self.passive_agent
+            .get(apid)
+            .map(|x| x.value().to_owned())
+            .unwrap_or_default()
+    }
 }
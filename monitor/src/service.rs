@@ -1,8 +1,16 @@
-use std::{cmp::max, net::SocketAddr};
+use std::{
+    cmp::max,
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
 
 use actix_cloud::{
     chrono::Utc,
-    tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    tokio::{
+        spawn,
+        sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    },
 };
 use ecies::SecretKey;
 use itertools::Itertools;
@@ -10,25 +18,68 @@ use miniz_oxide::deflate::compress_to_vec;
 use once_cell::sync::Lazy;
 use serde_json::Value;
 use skynet_api::{
-    HyUuid, Result,
+    HyUuid, Result, anyhow,
     ffi_rpc::{self, async_trait, ffi_rpc_macro::plugin_impl_trait, registry::Registry, rmp_serde},
-    sea_orm::{ActiveModelTrait, ConnectionTrait, DatabaseTransaction, EntityTrait, Set},
+    sea_orm::{
+        ActiveModelTrait, ConnectionTrait, DatabaseTransaction, EntityTrait, Set, TransactionTrait,
+    },
     service::{SError, SResult},
+    tracing::{error, info, warn},
     viewer::settings::SettingViewer,
 };
 use skynet_api_monitor::{
-    Agent, AgentCommand, AgentFile, AgentStatus, CommandKillMessage, CommandReqMessage,
-    FileReqMessage, ID, InfoMessage, QuitMessage, StatusRspMessage, entity::agents, message::Data,
-    semver::Version, viewer::agents::AgentViewer,
+    Agent, AgentCommand, AgentCommandOutput, AgentFile, AgentStatus, CommandKillMessage,
+    CommandOption, CommandReqMessage, CommandRspMessage, FileReqMessage, ID, InfoMessage,
+    MigrateMessage, MigrateRspMessage, QuitMessage, StatusRspMessage,
+    entity::{
+        agents,
+        jobs::{self, JobKind, JobStatus},
+    },
+    message::Data,
+    semver::Version,
+    viewer::{
+        agent_settings::AgentSettingViewer, agents::AgentViewer, jobs::JobViewer,
+        passive_agents::PassiveAgentViewer,
+    },
+};
+
+use crate::{
+    PLUGIN_INSTANCE, Plugin,
+    firewall::FirewallSetting,
+    limit::Pending,
+    migrate::{MigrateJob, MigrateStatus},
+    recording::RecordMode,
+    rpc::RpcError,
+    session::ShellLimit,
+    socks::SocksSetting,
 };
 
-use crate::{PLUGIN_INSTANCE, Plugin};
+const MAX_MIGRATE_JOB: usize = 64;
+const AGENT_SETTING_LABELS: &str = "labels";
+const AGENT_SETTING_COMMAND_LIMIT: &str = "command_limit";
+const AGENT_SETTING_SHELL_RECORD: &str = "shell_record";
+const AGENT_SETTING_SHELL: &str = "shell";
+/// Max total output of all commands kept in memory, unit bytes.
+const COMMAND_OUTPUT_BUDGET: usize = 64 * 1024 * 1024;
+/// Max command output saved in database, unit bytes.
+const MAX_JOB_OUTPUT: usize = 1024 * 1024;
 
 static SETTING_ADDRESS: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.address"));
 static SETTING_CERTIFICATE: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.certificate"));
 static SETTING_SHELL: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.shell"));
 static SETTING_MSG_TIMEOUT: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.msg.timeout"));
 static SETTING_ALERT_TIMEOUT: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.alert.timeout"));
+static SETTING_PROXY_PROTOCOL: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.proxy.enable"));
+static SETTING_PROXY_TRUSTED: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.proxy.trusted"));
+static SETTING_FIREWALL: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.firewall"));
+static SETTING_JOB_RETENTION: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.job.retention"));
+static SETTING_SHELL_RECORD: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.shell.record"));
+static SETTING_SHELL_GRACE: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.shell.grace"));
+static SETTING_SHELL_LIMIT: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.shell.limit"));
+static SETTING_SOCKS: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.socks"));
+static SETTING_COMMAND_GROUP_LIMIT: Lazy<String> =
+    Lazy::new(|| format!("plugin.{ID}.command.group_limit"));
+static SETTING_ACL: Lazy<String> = Lazy::new(|| format!("plugin.{ID}.acl.enable"));
 
 #[plugin_impl_trait]
 impl skynet_api_monitor::Service for Plugin {
@@ -45,18 +96,42 @@ impl skynet_api_monitor::Service for Plugin {
     }
 
     async fn run_command(&self, _: &Registry, id: HyUuid, cmd: String) -> SResult<HyUuid> {
-        if let Some(mut x) = self.agent.get_mut(&id) {
-            if let Some(msg) = &x.message {
-                let id = HyUuid::new();
-                msg.send(Data::CommandReq(CommandReqMessage {
-                    id: id.to_string(),
-                    cmd: cmd.to_owned(),
-                }))?;
-                x.command.insert(id, None);
-                return Ok(id);
+        self.send_command(&id, None, &cmd, CommandOption::new(), None)
+            .await
+    }
+
+    async fn run_command_with_option(
+        &self,
+        _: &Registry,
+        id: HyUuid,
+        cmd: String,
+        option: CommandOption,
+    ) -> SResult<HyUuid> {
+        self.send_command(&id, None, &cmd, option, None).await
+    }
+
+    async fn run_command_wait(
+        &self,
+        _: &Registry,
+        id: HyUuid,
+        cmd: String,
+        mut option: CommandOption,
+        timeout: u32,
+    ) -> SResult<AgentCommand> {
+        option.queue_ttl = 0;
+        let timeout = (timeout != 0).then(|| Duration::from_secs(timeout.into()));
+        let cid = self.send_command(&id, None, &cmd, option, timeout).await?;
+        // Command may wait in the agent queue before its deadline is started,
+        // the caller deadline starts from now.
+        match self.command.wait(&cid, timeout).await {
+            Ok(x) => Ok(x),
+            Err(e) => {
+                if e == RpcError::Timeout {
+                    let _ = self.send_command_kill(&id, &cid, true);
+                }
+                Err(e.into())
             }
         }
-        Err(SError::new("Agent not exist or offline"))
     }
 
     /// Get agent `id` command `cid` output.
@@ -66,7 +141,30 @@ impl skynet_api_monitor::Service for Plugin {
         id: HyUuid,
         cid: HyUuid,
     ) -> Option<AgentCommand> {
-        self.agent.get(&id)?.command.get(&cid)?.to_owned()
+        if let Some(x) = self.command.get(&id, &cid) {
+            return x.1;
+        }
+        self.find_job(&id, &cid, JobKind::Command)
+            .await
+            .map(Into::into)
+    }
+
+    async fn read_command_output(
+        &self,
+        _: &Registry,
+        id: HyUuid,
+        cid: HyUuid,
+        offset: u64,
+    ) -> Option<AgentCommandOutput> {
+        if let Some(x) = self
+            .command
+            .with(&id, &cid, |_, x| x.map(|x| x.read(offset)))
+        {
+            return x;
+        }
+        self.find_job(&id, &cid, JobKind::Command)
+            .await
+            .map(|x| AgentCommand::from(x).read(offset))
     }
 
     /// Kill async command `cid` in agent `id`.
@@ -77,17 +175,7 @@ impl skynet_api_monitor::Service for Plugin {
         cid: HyUuid,
         force: bool,
     ) -> SResult<()> {
-        if let Some(x) = self.agent.get(&id) {
-            if let Some(x) = &x.message {
-                return x
-                    .send(Data::CommandKill(CommandKillMessage {
-                        id: cid.to_string(),
-                        force,
-                    }))
-                    .map_err(Into::into);
-            }
-        }
-        Err(SError::new("Agent not exist or offline"))
+        self.send_command_kill(&id, &cid, force)
     }
 
     /// Send file to agent `id`.
@@ -101,28 +189,377 @@ impl skynet_api_monitor::Service for Plugin {
         path: String,
         data: Vec<u8>,
     ) -> SResult<HyUuid> {
-        if let Some(mut x) = self.agent.get_mut(&id) {
-            if let Some(msg) = &x.message {
-                let id = HyUuid::new();
-                let data = compress_to_vec(&data, 6);
-                msg.send(Data::FileReq(FileReqMessage {
-                    id: id.to_string(),
-                    path: path.to_owned(),
-                    data,
-                }))?;
-                x.file.insert(id, None);
-                return Ok(id);
-            }
-        }
-        Err(SError::new("Agent not exist or offline"))
+        self.send_file(&id, None, &path, &data, 0).await
     }
 
     async fn get_file_result(&self, _: &Registry, id: HyUuid, fid: HyUuid) -> Option<AgentFile> {
-        self.agent.get(&id)?.file.get(&fid)?.to_owned()
+        if let Some(x) = self.file.get(&id, &fid) {
+            return x.1;
+        }
+        self.find_job(&id, &fid, JobKind::File)
+            .await
+            .map(Into::into)
+    }
+
+    async fn send_file_queued(
+        &self,
+        _: &Registry,
+        id: HyUuid,
+        path: String,
+        data: Vec<u8>,
+        ttl: u32,
+    ) -> SResult<HyUuid> {
+        self.send_file(&id, None, &path, &data, ttl).await
+    }
+
+    async fn get_job_status(&self, _: &Registry, id: HyUuid, jid: HyUuid) -> Option<JobStatus> {
+        self.job_status(&id, &jid).await
     }
 }
 
 impl Plugin {
+    /// Send command `cmd` issued by user `uid` to agent `id` with `option`,
+    /// expired after `timeout` when set.
+    ///
+    /// Return generated command id.
+    pub async fn send_command(
+        &self,
+        id: &HyUuid,
+        uid: Option<HyUuid>,
+        cmd: &str,
+        option: CommandOption,
+        timeout: Option<Duration>,
+    ) -> SResult<HyUuid> {
+        let Some(msg) = self.agent.get(id).and_then(|x| x.message.clone()) else {
+            if option.queue_ttl != 0 && self.agent.contains_key(id) {
+                let ttl = option.queue_ttl;
+                let payload = serde_json::to_vec(&option)?;
+                return self
+                    .queue_job(id, uid, JobKind::Command, cmd, payload, ttl)
+                    .await;
+            }
+            return Err(SError::new("Agent not exist or offline"));
+        };
+        let cid = HyUuid::new();
+        JobViewer::create(self.db.get().unwrap(), &cid, id, uid, JobKind::Command, cmd).await?;
+        self.launch_command(&msg, id, &cid, cmd, option, timeout)?;
+        Ok(cid)
+    }
+
+    /// Register command `cid` of agent `id` and send it when a slot is free,
+    /// otherwise it waits in the agent command queue.
+    fn launch_command(
+        &self,
+        msg: &UnboundedSender<Data>,
+        id: &HyUuid,
+        cid: &HyUuid,
+        cmd: &str,
+        option: CommandOption,
+        timeout: Option<Duration>,
+    ) -> SResult<()> {
+        // Deadline is started when dispatched.
+        self.command.register(id, cid, None);
+        Self::persist_command(*id, *cid);
+        if let Some(x) = self
+            .limiter
+            .acquire(id, Pending::new(*cid, cmd, option, timeout))
+        {
+            self.dispatch_command(msg, id, x)?;
+        }
+        Ok(())
+    }
+
+    fn dispatch_command(
+        &self,
+        msg: &UnboundedSender<Data>,
+        id: &HyUuid,
+        x: Pending,
+    ) -> SResult<()> {
+        if let Some(timeout) = x.timeout {
+            self.command.set_deadline(&x.cid, timeout);
+        }
+        if let Err(e) = msg.send(Data::CommandReq(CommandReqMessage {
+            id: x.cid.to_string(),
+            cmd: x.cmd,
+            cwd: x.option.cwd,
+            env: x.option.env,
+            timeout: x.option.timeout,
+            user: x.option.user,
+            stdin: x.option.stdin,
+            output_limit: x.option.output_limit,
+        })) {
+            self.command.cancel(id, &x.cid);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Release slot of finished command `cid` in agent `id`, then send queued commands.
+    fn release_command(&self, id: &HyUuid, cid: &HyUuid) {
+        self.dispatch_ready(self.limiter.release(id, cid));
+    }
+
+    /// Send `ready` commands taking free slots, slots are released again when failed to send.
+    fn dispatch_ready(&self, ready: Vec<(HyUuid, Pending)>) {
+        let mut ready = VecDeque::from(ready);
+        while let Some((id, x)) = ready.pop_front() {
+            let cid = x.cid;
+            let pending = self
+                .command
+                .with(&id, &cid, |status, _| status.is_pending())
+                .unwrap_or_default();
+            let sent = pending
+                && self
+                    .agent
+                    .get(&id)
+                    .and_then(|x| x.message.clone())
+                    .is_some_and(|msg| self.dispatch_command(&msg, &id, x).is_ok());
+            if !sent {
+                ready.extend(self.limiter.release(&id, &cid));
+            }
+        }
+    }
+
+    /// Send file `data` issued by user `uid` to agent `id`, saved to `path`.
+    /// File contents will be compressed automatically.
+    ///
+    /// Queued and expired after `ttl` seconds when agent is offline, 0 to fail immediately.
+    ///
+    /// Return generated file id.
+    pub async fn send_file(
+        &self,
+        id: &HyUuid,
+        uid: Option<HyUuid>,
+        path: &str,
+        data: &[u8],
+        ttl: u32,
+    ) -> SResult<HyUuid> {
+        let Some(msg) = self.agent.get(id).and_then(|x| x.message.clone()) else {
+            if ttl != 0 && self.agent.contains_key(id) {
+                let payload = compress_to_vec(data, 6);
+                return self
+                    .queue_job(id, uid, JobKind::File, path, payload, ttl)
+                    .await;
+            }
+            return Err(SError::new("Agent not exist or offline"));
+        };
+        let fid = HyUuid::new();
+        JobViewer::create(self.db.get().unwrap(), &fid, id, uid, JobKind::File, path).await?;
+        self.dispatch_file(&msg, id, &fid, path, compress_to_vec(data, 6))?;
+        Ok(fid)
+    }
+
+    fn dispatch_file(
+        &self,
+        msg: &UnboundedSender<Data>,
+        id: &HyUuid,
+        fid: &HyUuid,
+        path: &str,
+        data: Vec<u8>,
+    ) -> SResult<()> {
+        self.file.register(id, fid, None);
+        Self::persist_file(*id, *fid);
+        if let Err(e) = msg.send(Data::FileReq(FileReqMessage {
+            id: fid.to_string(),
+            path: path.to_owned(),
+            data,
+        })) {
+            self.file.cancel(id, fid);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Queue job `cmd` of `kind` with request `payload` until agent `id` login.
+    ///
+    /// Return generated job id.
+    async fn queue_job(
+        &self,
+        id: &HyUuid,
+        uid: Option<HyUuid>,
+        kind: JobKind,
+        cmd: &str,
+        payload: Vec<u8>,
+        ttl: u32,
+    ) -> SResult<HyUuid> {
+        let jid = HyUuid::new();
+        let expire_at = Utc::now().timestamp_millis() + i64::from(ttl) * 1000;
+        let tx = self.db.get().unwrap().begin().await?;
+        JobViewer::create(&tx, &jid, id, uid, kind, cmd).await?;
+        JobViewer::enqueue(&tx, &jid, payload, expire_at).await?;
+        tx.commit().await?;
+        Ok(jid)
+    }
+
+    /// Deliver queued jobs of agent `id`.
+    fn replay_queue(id: HyUuid) {
+        spawn(async move {
+            let db = PLUGIN_INSTANCE.db.get().unwrap();
+            let queued = match JobViewer::find_queued(db, &id).await {
+                Ok(x) => x,
+                Err(e) => {
+                    error!(plugin = %ID, aid = %id, error = %e, "Failed to load queued jobs");
+                    return;
+                }
+            };
+            for i in queued {
+                let Some(msg) = PLUGIN_INSTANCE
+                    .agent
+                    .get(&id)
+                    .and_then(|x| x.message.clone())
+                else {
+                    return;
+                };
+                match JobViewer::deliver(db, &i.id).await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        error!(plugin = %ID, aid = %id, jid = %i.id, error = %e, "Failed to deliver queued job");
+                        continue;
+                    }
+                }
+                let payload = i.payload.unwrap_or_default();
+                let ret = match i.kind {
+                    JobKind::Command => serde_json::from_slice(&payload)
+                        .map_err(Into::into)
+                        .and_then(|option| {
+                            PLUGIN_INSTANCE.launch_command(&msg, &id, &i.id, &i.cmd, option, None)
+                        }),
+                    JobKind::File => {
+                        PLUGIN_INSTANCE.dispatch_file(&msg, &id, &i.id, &i.cmd, payload)
+                    }
+                };
+                if let Err(e) = ret {
+                    let e = anyhow::Error::from(e);
+                    error!(plugin = %ID, aid = %id, jid = %i.id, error = %e, "Failed to deliver queued job");
+                    let _ = JobViewer::finish(
+                        db,
+                        &i.id,
+                        JobStatus::Cancelled,
+                        None,
+                        e.to_string().into_bytes(),
+                        false,
+                        false,
+                    )
+                    .await;
+                } else {
+                    info!(plugin = %ID, aid = %id, jid = %i.id, "Queued job delivered");
+                }
+            }
+        });
+    }
+
+    /// Get job `jid` status of agent `aid`, including queued ones.
+    pub async fn job_status(&self, aid: &HyUuid, jid: &HyUuid) -> Option<JobStatus> {
+        if let Some(x) = self.command.with(aid, jid, |status, _| status) {
+            return Some(x.into());
+        }
+        if let Some(x) = self.file.with(aid, jid, |status, _| status) {
+            return Some(x.into());
+        }
+        let x = JobViewer::find_by_id(self.db.get().unwrap(), jid)
+            .await
+            .ok()
+            .flatten()
+            .filter(|x| x.aid == *aid)?;
+        if x.status == JobStatus::Queued
+            && x.expire_at
+                .is_some_and(|t| t <= Utc::now().timestamp_millis())
+        {
+            Some(JobStatus::Expired)
+        } else {
+            Some(x.status)
+        }
+    }
+
+    /// Save command `cid` of agent `aid` to database when finished.
+    fn persist_command(aid: HyUuid, cid: HyUuid) {
+        spawn(async move {
+            let status = PLUGIN_INSTANCE.command.finished(&cid).await;
+            PLUGIN_INSTANCE.release_command(&aid, &cid);
+            let Some(status) = status else {
+                return;
+            };
+            let x = PLUGIN_INSTANCE
+                .command
+                .with(&aid, &cid, |_, x| {
+                    x.map(|x| AgentCommand {
+                        code: x.code,
+                        output: x.output[..x.output.len().min(MAX_JOB_OUTPUT)].to_vec(),
+                        timed_out: x.timed_out,
+                        truncated: x.truncated || x.output.len() > MAX_JOB_OUTPUT,
+                    })
+                })
+                .flatten()
+                .unwrap_or_default();
+            if let Err(e) = JobViewer::finish(
+                PLUGIN_INSTANCE.db.get().unwrap(),
+                &cid,
+                status.into(),
+                x.code,
+                x.output,
+                x.timed_out,
+                x.truncated,
+            )
+            .await
+            {
+                error!(plugin = %ID, cid = %cid, error = %e, "Failed to save command result");
+            }
+        });
+    }
+
+    /// Save file `fid` of agent `aid` to database when finished.
+    fn persist_file(aid: HyUuid, fid: HyUuid) {
+        spawn(async move {
+            let Some(status) = PLUGIN_INSTANCE.file.finished(&fid).await else {
+                return;
+            };
+            let x = PLUGIN_INSTANCE.file.get(&aid, &fid).and_then(|x| x.1);
+            if let Err(e) = JobViewer::finish(
+                PLUGIN_INSTANCE.db.get().unwrap(),
+                &fid,
+                status.into(),
+                x.as_ref().and_then(|x| x.code.try_into().ok()),
+                x.map(|x| x.message.into_bytes()).unwrap_or_default(),
+                false,
+                false,
+            )
+            .await
+            {
+                error!(plugin = %ID, fid = %fid, error = %e, "Failed to save file result");
+            }
+        });
+    }
+
+    /// Find job `jid` of agent `aid` with `kind` in database.
+    pub async fn find_job(&self, aid: &HyUuid, jid: &HyUuid, kind: JobKind) -> Option<jobs::Model> {
+        JobViewer::find_by_id(self.db.get().unwrap(), jid)
+            .await
+            .ok()
+            .flatten()
+            .filter(|x| x.aid == *aid && x.kind == kind)
+    }
+
+    /// Kill command `cid` in agent `id`.
+    #[allow(clippy::collapsible_if)]
+    pub fn send_command_kill(&self, id: &HyUuid, cid: &HyUuid, force: bool) -> SResult<()> {
+        if self.limiter.cancel(id, cid) {
+            self.command.cancel(id, cid);
+            return Ok(());
+        }
+        if let Some(x) = self.agent.get(id) {
+            if let Some(x) = &x.message {
+                return x
+                    .send(Data::CommandKill(CommandKillMessage {
+                        id: cid.to_string(),
+                        force,
+                    }))
+                    .map_err(Into::into);
+            }
+        }
+        Err(SError::new("Agent not exist or offline"))
+    }
+
     /// Login agent `uid` with `ip`. Returns `None` when already login, otherwise agent id.
     pub async fn login(
         &self,
@@ -179,6 +616,44 @@ impl Plugin {
         }
     }
 
+    /// Link passive agent `apid` to agent `aid`.
+    ///
+    /// Warn when the agent behind passive address changes.
+    pub async fn link_passive(
+        &self,
+        db: &DatabaseTransaction,
+        apid: &HyUuid,
+        aid: &HyUuid,
+    ) -> Result<()> {
+        if let Some(m) = PassiveAgentViewer::find_by_id(db, apid).await?
+            && m.aid != Some(*aid)
+        {
+            if let Some(old) = m.aid {
+                let uid = |id: &HyUuid| self.agent.get(id).map(|x| x.uid.clone());
+                let (old_uid, new_uid) =
+                    (uid(&old).unwrap_or_default(), uid(aid).unwrap_or_default());
+                warn!(
+                    plugin = %ID,
+                    apid = %apid,
+                    address = m.address,
+                    old_aid = %old,
+                    old_uid,
+                    new_aid = %aid,
+                    new_uid,
+                    "Passive agent uid changed",
+                );
+                // Do not block the handshake transaction.
+                let body = format!(
+                    "Passive agent `{}` ({}) uid changed from `{old_uid}` to `{new_uid}`",
+                    m.name, m.address
+                );
+                spawn(async move { PLUGIN_INSTANCE.server.alert(body, "config").await });
+            }
+            PassiveAgentViewer::link(db, apid, Some(*aid)).await?;
+        }
+        Ok(())
+    }
+
     /// Logout agent `id`. Will be invoked automatically when connection losts.
     pub fn logout(&self, id: &HyUuid) {
         if let Some(mut item) = self.agent.get_mut(id) {
@@ -199,6 +674,11 @@ impl Plugin {
             item.band_down = None;
             item.message = None;
         }
+        self.dispatch_ready(self.limiter.clear(id));
+        self.command.disconnect(id);
+        self.file.disconnect(id);
+        self.migrate_rpc.disconnect(id);
+        self.stream.remove_agent(id);
     }
 
     /// Update agent `id` status.
@@ -252,30 +732,109 @@ impl Plugin {
         if let Some(mut item) = self.agent.get_mut(id) {
             item.message = Some(tx);
         }
+        Self::replay_queue(*id);
         rx
     }
 
-    /// Update agent `id` command `cid` code and output.
+    /// Update agent `id` command `cid` code, output and flags.
     ///
     /// Return true when `id` and `cid` is valid.
     pub fn update_command_output(
         &self,
         id: &HyUuid,
         cid: &HyUuid,
-        code: Option<i32>,
-        mut output: Vec<u8>,
+        mut data: CommandRspMessage,
     ) -> bool {
-        if let Some(mut agent) = self.agent.get_mut(id) {
-            if let Some(command) = agent.command.get_mut(cid) {
-                if command.is_none() {
-                    *command = Some(AgentCommand::new());
+        let size = data.output.len();
+        self.command
+            .update_within(id, cid, size, COMMAND_OUTPUT_BUDGET, |x, allowed| {
+                x.code = data.code;
+                if allowed < size {
+                    data.output.truncate(allowed);
+                    x.truncated = true;
                 }
-                command.as_mut().unwrap().code = code;
-                command.as_mut().unwrap().output.append(&mut output);
-                return true;
+                x.output.append(&mut data.output);
+                x.timed_out |= data.timed_out;
+                x.truncated |= data.truncated;
+                data.code.is_some()
+            })
+    }
+
+    /// Send migrate message to agents `aid`, switching to `endpoint` with `pubkey`.
+    ///
+    /// Return migration id.
+    pub fn migrate_agents(
+        &self,
+        aid: &[HyUuid],
+        endpoint: &str,
+        pubkey: &[u8],
+        timeout: u32,
+    ) -> HyUuid {
+        let mut job = MigrateJob::new(endpoint, timeout);
+        let mut pending = Vec::new();
+        for i in aid {
+            let rid = HyUuid::new();
+            self.migrate_rpc.register(i, &rid, Some(job.deadline()));
+            let sent = self.agent.get(i).is_some_and(|x| {
+                x.message.as_ref().is_some_and(|x| {
+                    x.send(Data::Migrate(MigrateMessage {
+                        id: rid.to_string(),
+                        endpoint: endpoint.to_owned(),
+                        pubkey: pubkey.to_owned(),
+                        timeout,
+                    }))
+                    .is_ok()
+                })
+            });
+            if sent {
+                job.set(i, MigrateStatus::Pending, "");
+                pending.push((*i, rid));
+            } else {
+                self.migrate_rpc.cancel(i, &rid);
+                job.set(i, MigrateStatus::Offline, "Agent not exist or offline");
             }
         }
-        false
+        let id = job.id;
+        self.migrate.insert(id, job);
+        for (aid, rid) in pending {
+            spawn(async move {
+                let (status, message) = match PLUGIN_INSTANCE.migrate_rpc.wait(&rid, None).await {
+                    Ok(x) if x.success => (MigrateStatus::Success, x.message),
+                    Ok(x) => (MigrateStatus::RolledBack, x.message),
+                    Err(RpcError::Timeout) => (MigrateStatus::Timeout, String::new()),
+                    Err(e) => (MigrateStatus::Offline, e.to_string()),
+                };
+                if let Some(mut x) = PLUGIN_INSTANCE.migrate.get_mut(&id) {
+                    x.set(&aid, status, &message);
+                }
+            });
+        }
+        if self.migrate.len() > MAX_MIGRATE_JOB {
+            let oldest = self
+                .migrate
+                .iter()
+                .min_by_key(|x| x.created_at)
+                .map(|x| x.id);
+            if let Some(x) = oldest {
+                self.migrate.remove(&x);
+            }
+        }
+        id
+    }
+
+    /// Update agent `id` migration request `rid` result.
+    ///
+    /// Return true when `id` and `rid` is valid.
+    pub fn update_migrate_result(
+        &self,
+        id: &HyUuid,
+        rid: &HyUuid,
+        data: MigrateRspMessage,
+    ) -> bool {
+        self.migrate_rpc.update(id, rid, |x| {
+            *x = data;
+            true
+        })
     }
 
     /// Update agent `id` file `mid` code and message.
@@ -288,17 +847,11 @@ impl Plugin {
         code: u32,
         message: &str,
     ) -> bool {
-        if let Some(mut agent) = self.agent.get_mut(id) {
-            if let Some(file) = agent.file.get_mut(fid) {
-                if file.is_none() {
-                    *file = Some(AgentFile::new());
-                }
-                file.as_mut().unwrap().code = code;
-                file.as_mut().unwrap().message = message.to_owned();
-                return true;
-            }
-        }
-        false
+        self.file.update(id, fid, |x| {
+            x.code = code;
+            x.message = message.to_owned();
+            true
+        })
     }
 
     pub async fn get_setting_address<C>(db: &C) -> Result<Option<String>>
@@ -317,6 +870,7 @@ impl Plugin {
             .and_then(|d| d.try_into().ok().and_then(|d| SecretKey::parse(&d).ok())))
     }
 
+    #[allow(clippy::collapsible_if)]
     pub async fn get_setting_shell<C>(db: &C) -> Result<Option<Vec<String>>>
     where
         C: ConnectionTrait,
@@ -359,6 +913,116 @@ impl Plugin {
         }
     }
 
+    pub async fn get_setting_proxy_protocol<C>(db: &C) -> Result<Option<bool>>
+    where
+        C: ConnectionTrait,
+    {
+        let x = SettingViewer::get(db, &SETTING_PROXY_PROTOCOL).await?;
+        if let Some(x) = x {
+            Ok(Some(x.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn get_setting_proxy_trusted<C>(db: &C) -> Result<Option<Vec<String>>>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(x) = SettingViewer::get(db, &SETTING_PROXY_TRUSTED).await? {
+            return Ok(serde_json::from_str(&x).ok());
+        }
+        Ok(None)
+    }
+
+    pub async fn get_setting_firewall<C>(db: &C) -> Result<Option<FirewallSetting>>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(x) = SettingViewer::get(db, &SETTING_FIREWALL).await? {
+            return Ok(serde_json::from_str(&x).ok());
+        }
+        Ok(None)
+    }
+
+    pub async fn get_setting_job_retention<C>(db: &C) -> Result<Option<u32>>
+    where
+        C: ConnectionTrait,
+    {
+        let x = SettingViewer::get(db, &SETTING_JOB_RETENTION).await?;
+        if let Some(x) = x {
+            Ok(Some(x.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn get_setting_shell_record<C>(db: &C) -> Result<Option<bool>>
+    where
+        C: ConnectionTrait,
+    {
+        let x = SettingViewer::get(db, &SETTING_SHELL_RECORD).await?;
+        if let Some(x) = x {
+            Ok(Some(x.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn get_setting_shell_grace<C>(db: &C) -> Result<Option<u32>>
+    where
+        C: ConnectionTrait,
+    {
+        let x = SettingViewer::get(db, &SETTING_SHELL_GRACE).await?;
+        if let Some(x) = x {
+            Ok(Some(x.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn get_setting_shell_limit<C>(db: &C) -> Result<Option<ShellLimit>>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(x) = SettingViewer::get(db, &SETTING_SHELL_LIMIT).await? {
+            return Ok(serde_json::from_str(&x).ok());
+        }
+        Ok(None)
+    }
+
+    pub async fn get_setting_command_group_limit<C>(db: &C) -> Result<Option<HashMap<String, u32>>>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(x) = SettingViewer::get(db, &SETTING_COMMAND_GROUP_LIMIT).await? {
+            return Ok(serde_json::from_str(&x).ok());
+        }
+        Ok(None)
+    }
+
+    pub async fn get_setting_socks<C>(db: &C) -> Result<Option<SocksSetting>>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(x) = SettingViewer::get(db, &SETTING_SOCKS).await? {
+            return Ok(serde_json::from_str(&x).ok());
+        }
+        Ok(None)
+    }
+
+    pub async fn get_setting_acl<C>(db: &C) -> Result<Option<bool>>
+    where
+        C: ConnectionTrait,
+    {
+        let x = SettingViewer::get(db, &SETTING_ACL).await?;
+        if let Some(x) = x {
+            Ok(Some(x.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub async fn set_setting_address(db: &DatabaseTransaction, address: &str) -> Result<()> {
         SettingViewer::set(db, &SETTING_ADDRESS, address).await
     }
@@ -379,6 +1043,68 @@ impl Plugin {
         SettingViewer::set(db, &SETTING_ALERT_TIMEOUT, &timeout.to_string()).await
     }
 
+    pub async fn set_setting_proxy_protocol(db: &DatabaseTransaction, enable: bool) -> Result<()> {
+        SettingViewer::set(db, &SETTING_PROXY_PROTOCOL, &enable.to_string()).await
+    }
+
+    pub async fn set_setting_proxy_trusted(
+        db: &DatabaseTransaction,
+        cidr: &[String],
+    ) -> Result<()> {
+        SettingViewer::set(db, &SETTING_PROXY_TRUSTED, &serde_json::to_string(&cidr)?).await
+    }
+
+    pub async fn set_setting_job_retention(db: &DatabaseTransaction, days: u32) -> Result<()> {
+        SettingViewer::set(db, &SETTING_JOB_RETENTION, &days.to_string()).await
+    }
+
+    pub async fn set_setting_shell_record(db: &DatabaseTransaction, enable: bool) -> Result<()> {
+        SettingViewer::set(db, &SETTING_SHELL_RECORD, &enable.to_string()).await
+    }
+
+    pub async fn set_setting_shell_grace(db: &DatabaseTransaction, secs: u32) -> Result<()> {
+        SettingViewer::set(db, &SETTING_SHELL_GRACE, &secs.to_string()).await
+    }
+
+    pub async fn set_setting_shell_limit(
+        db: &DatabaseTransaction,
+        setting: &ShellLimit,
+    ) -> Result<()> {
+        SettingViewer::set(db, &SETTING_SHELL_LIMIT, &serde_json::to_string(setting)?).await
+    }
+
+    pub async fn set_setting_command_group_limit(
+        db: &DatabaseTransaction,
+        limit: &HashMap<String, u32>,
+    ) -> Result<()> {
+        SettingViewer::set(
+            db,
+            &SETTING_COMMAND_GROUP_LIMIT,
+            &serde_json::to_string(limit)?,
+        )
+        .await
+    }
+
+    /// Set max concurrent commands of all agents having each label, 0 for unlimited.
+    pub fn set_command_group_limit(&self, limit: HashMap<String, u32>) {
+        self.dispatch_ready(self.limiter.set_group_limit(limit));
+    }
+
+    pub async fn set_setting_socks(db: &DatabaseTransaction, setting: &SocksSetting) -> Result<()> {
+        SettingViewer::set(db, &SETTING_SOCKS, &serde_json::to_string(setting)?).await
+    }
+
+    pub async fn set_setting_acl(db: &DatabaseTransaction, enable: bool) -> Result<()> {
+        SettingViewer::set(db, &SETTING_ACL, &enable.to_string()).await
+    }
+
+    pub async fn set_setting_firewall(
+        db: &DatabaseTransaction,
+        setting: &FirewallSetting,
+    ) -> Result<()> {
+        SettingViewer::set(db, &SETTING_FIREWALL, &serde_json::to_string(setting)?).await
+    }
+
     pub async fn init_agent(&self, db: &DatabaseTransaction) -> Result<()> {
         agents::Entity::find()
             .all(db)
@@ -388,10 +1114,123 @@ impl Plugin {
             .for_each(|x: Agent| {
                 self.agent.insert(x.id, x);
             });
+        for i in AgentSettingViewer::find_by_name(db, AGENT_SETTING_LABELS).await? {
+            if let Some(mut x) = self.agent.get_mut(&i.aid) {
+                x.labels = serde_json::from_str(&i.value).unwrap_or_default();
+                self.limiter.set_label(&i.aid, &x.labels);
+            }
+        }
+        for i in AgentSettingViewer::find_by_name(db, AGENT_SETTING_COMMAND_LIMIT).await? {
+            self.limiter
+                .set_limit(&i.aid, i.value.parse().unwrap_or_default());
+        }
+        for i in AgentSettingViewer::find_by_name(db, AGENT_SETTING_SHELL_RECORD).await? {
+            let mode = serde_json::from_str(&i.value).unwrap_or_default();
+            self.recording.set_mode(&i.aid, mode);
+        }
+        for i in AgentSettingViewer::find_by_name(db, AGENT_SETTING_SHELL).await? {
+            let shell: Vec<String> = serde_json::from_str(&i.value).unwrap_or_default();
+            if !shell.is_empty() {
+                self.agent_shell.insert(i.aid, shell);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set agent `id` labels.
+    pub async fn set_agent_labels(
+        &self,
+        db: &DatabaseTransaction,
+        id: &HyUuid,
+        labels: &[String],
+    ) -> Result<()> {
+        AgentSettingViewer::set(
+            db,
+            id,
+            AGENT_SETTING_LABELS,
+            &serde_json::to_string(labels)?,
+        )
+        .await?;
+        if let Some(mut x) = self.agent.get_mut(id) {
+            x.labels = labels.to_vec();
+        }
+        self.dispatch_ready(self.limiter.set_label(id, labels));
         Ok(())
     }
 
+    /// Set agent `id` max concurrent commands, 0 for unlimited.
+    pub async fn set_agent_command_limit(
+        &self,
+        db: &DatabaseTransaction,
+        id: &HyUuid,
+        limit: u32,
+    ) -> Result<()> {
+        AgentSettingViewer::set(db, id, AGENT_SETTING_COMMAND_LIMIT, &limit.to_string()).await?;
+        self.dispatch_ready(self.limiter.set_limit(id, limit));
+        Ok(())
+    }
+
+    /// Set agent `id` shell recording `mode`.
+    pub async fn set_agent_record_mode(
+        &self,
+        db: &DatabaseTransaction,
+        id: &HyUuid,
+        mode: RecordMode,
+    ) -> Result<()> {
+        AgentSettingViewer::set(
+            db,
+            id,
+            AGENT_SETTING_SHELL_RECORD,
+            &serde_json::to_string(&mode)?,
+        )
+        .await?;
+        self.recording.set_mode(id, mode);
+        Ok(())
+    }
+
+    /// Set agent `id` allowed shell programs, empty to use the global list.
+    pub async fn set_agent_shell(
+        &self,
+        db: &DatabaseTransaction,
+        id: &HyUuid,
+        shell: &[String],
+    ) -> Result<()> {
+        AgentSettingViewer::set(db, id, AGENT_SETTING_SHELL, &serde_json::to_string(shell)?)
+            .await?;
+        if shell.is_empty() {
+            self.agent_shell.remove(id);
+        } else {
+            self.agent_shell.insert(*id, shell.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Get allowed shell programs of agent `id`.
+    pub fn agent_shell(&self, id: &HyUuid) -> Vec<String> {
+        self.agent_shell
+            .get(id)
+            .map(|x| x.clone())
+            .unwrap_or_else(|| self.shell_prog.read().clone())
+    }
+
+    /// Get labels of agent `id`.
+    pub fn agent_labels(&self, id: &HyUuid) -> Vec<String> {
+        self.agent
+            .get(id)
+            .map(|x| x.labels.clone())
+            .unwrap_or_default()
+    }
+
     pub fn remove_agent(&self, id: &HyUuid) -> bool {
+        self.dispatch_ready(self.limiter.remove_agent(id));
+        self.agent_shell.remove(id);
+        self.tunnel.remove_agent(id);
+        self.socks.remove_agent(id);
+        self.stream.remove_agent(id);
+        self.recording.remove_agent(id);
+        self.command.remove_agent(id);
+        self.file.remove_agent(id);
+        self.migrate_rpc.remove_agent(id);
         if let Some(x) = self.agent.remove(id) {
             if let Some(x) = &x.1.message {
                 let _ = x.send(Data::Quit(QuitMessage {}));
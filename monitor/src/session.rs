@@ -0,0 +1,321 @@
+use std::{collections::VecDeque, time::Duration};
+
+use actix_cloud::{
+    tokio::{spawn, task::JoinHandle, time::Instant},
+    tracing::error,
+};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use skynet_api::{HyUuid, Result, bail};
+use skynet_api_monitor::{ID, entity::shell_sessions, viewer::shell_sessions::ShellSessionViewer};
+use validator::Validate;
+
+use crate::PLUGIN_INSTANCE;
+
+/// Max output kept for reattaching, unit bytes.
+const MAX_SCROLLBACK: usize = 64 * 1024;
+
+#[derive(Default, Serialize, Deserialize, Validate, Clone, Debug)]
+#[serde(default)]
+pub struct ShellLimit {
+    /// Close shells without input for this time, unit minutes, 0 for unlimited.
+    #[validate(range(max = 10080))]
+    pub idle: u32,
+    /// Max shell session length, unit minutes, 0 for unlimited.
+    #[validate(range(max = 10080))]
+    pub duration: u32,
+    /// Max concurrent shells per user, 0 for unlimited.
+    pub user: u32,
+    /// Max concurrent shells per agent, 0 for unlimited.
+    pub agent: u32,
+}
+
+/// Live shell session.
+pub struct Session {
+    pub id: HyUuid,
+    pub aid: HyUuid,
+    pub uid: Option<HyUuid>,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    scrollback: VecDeque<u8>,
+    started_at: Instant,
+    input_at: Instant,
+    detached_at: Option<Instant>,
+    created: JoinHandle<()>,
+}
+
+/// Live shell sessions keyed by shell token, persisted for auditing.
+#[derive(Default)]
+pub struct Sessions {
+    limit: RwLock<ShellLimit>,
+    active: DashMap<HyUuid, Session>,
+}
+
+impl Sessions {
+    pub fn limit(&self) -> ShellLimit {
+        self.limit.read().clone()
+    }
+
+    pub fn set_limit(&self, limit: ShellLimit) {
+        *self.limit.write() = limit;
+    }
+
+    /// Check concurrent shell caps before user `uid` opens shell `token` on agent `aid`.
+    ///
+    /// Existing shell `token` is not counted, it is replaced by the new one.
+    ///
+    /// # Errors
+    /// Will return `Err` when a cap is reached.
+    pub fn check_cap(&self, token: &HyUuid, aid: &HyUuid, uid: Option<HyUuid>) -> Result<()> {
+        let limit = self.limit();
+        let count = |f: &dyn Fn(&Session) -> bool| {
+            self.active
+                .iter()
+                .filter(|x| x.key() != token && f(x.value()))
+                .count()
+        };
+        if limit.agent != 0 && count(&|x| x.aid == *aid) >= limit.agent as usize {
+            bail!("Too many shells on agent, limit is {}", limit.agent);
+        }
+        if limit.user != 0 && uid.is_some() && count(&|x| x.uid == uid) >= limit.user as usize {
+            bail!("Too many shells of user, limit is {}", limit.user);
+        }
+        Ok(())
+    }
+
+    /// Get shells exceeding idle timeout or max duration as (token, reason).
+    pub fn timeout(&self) -> Vec<(HyUuid, String)> {
+        let limit = self.limit();
+        let idle = Duration::from_secs(u64::from(limit.idle) * 60);
+        let duration = Duration::from_secs(u64::from(limit.duration) * 60);
+        self.active
+            .iter()
+            .filter_map(|x| {
+                if limit.duration != 0 && x.started_at.elapsed() >= duration {
+                    Some((
+                        *x.key(),
+                        format!("Shell exceeded max duration of {} minutes", limit.duration),
+                    ))
+                } else if limit.idle != 0 && x.input_at.elapsed() >= idle {
+                    Some((
+                        *x.key(),
+                        format!("Shell idle for more than {} minutes", limit.idle),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Start session of shell `token` running `cmd` on agent `aid`, opened by user `uid` from `ip`.
+    ///
+    /// Return session id.
+    pub fn start(
+        &self,
+        token: &HyUuid,
+        aid: &HyUuid,
+        uid: Option<HyUuid>,
+        cmd: &str,
+        ip: &str,
+    ) -> HyUuid {
+        let id = HyUuid::new();
+        let model = shell_sessions::Model {
+            id,
+            aid: *aid,
+            uid,
+            token: *token,
+            cmd: cmd.to_owned(),
+            ip: ip.to_owned(),
+            ..Default::default()
+        };
+        let created = spawn(async move {
+            if let Err(e) =
+                ShellSessionViewer::create(PLUGIN_INSTANCE.db.get().unwrap(), model).await
+            {
+                error!(plugin = %ID, sid = %id, error = %e, "Failed to save shell session");
+            }
+        });
+        let session = Session {
+            id,
+            aid: *aid,
+            uid,
+            scrollback: VecDeque::new(),
+            started_at: Instant::now(),
+            input_at: Instant::now(),
+            detached_at: None,
+            bytes_in: 0,
+            bytes_out: 0,
+            created,
+        };
+        if let Some(x) = self.active.insert(*token, session) {
+            Self::finish(x);
+        }
+        id
+    }
+
+    pub fn input(&self, token: &HyUuid, len: usize) {
+        if let Some(mut x) = self.active.get_mut(token) {
+            x.bytes_in += len as u64;
+            x.input_at = Instant::now();
+        }
+    }
+
+    pub fn output(&self, token: &HyUuid, data: &[u8]) {
+        if let Some(mut x) = self.active.get_mut(token) {
+            x.bytes_out += data.len() as u64;
+            x.scrollback.extend(data);
+            let len = x.scrollback.len();
+            if len > MAX_SCROLLBACK {
+                x.scrollback.drain(..len - MAX_SCROLLBACK);
+            }
+        }
+    }
+
+    /// Detach shell `token` from its frontend, the shell is kept until attached or expired.
+    pub fn detach(&self, token: &HyUuid) -> bool {
+        self.active.get_mut(token).is_some_and(|mut x| {
+            x.detached_at = Some(Instant::now());
+            true
+        })
+    }
+
+    /// Get agent id and owner of shell `token`.
+    pub fn owner(&self, token: &HyUuid) -> Option<(HyUuid, Option<HyUuid>)> {
+        self.active.get(token).map(|x| (x.aid, x.uid))
+    }
+
+    /// Get scrollback of shell `token`.
+    pub fn scrollback(&self, token: &HyUuid) -> Option<Vec<u8>> {
+        self.active
+            .get(token)
+            .map(|x| x.scrollback.iter().copied().collect())
+    }
+
+    /// Get agent id and owner of detached shell `token`.
+    pub fn detached(&self, token: &HyUuid) -> Option<(HyUuid, Option<HyUuid>)> {
+        self.active
+            .get(token)
+            .filter(|x| x.detached_at.is_some())
+            .map(|x| (x.aid, x.uid))
+    }
+
+    /// Attach detached shell `token`, return scrollback.
+    pub fn attach(&self, token: &HyUuid) -> Option<Vec<u8>> {
+        let mut x = self.active.get_mut(token)?;
+        x.detached_at.take()?;
+        Some(x.scrollback.iter().copied().collect())
+    }
+
+    /// Get shells detached longer than `grace` as (token, agent id).
+    pub fn expired(&self, grace: Duration) -> Vec<(HyUuid, HyUuid)> {
+        self.active
+            .iter()
+            .filter(|x| x.detached_at.is_some_and(|x| x.elapsed() >= grace))
+            .map(|x| (*x.key(), x.aid))
+            .collect()
+    }
+
+    /// Stop session of shell `token`.
+    pub fn stop(&self, token: &HyUuid) {
+        if let Some((_, x)) = self.active.remove(token) {
+            Self::finish(x);
+        }
+    }
+
+    /// Stop all sessions.
+    pub fn clear(&self) {
+        let token: Vec<HyUuid> = self.active.iter().map(|x| *x.key()).collect();
+        for i in token {
+            self.stop(&i);
+        }
+    }
+
+    /// Find shell token of live session `id`.
+    pub fn token(&self, id: &HyUuid) -> Option<HyUuid> {
+        self.active.iter().find(|x| x.id == *id).map(|x| *x.key())
+    }
+
+    /// Get transferred bytes of live session `id`.
+    pub fn bytes(&self, id: &HyUuid) -> Option<(u64, u64)> {
+        self.active
+            .iter()
+            .find(|x| x.id == *id)
+            .map(|x| (x.bytes_in, x.bytes_out))
+    }
+
+    fn finish(x: Session) {
+        spawn(async move {
+            let _ = x.created.await;
+            if let Err(e) = ShellSessionViewer::finish(
+                PLUGIN_INSTANCE.db.get().unwrap(),
+                &x.id,
+                x.bytes_in.try_into().unwrap_or(i64::MAX),
+                x.bytes_out.try_into().unwrap_or(i64::MAX),
+            )
+            .await
+            {
+                error!(plugin = %ID, sid = %x.id, error = %e, "Failed to save shell session");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::runtime::Builder;
+
+    use super::*;
+
+    fn insert(sessions: &Sessions, aid: HyUuid, uid: Option<HyUuid>) -> HyUuid {
+        let session = Session {
+            id: HyUuid::new(),
+            aid,
+            uid,
+            bytes_in: 0,
+            bytes_out: 0,
+            scrollback: VecDeque::new(),
+            started_at: Instant::now(),
+            input_at: Instant::now(),
+            detached_at: None,
+            created: spawn(async {}),
+        };
+        let token = HyUuid::new();
+        sessions.active.insert(token, session);
+        token
+    }
+
+    #[test]
+    fn check_cap() {
+        let rt = Builder::new_current_thread().enable_time().build().unwrap();
+        let _guard = rt.enter();
+        let sessions = Sessions::default();
+        let (a, b) = (HyUuid::new(), HyUuid::new());
+        let (u, v) = (HyUuid::new(), HyUuid::new());
+        let t = HyUuid::new();
+        let owned = insert(&sessions, a, Some(u));
+        insert(&sessions, a, None);
+        // No limit by default.
+        assert!(sessions.check_cap(&t, &a, Some(u)).is_ok());
+
+        sessions.set_limit(ShellLimit {
+            agent: 2,
+            ..Default::default()
+        });
+        assert!(sessions.check_cap(&t, &a, Some(v)).is_err());
+        assert!(sessions.check_cap(&t, &b, Some(u)).is_ok());
+        // Replaced shell is not counted.
+        assert!(sessions.check_cap(&owned, &a, Some(u)).is_ok());
+
+        sessions.set_limit(ShellLimit {
+            user: 1,
+            ..Default::default()
+        });
+        assert!(sessions.check_cap(&t, &b, Some(u)).is_err());
+        assert!(sessions.check_cap(&t, &b, Some(v)).is_ok());
+        assert!(sessions.check_cap(&owned, &b, Some(u)).is_ok());
+        // Anonymous shells are not capped per user.
+        assert!(sessions.check_cap(&t, &b, None).is_ok());
+    }
+}
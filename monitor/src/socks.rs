@@ -0,0 +1,587 @@
+use std::{
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use actix_cloud::{
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        select, spawn,
+        sync::watch,
+        task::JoinHandle,
+        time::{sleep, timeout},
+    },
+    tracing::{debug, info},
+};
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use dashmap::DashMap;
+use derivative::Derivative;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use skynet_api::{
+    HyUuid, Result, bail,
+    permission::{PERM_WRITE, PermEntry},
+};
+use skynet_api_monitor::{ID, entity::socks_users};
+use subtle::ConstantTimeEq;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    PLUGIN_INSTANCE,
+    acl::{Acl, AclRight},
+    firewall::{Firewall, FirewallSetting},
+    stream::Traffic,
+};
+
+const SOCKS_VERSION: u8 = 5;
+const AUTH_VERSION: u8 = 1;
+const METHOD_PASSWORD: u8 = 2;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const REPLY_SUCCEEDED: u8 = 0;
+const REPLY_HOST_UNREACHABLE: u8 = 4;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 7;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 8;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const SALT_LEN: usize = 16;
+/// Separator of proxy username and agent in SOCKS username.
+const AGENT_SEPARATOR: char = ':';
+
+#[derive(Derivative, Serialize, Deserialize, Validate, Clone, Debug)]
+#[derivative(Default(new = "true"))]
+#[serde(default)]
+pub struct SocksSetting {
+    pub enable: bool,
+    /// Listen address, authenticated by username `username:agent` and the user password,
+    /// `agent` is the agent id or name.
+    #[derivative(Default(value = "SocketAddr::from((Ipv4Addr::LOCALHOST, 1080))"))]
+    pub address: SocketAddr,
+    /// Max failed authentications per IP in `auth_window`, 0 for unlimited.
+    #[derivative(Default(value = "5"))]
+    pub auth_limit: u32,
+    /// Failed authentication window, unit seconds.
+    #[derivative(Default(value = "60"))]
+    #[validate(range(min = 1))]
+    pub auth_window: u32,
+    /// Ban time when exceeding `auth_limit`, unit seconds.
+    #[derivative(Default(value = "600"))]
+    pub ban_time: u32,
+}
+
+/// # Errors
+/// Will return `Err` when `x` contains the agent separator.
+pub fn username_validator(x: &str) -> Result<(), ValidationError> {
+    if x.contains(AGENT_SEPARATOR) {
+        Err(ValidationError::new("invalid username"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Generated proxy password, only the hash and salt are stored.
+pub struct Password {
+    pub password: String,
+    /// Base64 encoded salted hash.
+    pub hash: String,
+    /// Base64 encoded salt.
+    pub salt: String,
+}
+
+impl Password {
+    pub fn generate() -> Self {
+        let mut buf = [0; 32];
+        OsRng.fill_bytes(&mut buf);
+        let password = URL_SAFE_NO_PAD.encode(buf);
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            hash: STANDARD.encode(Self::hash(&salt, &password)),
+            salt: STANDARD.encode(salt),
+            password,
+        }
+    }
+
+    fn hash(salt: &[u8], password: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(password);
+        hasher.finalize().into()
+    }
+
+    /// Whether `password` matches `user`, compared in constant time.
+    fn verify(user: &socks_users::Model, password: &str) -> bool {
+        let (Ok(salt), Ok(hash)) = (STANDARD.decode(&user.salt), STANDARD.decode(&user.password))
+        else {
+            return false;
+        };
+        Self::hash(&salt, password)[..].ct_eq(&hash).into()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SocksTraffic {
+    pub aid: HyUuid,
+    pub connections: usize,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+}
+
+#[derive(Default)]
+struct Usage {
+    connections: AtomicUsize,
+    traffic: Traffic,
+}
+
+struct Listener {
+    task: JoinHandle<()>,
+    // Connections are closed when dropped.
+    _close: watch::Sender<()>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// SOCKS5 proxy with egress through agents.
+#[derive(Default)]
+pub struct Socks {
+    user: RwLock<Vec<socks_users::Model>>,
+    usage: DashMap<HyUuid, Arc<Usage>>,
+    listener: Mutex<Option<Listener>>,
+    firewall: Firewall,
+}
+
+impl Socks {
+    /// Replace cached users with `user`.
+    pub fn load(&self, user: Vec<socks_users::Model>) {
+        *self.user.write() = user;
+    }
+
+    /// Apply `setting`, the listener is restarted and existing connections are closed.
+    ///
+    /// # Errors
+    /// Will return `Err` when failed to listen.
+    pub async fn start(&self, setting: &SocksSetting) -> Result<()> {
+        self.stop().await;
+        self.firewall.set(FirewallSetting {
+            handshake_limit: setting.auth_limit,
+            handshake_window: setting.auth_window,
+            ban_time: setting.ban_time,
+            ..FirewallSetting::new()
+        });
+        if !setting.enable {
+            return Ok(());
+        }
+        let listener = TcpListener::bind(setting.address).await?;
+        let (close, _) = watch::channel(());
+        let task = spawn(Self::accept(listener, close.subscribe()));
+        *self.listener.lock() = Some(Listener {
+            task,
+            _close: close,
+        });
+        info!(plugin = %ID, "Monitor socks listening on {}", setting.address);
+        Ok(())
+    }
+
+    /// Stop the listener and close existing connections, the address is released when returned.
+    pub async fn stop(&self) {
+        let listener = self.listener.lock().take();
+        if let Some(mut x) = listener {
+            x.task.abort();
+            let _ = (&mut x.task).await;
+        }
+    }
+
+    /// Get traffic of all agents.
+    pub fn traffic(&self) -> Vec<SocksTraffic> {
+        self.usage
+            .iter()
+            .map(|x| {
+                let (bytes_up, bytes_down) = x.traffic.get();
+                SocksTraffic {
+                    aid: *x.key(),
+                    connections: x.connections.load(Ordering::Relaxed),
+                    bytes_up,
+                    bytes_down,
+                }
+            })
+            .collect()
+    }
+
+    pub fn remove_agent(&self, aid: &HyUuid) {
+        self.usage.remove(aid);
+    }
+
+    /// Remove idle authentication failure states.
+    pub fn purge(&self) {
+        self.firewall.purge();
+    }
+
+    async fn accept(listener: TcpListener, close: watch::Receiver<()>) {
+        loop {
+            let (conn, addr) = match listener.accept().await {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!(error = %e, "Failed to accept monitor socks connection");
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            if let Err(e) = PLUGIN_INSTANCE.socks.firewall.check(addr.ip()) {
+                debug!(%addr, reason = %e, "Monitor socks connection rejected");
+                continue;
+            }
+            let mut close = close.clone();
+            spawn(async move {
+                select! {
+                    () = PLUGIN_INSTANCE.socks.handle(conn, addr) => {},
+                    _ = close.changed() => {},
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, mut conn: TcpStream, addr: SocketAddr) {
+        let (uid, aid, host, port) =
+            match timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut conn, addr.ip())).await {
+                Ok(Ok(x)) => x,
+                Ok(Err(e)) => {
+                    debug!(%addr, error = %e, "Monitor socks handshake failed");
+                    return;
+                }
+                Err(_) => {
+                    debug!(%addr, "Monitor socks handshake timed out");
+                    return;
+                }
+            };
+        let usage = self.usage.entry(aid).or_default().clone();
+        usage.connections.fetch_add(1, Ordering::Relaxed);
+        let ret = async {
+            let stream = match PLUGIN_INSTANCE.stream.open(&aid, &host, port).await {
+                Ok(x) => x,
+                Err(e) => {
+                    let _ = Self::reply(&mut conn, REPLY_HOST_UNREACHABLE).await;
+                    return Err(e);
+                }
+            };
+            Self::reply(&mut conn, REPLY_SUCCEEDED).await?;
+            stream.pipe(conn, &usage.traffic).await
+        }
+        .await;
+        usage.connections.fetch_sub(1, Ordering::Relaxed);
+        info!(
+            success = ret.is_ok(),
+            uid = %uid,
+            aid = %aid,
+            host,
+            port,
+            %addr,
+            error = ret.err().map(|e| e.to_string()),
+            "Monitor socks connection closed",
+        );
+    }
+
+    /// Find enabled user id for `username` and `password`.
+    fn login(&self, username: &str, password: &str) -> Option<HyUuid> {
+        let user = self.user.read();
+        let Some(x) = user.iter().find(|x| x.username == username) else {
+            // Unknown users take the same time as a wrong password.
+            black_box(Password::hash(&[0; SALT_LEN], password));
+            return None;
+        };
+        (Password::verify(x, password) && x.enable).then_some(x.uid)
+    }
+
+    /// Find agent id for `agent` id or name, return `None` when user `uid` has no access.
+    async fn authorize(uid: &HyUuid, agent: &str) -> Result<Option<HyUuid>> {
+        let Some(aid) = HyUuid::parse(agent)
+            .ok()
+            .filter(|x| PLUGIN_INSTANCE.agent.contains_key(x))
+            .or_else(|| {
+                PLUGIN_INSTANCE
+                    .agent
+                    .iter()
+                    .find(|x| x.name == agent)
+                    .map(|x| x.id)
+            })
+        else {
+            return Ok(None);
+        };
+        let perm = Acl::user_perm(PLUGIN_INSTANCE.db.get().unwrap(), uid).await?;
+        let exec = PermEntry {
+            pid: *PLUGIN_INSTANCE.exec_id.get().unwrap(),
+            perm: PERM_WRITE,
+        }
+        .check(&perm);
+        Ok((exec
+            && PLUGIN_INSTANCE
+                .acl
+                .check(Some(*uid), &perm, &aid, AclRight::Forward, || {
+                    PLUGIN_INSTANCE.agent_labels(&aid)
+                }))
+        .then_some(aid))
+    }
+
+    async fn read_string(conn: &mut TcpStream) -> Result<String> {
+        let mut buf = vec![0; conn.read_u8().await?.into()];
+        conn.read_exact(&mut buf).await?;
+        String::from_utf8(buf).map_err(Into::into)
+    }
+
+    async fn reply(conn: &mut TcpStream, code: u8) -> Result<()> {
+        conn.write_all(&[SOCKS_VERSION, code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Negotiate with client from `ip`, return (user id, agent id, host, port).
+    async fn handshake(
+        &self,
+        conn: &mut TcpStream,
+        ip: IpAddr,
+    ) -> Result<(HyUuid, HyUuid, String, u16)> {
+        let (uid, aid) = self.auth(conn, ip).await?;
+        let (host, port) = Self::request(conn).await?;
+        Ok((uid, aid, host, port))
+    }
+
+    /// Authenticate client from `ip`, return (user id, agent id).
+    ///
+    /// Failed logins are counted by the firewall.
+    async fn auth(&self, conn: &mut TcpStream, ip: IpAddr) -> Result<(HyUuid, HyUuid)> {
+        if conn.read_u8().await? != SOCKS_VERSION {
+            bail!("Invalid socks version");
+        }
+        let mut methods = vec![0; conn.read_u8().await?.into()];
+        conn.read_exact(&mut methods).await?;
+        if !methods.contains(&METHOD_PASSWORD) {
+            conn.write_all(&[SOCKS_VERSION, METHOD_NONE_ACCEPTABLE])
+                .await?;
+            bail!("Password authentication is required");
+        }
+        conn.write_all(&[SOCKS_VERSION, METHOD_PASSWORD]).await?;
+
+        // Username/password authentication, RFC 1929.
+        if conn.read_u8().await? != AUTH_VERSION {
+            bail!("Invalid auth version");
+        }
+        let username = Self::read_string(conn).await?;
+        let password = Self::read_string(conn).await?;
+        let (name, agent) = username
+            .split_once(AGENT_SEPARATOR)
+            .unwrap_or((&username, ""));
+        let Some(uid) = self.login(name, &password) else {
+            self.firewall.handshake_failed(ip);
+            conn.write_all(&[AUTH_VERSION, 1]).await?;
+            bail!("Authentication failed for `{name}`");
+        };
+        let Some(aid) = Self::authorize(&uid, agent).await? else {
+            conn.write_all(&[AUTH_VERSION, 1]).await?;
+            bail!("User `{name}` has no access to agent `{agent}`");
+        };
+        conn.write_all(&[AUTH_VERSION, 0]).await?;
+        Ok((uid, aid))
+    }
+
+    /// Read connect request, return (host, port).
+    async fn request(conn: &mut TcpStream) -> Result<(String, u16)> {
+        let mut head = [0; 4];
+        conn.read_exact(&mut head).await?;
+        if head[0] != SOCKS_VERSION {
+            bail!("Invalid socks version");
+        }
+        if head[1] != CMD_CONNECT {
+            Self::reply(conn, REPLY_COMMAND_NOT_SUPPORTED).await?;
+            bail!("Unsupported command {}", head[1]);
+        }
+        let host = match head[3] {
+            ATYP_IPV4 => {
+                let mut buf = [0; 4];
+                conn.read_exact(&mut buf).await?;
+                Ipv4Addr::from(buf).to_string()
+            }
+            ATYP_DOMAIN => Self::read_string(conn).await?,
+            ATYP_IPV6 => {
+                let mut buf = [0; 16];
+                conn.read_exact(&mut buf).await?;
+                Ipv6Addr::from(buf).to_string()
+            }
+            x => {
+                Self::reply(conn, REPLY_ADDRESS_NOT_SUPPORTED).await?;
+                bail!("Unsupported address type {x}");
+            }
+        };
+        let port = conn.read_u16().await?;
+        Ok((host, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::{join, runtime::Builder};
+
+    use super::*;
+
+    /// Run `server` on one end of a loopback connection and `client` on the other.
+    fn connect<S, C, T>(server: S, client: C) -> T
+    where
+        S: AsyncFnOnce(TcpStream) -> T,
+        C: AsyncFnOnce(TcpStream),
+    {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let conn = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let accepted = listener.accept().await.unwrap().0;
+            join!(server(accepted), client(conn)).0
+        })
+    }
+
+    fn auth_msg(username: &str, password: &str) -> Vec<u8> {
+        let mut ret = vec![SOCKS_VERSION, 1, METHOD_PASSWORD, AUTH_VERSION];
+        ret.push(username.len().try_into().unwrap());
+        ret.extend_from_slice(username.as_bytes());
+        ret.push(password.len().try_into().unwrap());
+        ret.extend_from_slice(password.as_bytes());
+        ret
+    }
+
+    fn user(username: &str, password: &Password, enable: bool) -> socks_users::Model {
+        socks_users::Model {
+            uid: HyUuid::new(),
+            username: username.to_owned(),
+            password: password.hash.clone(),
+            salt: password.salt.clone(),
+            enable,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn login() {
+        let (a, b) = (Password::generate(), Password::generate());
+        assert_ne!(a.password, b.password);
+        assert_ne!(a.salt, b.salt);
+        let socks = Socks::default();
+        let user = vec![user("alice", &a, true), user("bob", &b, false)];
+        let uid = user[0].uid;
+        socks.load(user);
+        assert_eq!(socks.login("alice", &a.password), Some(uid));
+        assert_eq!(socks.login("alice", &b.password), None);
+        assert_eq!(socks.login("bob", &b.password), None);
+        assert_eq!(socks.login("carol", &a.password), None);
+        assert_eq!(socks.login("alice", &a.hash), None);
+    }
+
+    #[test]
+    fn auth_failed() {
+        let ip = IpAddr::from(Ipv4Addr::LOCALHOST);
+        let password = Password::generate();
+        let socks = Socks::default();
+        socks.firewall.set(FirewallSetting {
+            handshake_limit: 1,
+            ..FirewallSetting::new()
+        });
+        socks.load(vec![user("alice", &password, true)]);
+
+        // No password method offered.
+        let ret = connect(
+            async |mut x| socks.auth(&mut x, ip).await,
+            async |mut x| {
+                x.write_all(&[SOCKS_VERSION, 1, 0]).await.unwrap();
+                let mut buf = [0; 2];
+                x.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [SOCKS_VERSION, METHOD_NONE_ACCEPTABLE]);
+            },
+        );
+        assert!(ret.is_err());
+        assert!(socks.firewall.check(ip).is_ok());
+
+        for _ in 0..2 {
+            let ret = connect(
+                async |mut x| socks.auth(&mut x, ip).await,
+                async |mut x| {
+                    x.write_all(&auth_msg("alice:agent", "wrong"))
+                        .await
+                        .unwrap();
+                    let mut buf = [0; 4];
+                    x.read_exact(&mut buf).await.unwrap();
+                    assert_eq!(buf, [SOCKS_VERSION, METHOD_PASSWORD, AUTH_VERSION, 1]);
+                },
+            );
+            assert!(ret.is_err());
+        }
+        assert!(socks.firewall.check(ip).is_err());
+    }
+
+    #[test]
+    fn request() {
+        let cases: [(&[u8], &str); 3] = [
+            (&[ATYP_IPV4, 10, 0, 0, 1], "10.0.0.1"),
+            (
+                &[ATYP_DOMAIN, 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e'],
+                "example",
+            ),
+            (
+                &[ATYP_IPV6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                "::1",
+            ),
+        ];
+        for (addr, host) in cases {
+            let ret = connect(
+                async |mut x| Socks::request(&mut x).await,
+                async |mut x| {
+                    let mut msg = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+                    msg.extend_from_slice(addr);
+                    msg.extend_from_slice(&443u16.to_be_bytes());
+                    x.write_all(&msg).await.unwrap();
+                },
+            );
+            assert_eq!(ret.unwrap(), (host.to_owned(), 443));
+        }
+
+        // Only CONNECT is supported.
+        let ret = connect(
+            async |mut x| Socks::request(&mut x).await,
+            async |mut x| {
+                x.write_all(&[SOCKS_VERSION, 2, 0, ATYP_IPV4])
+                    .await
+                    .unwrap();
+                let mut buf = [0; 2];
+                x.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [SOCKS_VERSION, REPLY_COMMAND_NOT_SUPPORTED]);
+            },
+        );
+        assert!(ret.is_err());
+        let ret = connect(
+            async |mut x| Socks::request(&mut x).await,
+            async |mut x| {
+                x.write_all(&[SOCKS_VERSION, CMD_CONNECT, 0, 5])
+                    .await
+                    .unwrap();
+                let mut buf = [0; 2];
+                x.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [SOCKS_VERSION, REPLY_ADDRESS_NOT_SUPPORTED]);
+            },
+        );
+        assert!(ret.is_err());
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use actix_cloud::tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{
+        Semaphore,
+        mpsc::{Receiver, Sender, channel},
+        oneshot,
+    },
+    time::timeout,
+    try_join,
+};
+use dashmap::DashMap;
+use skynet_api::{HyUuid, Result, anyhow::anyhow, bail};
+use skynet_api_monitor::{
+    StreamAckMessage, StreamCloseMessage, StreamDataMessage, StreamOpenMessage, message::Data,
+};
+
+use crate::PLUGIN_INSTANCE;
+
+/// Max unacknowledged bytes in flight per direction.
+const STREAM_WINDOW: u32 = 256 * 1024;
+/// Max bytes in one data message.
+const STREAM_CHUNK: usize = 16 * 1024;
+/// Max data messages queued per stream.
+const STREAM_QUEUE: usize = 256;
+const STREAM_OPEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Transferred bytes, `up` is from the client to the agent.
+#[derive(Default, Debug)]
+pub struct Traffic {
+    up: AtomicU64,
+    down: AtomicU64,
+}
+
+impl Traffic {
+    pub fn add_up(&self, len: usize) {
+        self.up.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_down(&self, len: usize) {
+        self.down.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Get (up, down) bytes.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.up.load(Ordering::Relaxed),
+            self.down.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Bytes received from the agent but not written out yet.
+#[derive(Default, Debug)]
+struct Window(AtomicU32);
+
+impl Window {
+    /// Reserve `len` bytes, return false when exceeding `STREAM_WINDOW`.
+    fn reserve(&self, len: usize) -> bool {
+        let Ok(len) = u32::try_from(len) else {
+            return false;
+        };
+        self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                x.checked_add(len).filter(|x| *x <= STREAM_WINDOW)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, len: usize) {
+        let len = u32::try_from(len).unwrap_or(u32::MAX);
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                Some(x.saturating_sub(len))
+            });
+    }
+}
+
+/// Return `size` bytes acknowledged by the agent to `credit`, never exceeding the window
+/// even when the agent acknowledges more than sent.
+fn grant(credit: &Semaphore, size: u32) {
+    let max = (STREAM_WINDOW as usize).saturating_sub(credit.available_permits());
+    credit.add_permits((size as usize).min(max));
+}
+
+struct Entry {
+    aid: HyUuid,
+    open: Option<oneshot::Sender<Option<String>>>,
+    data: Sender<Vec<u8>>,
+    credit: Arc<Semaphore>,
+    window: Arc<Window>,
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        // Wake the sending side, the stream is gone.
+        self.credit.close();
+    }
+}
+
+/// Opened stream to a remote target through an agent, closed when dropped.
+pub struct Stream {
+    id: HyUuid,
+    aid: HyUuid,
+    data: Receiver<Vec<u8>>,
+    credit: Arc<Semaphore>,
+    window: Arc<Window>,
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if PLUGIN_INSTANCE.stream.active.remove(&self.id).is_some() {
+            let _ = Streams::send_agent(
+                &self.aid,
+                Data::StreamClose(StreamCloseMessage {
+                    id: self.id.to_string(),
+                }),
+            );
+        }
+    }
+}
+
+impl Stream {
+    /// Pipe `conn` to the stream until both sides are closed, `traffic` is updated in place.
+    ///
+    /// Each direction is shut down on its own, so a half-closed side still receives data.
+    pub async fn pipe(self, conn: TcpStream, traffic: &Traffic) -> Result<()> {
+        let aid = self.aid;
+        self.pipe_with(conn, traffic, |x| Streams::send_agent(&aid, x))
+            .await
+    }
+
+    async fn pipe_with<F>(mut self, conn: TcpStream, traffic: &Traffic, send: F) -> Result<()>
+    where
+        F: Fn(Data) -> Result<()>,
+    {
+        let (mut rd, mut wr) = conn.into_split();
+        let id = self.id.to_string();
+        let (send, credit, window) = (&send, &self.credit, &self.window);
+        let data = &mut self.data;
+        let up = async {
+            let mut buf = vec![0; STREAM_CHUNK];
+            loop {
+                let n = rd.read(&mut buf).await?;
+                // Wait for the agent to consume previous data, fails when the stream is closed.
+                credit.acquire_many(n.try_into()?).await?.forget();
+                // Empty data tells the agent that the client finished sending.
+                send(Data::StreamData(StreamDataMessage {
+                    id: id.clone(),
+                    data: buf[..n].to_vec(),
+                }))?;
+                if n == 0 {
+                    return Ok(());
+                }
+                traffic.add_up(n);
+            }
+        };
+        let down = async {
+            // Sender is dropped when the agent closes the stream, empty data when it finished sending.
+            while let Some(x) = data.recv().await {
+                if x.is_empty() {
+                    break;
+                }
+                wr.write_all(&x).await?;
+                window.release(x.len());
+                traffic.add_down(x.len());
+                send(Data::StreamAck(StreamAckMessage {
+                    id: id.clone(),
+                    size: x.len().try_into()?,
+                }))?;
+            }
+            wr.shutdown().await.map_err(Into::into)
+        };
+        try_join!(up, down).map(|_| ())
+    }
+}
+
+/// TCP streams multiplexed over agent connections.
+#[derive(Default)]
+pub struct Streams {
+    active: DashMap<HyUuid, Entry>,
+}
+
+impl Streams {
+    fn send_agent(aid: &HyUuid, data: Data) -> Result<()> {
+        let agent = PLUGIN_INSTANCE
+            .agent
+            .get(aid)
+            .ok_or(anyhow!("Agent does not exist"))?;
+        agent
+            .message
+            .as_ref()
+            .ok_or(anyhow!("Agent is offline"))?
+            .send(data)?;
+        Ok(())
+    }
+
+    fn insert(&self, aid: &HyUuid) -> (Stream, oneshot::Receiver<Option<String>>) {
+        let id = HyUuid::new();
+        let (open, opened) = oneshot::channel();
+        let (tx, rx) = channel(STREAM_QUEUE);
+        let credit = Arc::new(Semaphore::new(STREAM_WINDOW as usize));
+        let window = Arc::new(Window::default());
+        self.active.insert(
+            id,
+            Entry {
+                aid: *aid,
+                open: Some(open),
+                data: tx,
+                credit: credit.clone(),
+                window: window.clone(),
+            },
+        );
+        let stream = Stream {
+            id,
+            aid: *aid,
+            data: rx,
+            credit,
+            window,
+        };
+        (stream, opened)
+    }
+
+    /// Open stream to `host:port` through agent `aid`.
+    ///
+    /// # Errors
+    /// Will return `Err` when the agent is offline, fails to connect or times out.
+    pub async fn open(&self, aid: &HyUuid, host: &str, port: u16) -> Result<Stream> {
+        let (stream, opened) = self.insert(aid);
+        let id = stream.id;
+        Self::send_agent(
+            aid,
+            Data::StreamOpen(StreamOpenMessage {
+                id: id.to_string(),
+                host: host.to_owned(),
+                port: port.into(),
+                window: STREAM_WINDOW,
+            }),
+        )?;
+        match timeout(STREAM_OPEN_TIMEOUT, opened).await {
+            Ok(Ok(None)) => Ok(stream),
+            Ok(Ok(Some(e))) => bail!("Failed to connect: {e}"),
+            Ok(Err(_)) => bail!("Stream is closed"),
+            Err(_) => bail!("Stream open timed out"),
+        }
+    }
+
+    /// Agent `aid` opened stream `id`, failed when `error` is set.
+    pub fn opened(&self, aid: &HyUuid, id: &HyUuid, error: Option<String>) {
+        let open = self
+            .active
+            .get_mut(id)
+            .filter(|x| x.aid == *aid)
+            .and_then(|mut x| x.open.take());
+        if error.is_some() {
+            self.closed(aid, id);
+        }
+        if let Some(x) = open {
+            let _ = x.send(error);
+        }
+    }
+
+    /// Agent `aid` sent `data` of stream `id`.
+    ///
+    /// The stream is closed when the agent exceeds the window.
+    pub fn data(&self, aid: &HyUuid, id: &HyUuid, data: Vec<u8>) {
+        let accepted = self
+            .active
+            .get(id)
+            .filter(|x| x.aid == *aid)
+            .map(|x| x.window.reserve(data.len()) && x.data.try_send(data).is_ok());
+        if accepted == Some(false) {
+            self.closed(aid, id);
+            let _ = Self::send_agent(
+                aid,
+                Data::StreamClose(StreamCloseMessage { id: id.to_string() }),
+            );
+        }
+    }
+
+    /// Agent `aid` consumed `size` bytes of stream `id`.
+    pub fn ack(&self, aid: &HyUuid, id: &HyUuid, size: u32) {
+        if let Some(x) = self.active.get(id)
+            && x.aid == *aid
+        {
+            grant(&x.credit, size);
+        }
+    }
+
+    /// Agent `aid` closed stream `id`.
+    pub fn closed(&self, aid: &HyUuid, id: &HyUuid) {
+        self.active.remove_if(id, |_, x| x.aid == *aid);
+    }
+
+    /// Close all streams of agent `aid`.
+    pub fn remove_agent(&self, aid: &HyUuid) {
+        self.active.retain(|_, x| x.aid != *aid);
+    }
+
+    /// Close all streams.
+    pub fn clear(&self) {
+        self.active.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_cloud::tokio::{
+        join,
+        net::TcpListener,
+        runtime::Builder,
+        sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    };
+
+    use super::*;
+
+    #[test]
+    fn window() {
+        let window = Window::default();
+        assert!(window.reserve(STREAM_WINDOW as usize - 1));
+        assert!(!window.reserve(2));
+        assert!(window.reserve(1));
+        window.release(STREAM_CHUNK);
+        assert!(window.reserve(STREAM_CHUNK));
+        assert!(!window.reserve(1));
+        window.release(usize::MAX);
+        assert!(window.reserve(STREAM_WINDOW as usize));
+        assert!(!window.reserve(usize::MAX));
+
+        let credit = Semaphore::new(STREAM_WINDOW as usize - 10);
+        grant(&credit, 5);
+        assert_eq!(credit.available_permits(), STREAM_WINDOW as usize - 5);
+        grant(&credit, 100);
+        assert_eq!(credit.available_permits(), STREAM_WINDOW as usize);
+    }
+
+    #[test]
+    fn window_exceeded() {
+        let streams = Streams::default();
+        let aid = HyUuid::new();
+        let (mut stream, _) = streams.insert(&aid);
+        streams.data(&aid, &stream.id, vec![0; STREAM_WINDOW as usize]);
+        assert!(streams.active.contains_key(&stream.id));
+        // Data from other agents is ignored.
+        streams.data(&HyUuid::new(), &stream.id, vec![0]);
+        assert!(streams.active.contains_key(&stream.id));
+        streams.data(&aid, &stream.id, vec![0]);
+        assert!(!streams.active.contains_key(&stream.id));
+        assert!(stream.credit.is_closed());
+        assert_eq!(
+            stream.data.try_recv().unwrap().len(),
+            STREAM_WINDOW as usize
+        );
+        assert!(stream.data.try_recv().is_err());
+    }
+
+    async fn recv_data(rx: &mut UnboundedReceiver<Data>) -> Vec<u8> {
+        loop {
+            match rx.recv().await.unwrap() {
+                Data::StreamData(x) => return x.data,
+                Data::StreamAck(_) => {}
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn half_close() {
+        let rt = Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(async {
+            let streams = Streams::default();
+            let aid = HyUuid::new();
+            let (stream, _) = streams.insert(&aid);
+            let id = stream.id;
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let conn = listener.accept().await.unwrap().0;
+            let (tx, mut rx) = unbounded_channel();
+            let traffic = Traffic::default();
+
+            let pipe = stream.pipe_with(conn, &traffic, |x| Ok(tx.send(x)?));
+            let peer = async {
+                // Client finishes sending, agent still replies.
+                client.write_all(b"ping").await.unwrap();
+                client.shutdown().await.unwrap();
+                assert_eq!(recv_data(&mut rx).await, b"ping");
+                assert!(recv_data(&mut rx).await.is_empty());
+                streams.data(&aid, &id, b"pong".to_vec());
+                streams.data(&aid, &id, Vec::new());
+                let mut buf = Vec::new();
+                client.read_to_end(&mut buf).await.unwrap();
+                assert_eq!(buf, b"pong");
+            };
+            let (ret, _) = join!(pipe, peer);
+            ret.unwrap();
+            assert_eq!(traffic.get(), (4, 4));
+            let ack: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|x| match x {
+                    Data::StreamAck(x) => Some(x.size),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(ack, [4]);
+        });
+    }
+}
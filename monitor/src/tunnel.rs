@@ -0,0 +1,185 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use actix_cloud::{
+    chrono::Utc,
+    tokio::{net::TcpListener, select, spawn, sync::watch, task::JoinHandle, time::sleep},
+    tracing::debug,
+};
+use dashmap::DashMap;
+use serde::Serialize;
+use skynet_api::{HyUuid, Result};
+
+use crate::{PLUGIN_INSTANCE, stream::Traffic};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TunnelInfo {
+    pub id: HyUuid,
+    pub aid: HyUuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<HyUuid>,
+    pub host: String,
+    pub port: u16,
+    pub listen: SocketAddr,
+    pub connections: usize,
+    pub bytes_up: u64,
+    pub bytes_down: u64,
+    pub created_at: i64,
+}
+
+/// Forward target shared by tunnel connections.
+struct Target {
+    aid: HyUuid,
+    host: String,
+    port: u16,
+    connections: AtomicUsize,
+    traffic: Traffic,
+}
+
+struct Tunnel {
+    uid: Option<HyUuid>,
+    listen: SocketAddr,
+    target: Arc<Target>,
+    created_at: i64,
+    task: JoinHandle<()>,
+    // Connections are closed when dropped.
+    _close: watch::Sender<()>,
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Local TCP port forwarding through agents.
+#[derive(Default)]
+pub struct Tunnels {
+    active: DashMap<HyUuid, Tunnel>,
+}
+
+impl Tunnels {
+    /// Listen on `bind` for user `uid` and forward connections to `host:port` through agent `aid`.
+    ///
+    /// # Errors
+    /// Will return `Err` when failed to listen on `bind`.
+    pub async fn open(
+        &self,
+        aid: &HyUuid,
+        uid: Option<HyUuid>,
+        host: &str,
+        port: u16,
+        bind: SocketAddr,
+    ) -> Result<TunnelInfo> {
+        let listener = TcpListener::bind(bind).await?;
+        let listen = listener.local_addr()?;
+        let id = HyUuid::new();
+        let target = Arc::new(Target {
+            aid: *aid,
+            host: host.to_owned(),
+            port,
+            connections: AtomicUsize::new(0),
+            traffic: Traffic::default(),
+        });
+        let (close, _) = watch::channel(());
+        let task = spawn(Self::accept(
+            id,
+            listener,
+            target.clone(),
+            close.subscribe(),
+        ));
+        self.active.insert(
+            id,
+            Tunnel {
+                uid,
+                listen,
+                target,
+                created_at: Utc::now().timestamp_millis(),
+                task,
+                _close: close,
+            },
+        );
+        Ok(self.get(&id).unwrap())
+    }
+
+    async fn accept(
+        id: HyUuid,
+        listener: TcpListener,
+        target: Arc<Target>,
+        close: watch::Receiver<()>,
+    ) {
+        loop {
+            let (conn, addr) = match listener.accept().await {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!(tid = %id, error = %e, "Failed to accept monitor tunnel connection");
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let target = target.clone();
+            let mut close = close.clone();
+            spawn(async move {
+                target.connections.fetch_add(1, Ordering::Relaxed);
+                let ret = select! {
+                    x = async {
+                        PLUGIN_INSTANCE
+                            .stream
+                            .open(&target.aid, &target.host, target.port)
+                            .await?
+                            .pipe(conn, &target.traffic)
+                            .await
+                    } => x,
+                    _ = close.changed() => Ok(()),
+                };
+                if let Err(e) = ret {
+                    debug!(tid = %id, %addr, error = %e, "Monitor tunnel connection failed");
+                }
+                target.connections.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+    }
+
+    pub fn get(&self, id: &HyUuid) -> Option<TunnelInfo> {
+        self.active.get(id).map(|x| {
+            let (bytes_up, bytes_down) = x.target.traffic.get();
+            TunnelInfo {
+                id: *id,
+                aid: x.target.aid,
+                uid: x.uid,
+                host: x.target.host.clone(),
+                port: x.target.port,
+                listen: x.listen,
+                connections: x.target.connections.load(Ordering::Relaxed),
+                bytes_up,
+                bytes_down,
+                created_at: x.created_at,
+            }
+        })
+    }
+
+    pub fn list(&self) -> Vec<TunnelInfo> {
+        let id: Vec<HyUuid> = self.active.iter().map(|x| *x.key()).collect();
+        id.iter().filter_map(|x| self.get(x)).collect()
+    }
+
+    /// Close tunnel `id` and all its connections.
+    pub fn close(&self, id: &HyUuid) -> bool {
+        self.active.remove(id).is_some()
+    }
+
+    pub fn remove_agent(&self, aid: &HyUuid) {
+        self.active.retain(|_, x| x.target.aid != *aid);
+    }
+
+    /// Close all tunnels and their connections.
+    pub fn clear(&self) {
+        self.active.clear();
+    }
+}
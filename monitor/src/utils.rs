@@ -0,0 +1,23 @@
+/// Length of `x` without the incomplete UTF-8 sequence at the end.
+pub fn utf8_boundary(x: &[u8]) -> usize {
+    match str::from_utf8(x) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => x.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary() {
+        let x = "a你".as_bytes();
+        assert_eq!(utf8_boundary(x), 4);
+        assert_eq!(utf8_boundary(&x[..3]), 1);
+        assert_eq!(utf8_boundary(&x[..2]), 1);
+        // Invalid bytes are not held back.
+        assert_eq!(utf8_boundary(b"a\xff"), 2);
+        assert_eq!(utf8_boundary(b""), 0);
+    }
+}
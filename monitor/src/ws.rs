@@ -1,108 +1,542 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use actix_cloud::actix_web::{HttpResponse, web};
+use actix_cloud::{
+    actix_web::{HttpResponse, web},
+    tokio::spawn,
+};
 use bytes::Bytes;
 use skynet_api::{
-    HyUuid, Result, anyhow::anyhow, bail, ffi_rpc::registry::Registry, plugin::WSMessage,
-    request::Request, service::SKYNET_SERVICE, tracing::debug,
+    HyUuid, Result,
+    anyhow::anyhow,
+    bail,
+    ffi_rpc::registry::Registry,
+    permission::PermissionItem,
+    plugin::WSMessage,
+    request::Request,
+    service::SKYNET_SERVICE,
+    tracing::{debug, info},
 };
 use skynet_api_monitor::{
-    FrontendMessage, ShellDisconnectMessage, ShellErrorMessage, frontend_message::Data, message,
-    prost::Message as _,
+    FrontendMessage, ShellDisconnectMessage, ShellErrorMessage, ShellOutputMessage,
+    frontend_message::Data, message, prost::Message as _,
+};
+
+use crate::{
+    PLUGIN_INSTANCE,
+    acl::{Acl, AclRight},
 };
 
-use crate::PLUGIN_INSTANCE;
+/// Frontend connections bound to a shell token.
+#[derive(Default)]
+pub struct ShellBinding {
+    /// Connection which opened the shell, `None` when detached.
+    pub owner: Option<HyUuid>,
+    /// Joined connections and whether they can write.
+    pub member: HashMap<HyUuid, bool>,
+}
+
+impl ShellBinding {
+    pub fn target(&self) -> Vec<HyUuid> {
+        self.owner
+            .into_iter()
+            .chain(self.member.keys().copied())
+            .collect()
+    }
+}
+
+/// Websocket sender of a frontend connection, cloned to send without holding the shell map.
+#[derive(Clone)]
+pub struct ShellSender {
+    id: HyUuid,
+    skynet: Arc<skynet_api::service::Service>,
+    reg: Arc<Registry>,
+}
+
+impl ShellSender {
+    pub async fn send(&self, data: FrontendMessage) -> Result<()> {
+        self.skynet
+            .websocket_send(
+                self.reg.as_ref(),
+                &self.id,
+                &WSMessage::Binary(data.encode_to_vec().into()),
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Get senders of frontend connections `id`, disconnected ones are skipped.
+    fn find(id: &[HyUuid]) -> Vec<Self> {
+        id.iter()
+            .filter_map(|x| PLUGIN_INSTANCE.shell.get(x).map(|x| x.sender()))
+            .collect()
+    }
+}
 
 pub struct ShellService {
     id: HyUuid,
-    agent_id: Option<HyUuid>,
-    token: HyUuid,
+    uid: Option<HyUuid>,
+    perm: HashMap<HyUuid, PermissionItem>,
+    ip: String,
+    shell: HashMap<HyUuid, HyUuid>,          // shell token to agent id
+    joined: HashMap<HyUuid, (HyUuid, bool)>, // shell token to agent id and write permission
     skynet: Arc<skynet_api::service::Service>,
     reg: Arc<Registry>,
 }
 
 impl Drop for ShellService {
     fn drop(&mut self) {
-        self.cleanup();
+        let detach = *PLUGIN_INSTANCE.shell_grace.read() != 0;
+        let token: Vec<HyUuid> = self.shell.keys().copied().collect();
+        for i in token {
+            if detach && PLUGIN_INSTANCE.session.detach(&i) {
+                self.shell.remove(&i);
+                if let Some(mut x) = PLUGIN_INSTANCE.shell_binding.get_mut(&i) {
+                    x.owner = None;
+                }
+                PLUGIN_INSTANCE
+                    .shell_binding
+                    .remove_if(&i, |_, x| x.member.is_empty());
+                info!(trace_id = ?self.id, token = %i, "Detach monitor shell");
+            } else {
+                self.cleanup(&i, "Shell is closed");
+            }
+        }
+        let token: Vec<HyUuid> = self.joined.keys().copied().collect();
+        for i in token {
+            self.leave(&i);
+        }
     }
 }
 
 impl ShellService {
-    pub fn new(skynet: skynet_api::service::Service, reg: Arc<Registry>, id: &HyUuid) -> Self {
+    pub fn new(
+        skynet: skynet_api::service::Service,
+        reg: Arc<Registry>,
+        id: &HyUuid,
+        uid: Option<HyUuid>,
+        perm: HashMap<HyUuid, PermissionItem>,
+        ip: String,
+    ) -> Self {
         Self {
-            token: HyUuid::nil(),
             skynet: skynet.into(),
             reg,
             id: *id,
-            agent_id: None,
+            uid,
+            perm,
+            ip,
+            shell: HashMap::new(),
+            joined: HashMap::new(),
         }
     }
 
-    pub async fn send(&self, data: FrontendMessage) -> Result<()> {
-        self.skynet
-            .websocket_send(
-                self.reg.as_ref(),
-                &self.id,
-                &WSMessage::Binary(data.encode_to_vec().into()),
-            )
-            .await
-            .map_err(Into::into)
+    pub fn sender(&self) -> ShellSender {
+        ShellSender {
+            id: self.id,
+            skynet: self.skynet.clone(),
+            reg: self.reg.clone(),
+        }
     }
 
-    fn cleanup(&mut self) {
-        if self.agent_id.is_some() {
-            let _ = self.send_agent(message::Data::ShellDisconnect(ShellDisconnectMessage {
-                token: Some(self.token.to_string()),
-            }));
-            debug!(trace_id = ?self.id, "Websocket cleanup");
+    /// Send `data` to all frontends bound to shell `token`.
+    pub async fn broadcast(token: &HyUuid, data: FrontendMessage) {
+        let target = PLUGIN_INSTANCE
+            .shell_binding
+            .get(token)
+            .map(|x| x.target())
+            .unwrap_or_default();
+        for i in ShellSender::find(&target) {
+            let _ = i.send(data.clone()).await;
         }
-        self.agent_id = None;
-        self.token = HyUuid::nil();
-        PLUGIN_INSTANCE.shell_binding.remove(&self.token);
     }
 
-    fn send_agent(&self, data: message::Data) -> Result<()> {
-        if let Some(id) = &self.agent_id {
-            if let Some(agent) = PLUGIN_INSTANCE.agent.get(id) {
-                if !agent.disable_shell {
-                    let c = agent.message.as_ref().ok_or(anyhow!("Agent is offline"))?;
-                    c.send(data)?;
-                }
-                Ok(())
-            } else {
-                bail!("Agent does not exist")
+    /// Close shell `token` with `reason`, bound frontends are notified.
+    ///
+    /// Return false when `token` is not found.
+    pub async fn terminate(token: &HyUuid, reason: &str) -> bool {
+        let Some(id) = PLUGIN_INSTANCE
+            .shell_binding
+            .get(token)
+            .and_then(|x| x.owner)
+        else {
+            if let Some((aid, _)) = PLUGIN_INSTANCE.session.detached(token) {
+                Self::close_detached(token, &aid, reason);
+                return true;
+            }
+            return false;
+        };
+        let sender = PLUGIN_INSTANCE.shell.get(&id).map(|x| x.sender());
+        if let Some(x) = sender {
+            let _ = x
+                .send(FrontendMessage {
+                    id: None,
+                    data: Some(Data::ShellError(ShellErrorMessage {
+                        token: Some(token.to_string()),
+                        error: reason.to_owned(),
+                    })),
+                })
+                .await;
+        }
+        if let Some(mut x) = PLUGIN_INSTANCE.shell.get_mut(&id)
+            && x.shell.contains_key(token)
+        {
+            x.cleanup(token, reason);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Close shells detached longer than the grace period, idle or exceeding max duration.
+    pub async fn purge() {
+        let grace = Duration::from_secs((*PLUGIN_INSTANCE.shell_grace.read()).into());
+        for (token, aid) in PLUGIN_INSTANCE.session.expired(grace) {
+            Self::close_detached(&token, &aid, "Detached shell expired");
+            info!(token = %token, aid = %aid, "Detached monitor shell expired");
+        }
+        for (token, reason) in PLUGIN_INSTANCE.session.timeout() {
+            if Self::terminate(&token, &reason).await {
+                info!(token = %token, reason, "Monitor shell limit reached");
+            }
+        }
+    }
+
+    fn close_detached(token: &HyUuid, aid: &HyUuid, reason: &str) {
+        let _ = Self::send_agent(
+            aid,
+            message::Data::ShellDisconnect(ShellDisconnectMessage {
+                token: Some(token.to_string()),
+            }),
+        );
+        PLUGIN_INSTANCE.recording.stop(token);
+        PLUGIN_INSTANCE.session.stop(token);
+        Self::unbind(token, reason);
+    }
+
+    /// Remove binding of shell `token`, joined frontends are notified with `reason`.
+    fn unbind(token: &HyUuid, reason: &str) {
+        let Some((_, x)) = PLUGIN_INSTANCE.shell_binding.remove(token) else {
+            return;
+        };
+        if x.member.is_empty() {
+            return;
+        }
+        let data = FrontendMessage {
+            id: None,
+            data: Some(Data::ShellError(ShellErrorMessage {
+                token: Some(token.to_string()),
+                error: reason.to_owned(),
+            })),
+        };
+        let target: Vec<HyUuid> = x.member.into_keys().collect();
+        spawn(async move {
+            for i in ShellSender::find(&target) {
+                let _ = i.send(data.clone()).await;
+            }
+        });
+    }
+
+    fn cleanup(&mut self, token: &HyUuid, reason: &str) {
+        if let Some(aid) = self.shell.remove(token) {
+            let _ = Self::send_agent(
+                &aid,
+                message::Data::ShellDisconnect(ShellDisconnectMessage {
+                    token: Some(token.to_string()),
+                }),
+            );
+            debug!(trace_id = ?self.id, token = %token, "Websocket cleanup");
+            PLUGIN_INSTANCE.recording.stop(token);
+            PLUGIN_INSTANCE.session.stop(token);
+            Self::unbind(token, reason);
+        }
+    }
+
+    /// Leave joined shell `token`, the shell keeps running.
+    fn leave(&mut self, token: &HyUuid) {
+        if self.joined.remove(token).is_some() {
+            if let Some(mut x) = PLUGIN_INSTANCE.shell_binding.get_mut(token) {
+                x.member.remove(&self.id);
             }
+            PLUGIN_INSTANCE
+                .shell_binding
+                .remove_if(token, |_, x| x.owner.is_none() && x.member.is_empty());
+            info!(trace_id = ?self.id, token = %token, "Leave monitor shell");
+        }
+    }
+
+    /// Find shell of `token`, the only shell is used when `token` is not set.
+    fn resolve(&self, token: Option<&str>) -> Result<(HyUuid, HyUuid)> {
+        let token = if let Some(token) = token {
+            HyUuid::parse(token)?
+        } else if self.shell.len() + self.joined.len() == 1 {
+            *self.shell.keys().chain(self.joined.keys()).next().unwrap()
         } else {
             bail!("Shell does not connect")
+        };
+        let aid = self
+            .shell
+            .get(&token)
+            .or_else(|| self.joined.get(&token).map(|x| &x.0))
+            .ok_or(anyhow!("Shell does not connect"))?;
+        Ok((token, *aid))
+    }
+
+    /// Check whether this connection can write to shell `token`.
+    fn writable(&self, token: &HyUuid) -> Result<()> {
+        if self.shell.contains_key(token) {
+            return Ok(());
+        }
+        if !self.joined.get(token).is_some_and(|x| x.1) {
+            bail!("Shell is read-only");
+        }
+        if !PLUGIN_INSTANCE
+            .shell_binding
+            .get(token)
+            .is_some_and(|x| x.member.contains_key(&self.id))
+        {
+            bail!("Shell does not connect");
+        }
+        Ok(())
+    }
+
+    fn send_agent(aid: &HyUuid, data: message::Data) -> Result<()> {
+        if let Some(agent) = PLUGIN_INSTANCE.agent.get(aid) {
+            if !agent.disable_shell {
+                let c = agent.message.as_ref().ok_or(anyhow!("Agent is offline"))?;
+                c.send(data)?;
+            }
+            Ok(())
+        } else {
+            bail!("Agent does not exist")
+        }
+    }
+
+    /// Attach detached shell `token`, return scrollback message to send.
+    fn attach(&mut self, token: &str) -> Result<FrontendMessage> {
+        let token = HyUuid::parse(token)?;
+        let (aid, uid) = PLUGIN_INSTANCE
+            .session
+            .detached(&token)
+            .ok_or(anyhow!("Shell is not detached"))?;
+        if uid != self.uid && !Acl::is_manager(&self.perm) {
+            bail!("Permission denied");
+        }
+        if !PLUGIN_INSTANCE
+            .acl
+            .check(self.uid, &self.perm, &aid, AclRight::Shell, || {
+                PLUGIN_INSTANCE.agent_labels(&aid)
+            })
+        {
+            bail!("Permission denied");
+        }
+        let scrollback = PLUGIN_INSTANCE
+            .session
+            .attach(&token)
+            .ok_or(anyhow!("Shell is not detached"))?;
+        self.leave(&token);
+        PLUGIN_INSTANCE
+            .shell_binding
+            .entry(token)
+            .or_default()
+            .owner = Some(self.id);
+        self.shell.insert(token, aid);
+        info!(trace_id = ?self.id, token = %token, aid = %aid, "Attach monitor shell");
+        Ok(FrontendMessage {
+            id: Some(aid.to_string()),
+            data: Some(Data::ShellOutput(ShellOutputMessage {
+                token: Some(token.to_string()),
+                data: scrollback,
+            })),
+        })
+    }
+
+    /// Join running shell `token` of another connection, read-only unless `write` is set.
+    ///
+    /// Return scrollback message to send.
+    fn join(&mut self, token: &str, write: bool) -> Result<FrontendMessage> {
+        let token = HyUuid::parse(token)?;
+        if self.shell.contains_key(&token) {
+            bail!("Shell is already connected");
+        }
+        let (aid, _) = PLUGIN_INSTANCE
+            .session
+            .owner(&token)
+            .ok_or(anyhow!("Shell does not connect"))?;
+        let right = if write {
+            AclRight::Shell
+        } else {
+            AclRight::View
+        };
+        if !PLUGIN_INSTANCE
+            .acl
+            .check(self.uid, &self.perm, &aid, right, || {
+                PLUGIN_INSTANCE.agent_labels(&aid)
+            })
+        {
+            bail!("Permission denied");
+        }
+        PLUGIN_INSTANCE
+            .shell_binding
+            .entry(token)
+            .or_default()
+            .member
+            .insert(self.id, write);
+        self.joined.insert(token, (aid, write));
+        info!(
+            trace_id = ?self.id,
+            token = %token,
+            aid = %aid,
+            uid = ?self.uid,
+            write,
+            ip = self.ip,
+            "Join monitor shell",
+        );
+        Ok(FrontendMessage {
+            id: Some(aid.to_string()),
+            data: Some(Data::ShellOutput(ShellOutputMessage {
+                token: Some(token.to_string()),
+                data: PLUGIN_INSTANCE
+                    .session
+                    .scrollback(&token)
+                    .unwrap_or_default(),
+            })),
+        })
+    }
+
+    /// Handle frontend message `text`, return message to send back when any.
+    ///
+    /// The shell token of the message is returned along with the result, so that errors are
+    /// routed to the failing shell. Never send in place, the connection is locked in the shell map.
+    fn recv(&mut self, text: Bytes) -> (Option<HyUuid>, Result<Option<FrontendMessage>>) {
+        let msg = match FrontendMessage::decode(text) {
+            Ok(x) => x,
+            Err(e) => return (None, Err(e.into())),
+        };
+        let token = msg.data.as_ref().and_then(|x| self.token(x));
+        (token, self.handle(msg))
+    }
+
+    /// Build error message of shell `token` for frontend.
+    fn error(token: Option<HyUuid>, e: &skynet_api::anyhow::Error) -> FrontendMessage {
+        FrontendMessage {
+            id: None,
+            data: Some(Data::ShellError(ShellErrorMessage {
+                token: token.map(|x| x.to_string()),
+                error: e.to_string(),
+            })),
+        }
+    }
+
+    /// Get shell token of `data`, the only shell is used when not set.
+    fn token(&self, data: &Data) -> Option<HyUuid> {
+        let token = match data {
+            Data::ShellConnect(x) => Some(x.token.as_str()),
+            Data::ShellInput(x) => x.token.as_deref(),
+            Data::ShellResize(x) => x.token.as_deref(),
+            Data::ShellDisconnect(x) => x.token.as_deref(),
+            Data::ShellAttach(x) => Some(x.token.as_str()),
+            Data::ShellJoin(x) => Some(x.token.as_str()),
+            _ => return None,
+        };
+        match token {
+            Some(x) => HyUuid::parse(x).ok(),
+            None => self.resolve(None).ok().map(|x| x.0),
         }
     }
 
-    fn recv(&mut self, text: Bytes) -> Result<()> {
-        let msg = FrontendMessage::decode(text)?;
+    fn handle(&mut self, msg: FrontendMessage) -> Result<Option<FrontendMessage>> {
         if let Some(data) = msg.data {
             match data {
                 Data::ShellConnect(data) => {
-                    self.cleanup();
                     let token = HyUuid::parse(&data.token)?;
-                    PLUGIN_INSTANCE.shell_binding.insert(token, self.id);
-                    self.agent_id =
-                        Some(HyUuid::parse(&msg.id.ok_or(anyhow!("Invalid message"))?)?);
-                    self.token = token;
-                    debug!(trace_id = ?self.id, "Websocket shell connect");
-                    self.send_agent(message::Data::ShellConnect(data))
+                    let aid = HyUuid::parse(&msg.id.ok_or(anyhow!("Invalid message"))?)?;
+                    if !PLUGIN_INSTANCE.acl.check(
+                        self.uid,
+                        &self.perm,
+                        &aid,
+                        AclRight::Shell,
+                        || PLUGIN_INSTANCE.agent_labels(&aid),
+                    ) {
+                        bail!("Permission denied");
+                    }
+                    if !PLUGIN_INSTANCE.agent_shell(&aid).contains(&data.cmd) {
+                        bail!("Shell program is not allowed");
+                    }
+                    if PLUGIN_INSTANCE
+                        .shell_binding
+                        .get(&token)
+                        .is_some_and(|x| x.owner != Some(self.id))
+                    {
+                        bail!("Shell token is in use");
+                    }
+                    // Reject before closing the shell being replaced.
+                    PLUGIN_INSTANCE.session.check_cap(&token, &aid, self.uid)?;
+                    let recorder = if PLUGIN_INSTANCE.recording.enabled(&aid) {
+                        Some(
+                            PLUGIN_INSTANCE
+                                .recording
+                                .create(&token, &aid, self.uid, &data.cmd, data.rows, data.cols)?,
+                        )
+                    } else {
+                        None
+                    };
+                    self.cleanup(&token, "Shell is closed");
+                    if let Some(x) = recorder {
+                        PLUGIN_INSTANCE.recording.start(&token, x);
+                    }
+                    PLUGIN_INSTANCE.shell_binding.insert(
+                        token,
+                        ShellBinding {
+                            owner: Some(self.id),
+                            ..Default::default()
+                        },
+                    );
+                    self.shell.insert(token, aid);
+                    let sid = PLUGIN_INSTANCE
+                        .session
+                        .start(&token, &aid, self.uid, &data.cmd, &self.ip);
+                    info!(
+                        trace_id = ?self.id,
+                        sid = %sid,
+                        aid = %aid,
+                        uid = ?self.uid,
+                        cmd = data.cmd,
+                        ip = self.ip,
+                        "Open monitor shell",
+                    );
+                    if let Err(e) = Self::send_agent(&aid, message::Data::ShellConnect(data)) {
+                        self.cleanup(&token, "Shell is closed");
+                        return Err(e);
+                    }
+                    Ok(None)
                 }
                 Data::ShellInput(mut data) => {
-                    data.token = Some(self.token.to_string());
-                    self.send_agent(message::Data::ShellInput(data))
+                    let (token, aid) = self.resolve(data.token.as_deref())?;
+                    self.writable(&token)?;
+                    data.token = Some(token.to_string());
+                    PLUGIN_INSTANCE.recording.input(&token, &data.data);
+                    PLUGIN_INSTANCE.session.input(&token, data.data.len());
+                    Self::send_agent(&aid, message::Data::ShellInput(data)).map(|_| None)
                 }
                 Data::ShellResize(mut data) => {
-                    data.token = Some(self.token.to_string());
-                    self.send_agent(message::Data::ShellResize(data))
+                    let (token, aid) = self.resolve(data.token.as_deref())?;
+                    self.writable(&token)?;
+                    data.token = Some(token.to_string());
+                    PLUGIN_INSTANCE
+                        .recording
+                        .resize(&token, data.rows, data.cols);
+                    Self::send_agent(&aid, message::Data::ShellResize(data)).map(|_| None)
                 }
-                Data::ShellDisconnect(_) => {
-                    self.cleanup();
-                    Ok(())
+                Data::ShellDisconnect(data) => {
+                    let (token, _) = self.resolve(data.token.as_deref())?;
+                    if self.shell.contains_key(&token) {
+                        self.cleanup(&token, "Shell is closed");
+                    } else {
+                        self.leave(&token);
+                    }
+                    Ok(None)
                 }
+                Data::ShellAttach(data) => self.attach(&data.token).map(Some),
+                Data::ShellJoin(data) => self.join(&data.token, data.write).map(Some),
                 _ => bail!("Invalid message type"),
             }
         } else {
@@ -116,32 +550,35 @@ pub async fn service(reg: web::Data<Registry>, req: Request, data: WSMessage) ->
     match data {
         WSMessage::Connect => {
             let skynet: skynet_api::service::Service = reg.get(SKYNET_SERVICE).unwrap().into();
-            PLUGIN_INSTANCE
-                .shell
-                .insert(id, ShellService::new(skynet, reg.into_inner(), &id));
+            PLUGIN_INSTANCE.shell.insert(
+                id,
+                ShellService::new(
+                    skynet,
+                    reg.into_inner(),
+                    &id,
+                    req.uid,
+                    req.perm.clone(),
+                    req.extension.real_ip.ip().to_string(),
+                ),
+            );
         }
         WSMessage::Binary(s) => {
             let skynet: skynet_api::service::Service = reg.get(SKYNET_SERVICE).unwrap().into();
-            if let Some(mut x) = PLUGIN_INSTANCE.shell.get_mut(&id) {
-                if let Err(e) = x.recv(s) {
-                    let _ = skynet
-                        .websocket_send(
-                            &reg,
-                            &id,
-                            &WSMessage::Binary(
-                                FrontendMessage {
-                                    id: None,
-                                    data: Some(Data::ShellError(ShellErrorMessage {
-                                        token: None,
-                                        error: e.to_string(),
-                                    })),
-                                }
-                                .encode_to_vec()
-                                .into(),
-                            ),
-                        )
-                        .await;
-                    debug!(error = %e, "Error handle ws message");
+            // Release the shell map before sending, slow clients must not block other sessions.
+            let ret = PLUGIN_INSTANCE
+                .shell
+                .get_mut(&id)
+                .map(|mut x| (x.sender(), x.recv(s)));
+            if let Some((sender, (token, ret))) = ret {
+                match ret {
+                    Ok(Some(data)) => {
+                        let _ = sender.send(data).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        let _ = sender.send(ShellService::error(token, &e)).await;
+                        debug!(token = ?token, error = %e, "Error handle ws message");
+                    }
                 }
             } else {
                 skynet.websocket_close(&reg, &id).await;
@@ -154,3 +591,73 @@ pub async fn service(reg: web::Data<Registry>, req: Request, data: WSMessage) ->
     };
     HttpResponse::Ok().finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use skynet_api::ffi_rpc::{
+        abi_stable::{
+            prefix_type::PrefixTypeTrait,
+            std_types::{RString, RVec},
+        },
+        async_ffi::{BorrowingFfiFuture, FutureExt},
+        plugin::PluginApi,
+    };
+    use skynet_api_monitor::ShellInputMessage;
+
+    use super::*;
+
+    extern "C" fn call<'fut>(
+        _: RString,
+        _: &'fut Registry,
+        _: RVec<u8>,
+    ) -> BorrowingFfiFuture<'fut, RVec<u8>> {
+        async { RVec::new() }.into_ffi()
+    }
+
+    fn input(token: Option<HyUuid>) -> Bytes {
+        FrontendMessage {
+            id: None,
+            data: Some(Data::ShellInput(ShellInputMessage {
+                token: token.map(|x| x.to_string()),
+                data: b"ls".to_vec(),
+            })),
+        }
+        .encode_to_vec()
+        .into()
+    }
+
+    #[test]
+    fn error_token() {
+        let skynet = PluginApi { call }.leak_into_prefix().into();
+        let mut s = ShellService::new(
+            skynet,
+            Arc::new(Registry::default()),
+            &HyUuid::new(),
+            None,
+            HashMap::new(),
+            String::new(),
+        );
+        let (ok, failed) = (HyUuid::new(), HyUuid::new());
+        s.shell.insert(ok, HyUuid::new());
+        s.shell.insert(failed, HyUuid::new());
+
+        // Agent of `failed` does not exist, error goes to `failed` only.
+        let (token, ret) = s.recv(input(Some(failed)));
+        let e = ret.unwrap_err();
+        let Some(Data::ShellError(msg)) = ShellService::error(token, &e).data else {
+            panic!("not a shell error");
+        };
+        assert_eq!(msg.token, Some(failed.to_string()));
+        assert_ne!(msg.token, Some(ok.to_string()));
+
+        // Token is ambiguous with two shells.
+        let (token, ret) = s.recv(input(None));
+        assert!(ret.is_err());
+        assert_eq!(token, None);
+
+        s.shell.remove(&ok);
+        let (token, ret) = s.recv(input(None));
+        assert!(ret.is_err());
+        assert_eq!(token, Some(failed));
+    }
+}
@@ -0,0 +1,31 @@
+use actix_cloud::chrono;
+use actix_cloud::macros::{entity_behavior, entity_id, entity_timestamp};
+use serde::{Deserialize, Serialize};
+use skynet_api::sea_orm::{self, prelude::*};
+
+use crate::HyUuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_acls")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: HyUuid,
+    pub uid: HyUuid, // granted user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aid: Option<HyUuid>, // granted agent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // granted agent group, agents with the label
+    pub perm: i32,   // granted rights, bit mask
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[entity_id(HyUuid::new())]
+#[entity_timestamp]
+impl ActiveModel {}
+
+#[entity_behavior]
+impl ActiveModelBehavior for ActiveModel {}
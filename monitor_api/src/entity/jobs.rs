@@ -0,0 +1,103 @@
+use actix_cloud::chrono;
+use actix_cloud::macros::{entity_behavior, entity_id, entity_timestamp};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use skynet_api::sea_orm::{self, prelude::*};
+
+use crate::HyUuid;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize_repr,
+    Deserialize_repr,
+)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+#[repr(u8)]
+pub enum JobKind {
+    #[default]
+    Command = 0,
+    File = 1,
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize_repr,
+    Deserialize_repr,
+)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+#[repr(u8)]
+pub enum JobStatus {
+    #[default]
+    Running = 0,
+    Finished = 1,
+    Timeout = 2,
+    Cancelled = 3,
+    Disconnected = 4,
+    Queued = 5,
+    Expired = 6,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_jobs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: HyUuid,
+    pub aid: HyUuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<HyUuid>, // issuer user, `None` for plugin calls
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub cmd: String, // command, or save path for files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    pub output: Vec<u8>, // output, or response message for files
+    pub timed_out: bool,
+    pub truncated: bool,
+    #[serde(skip)]
+    pub payload: Option<Vec<u8>>, // request kept until delivered for queued jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expire_at: Option<i64>, // queued job expire time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::agents::Entity",
+        from = "Column::Aid",
+        to = "super::agents::Column::Id"
+    )]
+    Agent,
+}
+
+impl Related<super::agents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Agent.def()
+    }
+}
+
+#[entity_id(HyUuid::new())]
+#[entity_timestamp]
+impl ActiveModel {}
+
+#[entity_behavior]
+impl ActiveModelBehavior for ActiveModel {}
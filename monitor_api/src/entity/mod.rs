@@ -1,3 +1,11 @@
+pub mod acls;
 pub mod agent_settings;
 pub mod agents;
+pub mod jobs;
 pub mod passive_agents;
+pub mod schedule_runs;
+pub mod schedules;
+pub mod scripts;
+pub mod shell_recordings;
+pub mod shell_sessions;
+pub mod socks_users;
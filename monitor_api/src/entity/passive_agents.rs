@@ -5,7 +5,7 @@ use skynet_api::sea_orm::{self, prelude::*};
 
 use crate::HyUuid;
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Default, Serialize, Deserialize)]
 #[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_passive_agents")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -13,12 +13,31 @@ pub struct Model {
     pub name: String,
     pub address: String,
     pub retry_time: i32,
+    pub retry_max_time: i32,
+    pub retry_multiplier: f64,
+    pub retry_jitter: f64,
+    pub retry_limit: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aid: Option<HyUuid>,
     pub created_at: i64,
     pub updated_at: i64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::agents::Entity",
+        from = "Column::Aid",
+        to = "super::agents::Column::Id"
+    )]
+    Agent,
+}
+
+impl Related<super::agents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Agent.def()
+    }
+}
 
 #[entity_id(HyUuid::new())]
 #[entity_timestamp]
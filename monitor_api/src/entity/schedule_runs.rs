@@ -0,0 +1,82 @@
+use actix_cloud::chrono;
+use actix_cloud::macros::{entity_behavior, entity_id, entity_timestamp};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use skynet_api::sea_orm::{self, prelude::*};
+
+use crate::HyUuid;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize_repr,
+    Deserialize_repr,
+)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+#[repr(u8)]
+pub enum RunStatus {
+    #[default]
+    Success = 0,
+    Failed = 1,
+    Offline = 2,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_schedule_runs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: HyUuid,
+    pub sid: HyUuid,
+    pub rid: HyUuid, // run id, shared by all agents in one run
+    pub aid: HyUuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<HyUuid>, // command job id
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    pub message: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::schedules::Entity",
+        from = "Column::Sid",
+        to = "super::schedules::Column::Id"
+    )]
+    Schedule,
+    #[sea_orm(
+        belongs_to = "super::agents::Entity",
+        from = "Column::Aid",
+        to = "super::agents::Column::Id"
+    )]
+    Agent,
+}
+
+impl Related<super::schedules::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Schedule.def()
+    }
+}
+
+impl Related<super::agents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Agent.def()
+    }
+}
+
+#[entity_id(HyUuid::new())]
+#[entity_timestamp]
+impl ActiveModel {}
+
+#[entity_behavior]
+impl ActiveModelBehavior for ActiveModel {}
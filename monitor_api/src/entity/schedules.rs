@@ -0,0 +1,47 @@
+use actix_cloud::chrono;
+use actix_cloud::macros::{entity_behavior, entity_id, entity_timestamp};
+use serde::{Deserialize, Serialize};
+use skynet_api::sea_orm::{self, prelude::*};
+
+use crate::HyUuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_schedules")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: HyUuid,
+    pub name: String,
+    pub cmd: String,
+    pub target: String, // json encoded target set
+    pub option: String, // json encoded command option
+    pub cron: String,
+    pub timezone: String,
+    pub concurrency: i32,
+    pub alert: bool, // alert when command failed
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<HyUuid>, // last editor, commands are issued as this user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::schedule_runs::Entity")]
+    ScheduleRun,
+}
+
+impl Related<super::schedule_runs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ScheduleRun.def()
+    }
+}
+
+#[entity_id(HyUuid::new())]
+#[entity_timestamp]
+impl ActiveModel {}
+
+#[entity_behavior]
+impl ActiveModelBehavior for ActiveModel {}
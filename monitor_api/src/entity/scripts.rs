@@ -0,0 +1,31 @@
+use actix_cloud::chrono;
+use actix_cloud::macros::{entity_behavior, entity_id, entity_timestamp};
+use serde::{Deserialize, Serialize};
+use skynet_api::sea_orm::{self, prelude::*};
+
+use crate::HyUuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Default, Serialize, Deserialize)]
+#[sea_orm(table_name = "2eb2e1a5-66b4-45f9-ad24-3c4f05c858aa_scripts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: HyUuid,
+    pub name: String,
+    pub description: String,
+    pub interpreter: String,
+    pub body: String,
+    pub params: String, // json encoded parameter definitions
+    pub target: String, // json encoded allowed target set
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[entity_id(HyUuid::new())]
+#[entity_timestamp]
+impl ActiveModel {}
+
+#[entity_behavior]
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,9 @@
 use actix_cloud::{tokio::sync::mpsc::UnboundedSender, utils};
 use derivative::Derivative;
-use entity::agents;
+use entity::{
+    agents,
+    jobs::{self, JobStatus},
+};
 use enum_as_inner::EnumAsInner;
 use ffi_rpc::{
     self, abi_stable, async_trait,
@@ -34,9 +37,33 @@ pub trait Service {
     /// Run async command `cmd` in agent `id`. Return generated command id.
     async fn run_command(id: HyUuid, cmd: String) -> SResult<HyUuid>;
 
+    /// Run async command `cmd` in agent `id` with `option`. Return generated command id.
+    async fn run_command_with_option(
+        id: HyUuid,
+        cmd: String,
+        option: CommandOption,
+    ) -> SResult<HyUuid>;
+
+    /// Run command `cmd` in agent `id` with `option` and wait until finished.
+    /// The command is killed when not finished in `timeout` seconds, 0 to wait forever.
+    /// `option.queue_ttl` is ignored.
+    async fn run_command_wait(
+        id: HyUuid,
+        cmd: String,
+        option: CommandOption,
+        timeout: u32,
+    ) -> SResult<AgentCommand>;
+
     /// Get agent `id` command `cid` output.
     async fn get_command_output(id: HyUuid, cid: HyUuid) -> Option<AgentCommand>;
 
+    /// Get agent `id` command `cid` output starting from byte `offset`.
+    async fn read_command_output(
+        id: HyUuid,
+        cid: HyUuid,
+        offset: u64,
+    ) -> Option<AgentCommandOutput>;
+
     /// Kill async command `cid` in agent `id`.
     async fn kill_command(id: HyUuid, cid: HyUuid, force: bool) -> SResult<()>;
 
@@ -47,6 +74,16 @@ pub trait Service {
     async fn send_file(id: HyUuid, path: String, data: Vec<u8>) -> SResult<HyUuid>;
 
     async fn get_file_result(id: HyUuid, fid: HyUuid) -> Option<AgentFile>;
+
+    /// Send file to agent `id`, queued and delivered on next login when offline.
+    /// Queued file is expired after `ttl` seconds.
+    ///
+    /// Return file id when success.
+    async fn send_file_queued(id: HyUuid, path: String, data: Vec<u8>, ttl: u32)
+    -> SResult<HyUuid>;
+
+    /// Get agent `id` command or file `jid` status, including queued ones.
+    async fn get_job_status(id: HyUuid, jid: HyUuid) -> Option<JobStatus>;
 }
 
 #[derive(
@@ -65,6 +102,38 @@ pub enum AgentStatus {
 pub struct AgentCommand {
     pub code: Option<i32>,
     pub output: Vec<u8>,
+    pub timed_out: bool,
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(Default(new = "true"))]
+pub struct AgentCommandOutput {
+    pub code: Option<i32>,
+    pub output: Vec<u8>,
+    pub offset: u64, // offset for next read, unit bytes
+    pub timed_out: bool,
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(Default(new = "true"))]
+pub struct CommandOption {
+    /// Working directory, agent default when `None`.
+    pub cwd: Option<String>,
+    /// Extra environment variables.
+    pub env: HashMap<String, String>,
+    /// Kill after timeout, unit seconds, 0 for unlimited.
+    pub timeout: u32,
+    /// Run as user, agent user when `None`.
+    pub user: Option<String>,
+    pub stdin: Vec<u8>,
+    /// Max output size, unit bytes, 0 for unlimited.
+    pub output_limit: u64,
+    /// Queue when agent is offline and deliver on next login, expired after seconds.
+    /// 0 to fail immediately.
+    #[serde(default)]
+    pub queue_ttl: u32,
 }
 
 #[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
@@ -91,11 +160,15 @@ pub struct Agent {
     pub arch: Option<String>,
     pub last_login: i64,
     pub status: AgentStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
 
     #[serde(skip)]
     pub message: Option<UnboundedSender<Data>>,
+    #[deprecated(note = "always empty, use `MonitorService::get_command_output` instead")]
     #[serde(skip)]
     pub command: HashMap<HyUuid, Option<AgentCommand>>,
+    #[deprecated(note = "always empty, use `MonitorService::get_file_result` instead")]
     #[serde(skip)]
     pub file: HashMap<HyUuid, Option<AgentFile>>,
 
@@ -131,6 +204,42 @@ pub struct Agent {
     pub band_down: Option<u64>, // bandwidth download, unit bytes
 }
 
+impl AgentCommand {
+    /// Read output starting from byte `offset`.
+    ///
+    /// Returned offset never goes backwards, even if the output is shorter than `offset`.
+    pub fn read(&self, offset: u64) -> AgentCommandOutput {
+        let start = usize::try_from(offset).map_or(self.output.len(), |x| x.min(self.output.len()));
+        AgentCommandOutput {
+            code: self.code,
+            output: self.output[start..].to_vec(),
+            offset: offset.max(self.output.len() as u64),
+            timed_out: self.timed_out,
+            truncated: self.truncated,
+        }
+    }
+}
+
+impl From<jobs::Model> for AgentCommand {
+    fn from(v: jobs::Model) -> Self {
+        Self {
+            code: v.code,
+            output: v.output,
+            timed_out: v.timed_out,
+            truncated: v.truncated,
+        }
+    }
+}
+
+impl From<jobs::Model> for AgentFile {
+    fn from(v: jobs::Model) -> Self {
+        Self {
+            code: v.code.and_then(|x| x.try_into().ok()).unwrap_or_default(),
+            message: String::from_utf8_lossy(&v.output).into_owned(),
+        }
+    }
+}
+
 impl From<agents::Model> for Agent {
     fn from(v: agents::Model) -> Self {
         Self {
@@ -144,8 +253,6 @@ impl From<agents::Model> for Agent {
             arch: v.arch,
             last_login: v.last_login,
             message: None,
-            command: HashMap::new(),
-            file: HashMap::new(),
             endpoint: String::new(),
             ..Default::default()
         }
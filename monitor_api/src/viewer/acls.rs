@@ -0,0 +1,59 @@
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::acls;
+
+pub struct AclViewer;
+
+#[default_viewer(acls)]
+impl AclViewer {
+    /// Create acl `model`, `id` is ignored.
+    pub async fn create<C>(db: &C, model: acls::Model) -> Result<acls::Model>
+    where
+        C: ConnectionTrait,
+    {
+        acls::ActiveModel {
+            uid: Set(model.uid),
+            aid: Set(model.aid),
+            label: Set(model.label),
+            perm: Set(model.perm),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Update granted rights of acl `id`.
+    pub async fn update<C>(db: &C, id: &HyUuid, perm: i32) -> Result<acls::Model>
+    where
+        C: ConnectionTrait,
+    {
+        acls::ActiveModel {
+            id: Unchanged(*id),
+            perm: Set(perm),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn find_all<C>(db: &C) -> Result<Vec<acls::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        acls::Entity::find()
+            .all(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}
@@ -1,8 +1,11 @@
 use skynet_api::{
-    HyUuid, anyhow,
+    HyUuid, Result, anyhow,
     hyuuid::uuids2strings,
     request::Condition,
-    sea_orm::{self, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter},
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged,
+    },
 };
 use skynet_macro::default_viewer;
 
@@ -11,4 +14,61 @@ use crate::entity::agent_settings;
 pub struct AgentSettingViewer;
 
 #[default_viewer(agent_settings)]
-impl AgentSettingViewer {}
+impl AgentSettingViewer {
+    /// Get setting `name` of agent `aid`.
+    pub async fn get<C>(db: &C, aid: &HyUuid, name: &str) -> Result<Option<String>>
+    where
+        C: ConnectionTrait,
+    {
+        agent_settings::Entity::find()
+            .filter(agent_settings::Column::Aid.eq(*aid))
+            .filter(agent_settings::Column::Name.eq(name))
+            .one(db)
+            .await
+            .map(|x| x.map(|x| x.value))
+            .map_err(anyhow::Error::from)
+    }
+
+    /// Set setting `name` of agent `aid` to `value`, created when not exist.
+    pub async fn set<C>(db: &C, aid: &HyUuid, name: &str, value: &str) -> Result<()>
+    where
+        C: ConnectionTrait,
+    {
+        let old = agent_settings::Entity::find()
+            .filter(agent_settings::Column::Aid.eq(*aid))
+            .filter(agent_settings::Column::Name.eq(name))
+            .one(db)
+            .await?;
+        if let Some(old) = old {
+            agent_settings::ActiveModel {
+                id: Unchanged(old.id),
+                value: Set(value.to_owned()),
+                ..Default::default()
+            }
+            .update(db)
+            .await?;
+        } else {
+            agent_settings::ActiveModel {
+                aid: Set(*aid),
+                name: Set(name.to_owned()),
+                value: Set(value.to_owned()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Find setting `name` of all agents.
+    pub async fn find_by_name<C>(db: &C, name: &str) -> Result<Vec<agent_settings::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        agent_settings::Entity::find()
+            .filter(agent_settings::Column::Name.eq(name))
+            .all(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}
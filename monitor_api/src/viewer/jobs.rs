@@ -0,0 +1,208 @@
+use actix_cloud::chrono::Utc;
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, QueryOrder, Set, Unchanged, prelude::Expr,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::jobs::{self, JobKind, JobStatus};
+
+pub struct JobViewer;
+
+#[default_viewer(jobs)]
+impl JobViewer {
+    /// Create running job `id` sent to agent `aid` by user `uid`.
+    pub async fn create<C>(
+        db: &C,
+        id: &HyUuid,
+        aid: &HyUuid,
+        uid: Option<HyUuid>,
+        kind: JobKind,
+        cmd: &str,
+    ) -> Result<jobs::Model>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::ActiveModel {
+            id: Set(*id),
+            aid: Set(*aid),
+            uid: Set(uid),
+            kind: Set(kind),
+            status: Set(JobStatus::Running),
+            cmd: Set(cmd.to_owned()),
+            code: Set(None),
+            output: Set(Vec::new()),
+            timed_out: Set(false),
+            truncated: Set(false),
+            payload: Set(None),
+            expire_at: Set(None),
+            finished_at: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Finish job `id` with final `status` and result.
+    pub async fn finish<C>(
+        db: &C,
+        id: &HyUuid,
+        status: JobStatus,
+        code: Option<i32>,
+        output: Vec<u8>,
+        timed_out: bool,
+        truncated: bool,
+    ) -> Result<jobs::Model>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::ActiveModel {
+            id: Unchanged(*id),
+            status: Set(status),
+            code: Set(code),
+            output: Set(output),
+            timed_out: Set(timed_out),
+            truncated: Set(truncated),
+            finished_at: Set(Some(Utc::now().timestamp_millis())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Queue created job `id` until delivered, expired at `expire_at`.
+    pub async fn enqueue<C>(
+        db: &C,
+        id: &HyUuid,
+        payload: Vec<u8>,
+        expire_at: i64,
+    ) -> Result<jobs::Model>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::ActiveModel {
+            id: Unchanged(*id),
+            status: Set(JobStatus::Queued),
+            payload: Set(Some(payload)),
+            expire_at: Set(Some(expire_at)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Find queued jobs of agent `aid` not expired, oldest first.
+    pub async fn find_queued<C>(db: &C, aid: &HyUuid) -> Result<Vec<jobs::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::Entity::find()
+            .filter(jobs::Column::Aid.eq(*aid))
+            .filter(jobs::Column::Status.eq(JobStatus::Queued))
+            .filter(jobs::Column::ExpireAt.gt(Utc::now().timestamp_millis()))
+            .order_by_asc(jobs::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Mark queued job `id` as running and drop its payload.
+    ///
+    /// Return false when `id` is no longer queued.
+    pub async fn deliver<C>(db: &C, id: &HyUuid) -> Result<bool>
+    where
+        C: ConnectionTrait,
+    {
+        Self::leave_queue(db, id, JobStatus::Running).await
+    }
+
+    /// Cancel queued job `id`.
+    ///
+    /// Return false when `id` is no longer queued.
+    pub async fn cancel_queued<C>(db: &C, id: &HyUuid) -> Result<bool>
+    where
+        C: ConnectionTrait,
+    {
+        Self::leave_queue(db, id, JobStatus::Cancelled).await
+    }
+
+    async fn leave_queue<C>(db: &C, id: &HyUuid, status: JobStatus) -> Result<bool>
+    where
+        C: ConnectionTrait,
+    {
+        let mut query = jobs::Entity::update_many()
+            .col_expr(jobs::Column::Status, Expr::value(status))
+            .col_expr(jobs::Column::Payload, Expr::value(Option::<Vec<u8>>::None));
+        if status != JobStatus::Running {
+            query = query.col_expr(
+                jobs::Column::FinishedAt,
+                Expr::value(Utc::now().timestamp_millis()),
+            );
+        }
+        query
+            .filter(jobs::Column::Id.eq(*id))
+            .filter(jobs::Column::Status.eq(JobStatus::Queued))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected != 0)
+            .map_err(Into::into)
+    }
+
+    /// Mark queued jobs reaching expire time as expired.
+    pub async fn expire_queued<C>(db: &C) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        let now = Utc::now().timestamp_millis();
+        jobs::Entity::update_many()
+            .col_expr(jobs::Column::Status, Expr::value(JobStatus::Expired))
+            .col_expr(jobs::Column::Payload, Expr::value(Option::<Vec<u8>>::None))
+            .col_expr(jobs::Column::FinishedAt, Expr::value(now))
+            .filter(jobs::Column::Status.eq(JobStatus::Queued))
+            .filter(jobs::Column::ExpireAt.lte(now))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+
+    /// Mark all running jobs as disconnected, used when results are lost.
+    pub async fn abort_running<C>(db: &C) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::Entity::update_many()
+            .col_expr(jobs::Column::Status, Expr::value(JobStatus::Disconnected))
+            .col_expr(
+                jobs::Column::FinishedAt,
+                Expr::value(Utc::now().timestamp_millis()),
+            )
+            .filter(jobs::Column::Status.eq(JobStatus::Running))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+
+    /// Delete finished jobs created before `time`, queued jobs are kept.
+    pub async fn delete_before<C>(db: &C, time: i64) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        jobs::Entity::delete_many()
+            .filter(jobs::Column::Status.is_not_in([JobStatus::Running, JobStatus::Queued]))
+            .filter(jobs::Column::CreatedAt.lt(time))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+}
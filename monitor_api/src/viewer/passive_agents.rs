@@ -4,7 +4,7 @@ use skynet_api::{
     request::Condition,
     sea_orm::{
         self, ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, EntityTrait,
-        PaginatorTrait, QueryFilter, Set, Unchanged,
+        PaginatorTrait, QueryFilter, Set, Unchanged, prelude::Expr,
     },
 };
 use skynet_macro::default_viewer;
@@ -31,6 +31,10 @@ impl PassiveAgentViewer {
             name: Set(name.to_owned()),
             address: Set(address.to_owned()),
             retry_time: Set(retry_time),
+            retry_max_time: Set(0),
+            retry_multiplier: Set(1.0),
+            retry_jitter: Set(0.0),
+            retry_limit: Set(0),
             ..Default::default()
         }
         .insert(db)
@@ -60,6 +64,63 @@ impl PassiveAgentViewer {
         .map_err(Into::into)
     }
 
+    /// Update retry backoff policy.
+    pub async fn update_backoff<C>(
+        db: &C,
+        id: &HyUuid,
+        max_time: Option<i32>,
+        multiplier: Option<f64>,
+        jitter: Option<f64>,
+        limit: Option<i32>,
+    ) -> Result<passive_agents::Model>
+    where
+        C: ConnectionTrait,
+    {
+        passive_agents::ActiveModel {
+            id: Unchanged(id.to_owned()),
+            retry_max_time: max_time.map_or(NotSet, Set),
+            retry_multiplier: multiplier.map_or(NotSet, Set),
+            retry_jitter: jitter.map_or(NotSet, Set),
+            retry_limit: limit.map_or(NotSet, Set),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Link passive agent `id` to agent `aid`.
+    pub async fn link<C>(db: &C, id: &HyUuid, aid: Option<HyUuid>) -> Result<passive_agents::Model>
+    where
+        C: ConnectionTrait,
+    {
+        passive_agents::ActiveModel {
+            id: Unchanged(id.to_owned()),
+            aid: Set(aid),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Remove links to agents `aid`.
+    pub async fn unlink<C>(db: &C, aid: &[HyUuid]) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        passive_agents::Entity::update_many()
+            .col_expr(
+                passive_agents::Column::Aid,
+                Expr::value(Option::<HyUuid>::None),
+            )
+            .filter(passive_agents::Column::Aid.is_in(uuids2strings(aid)))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+
     pub async fn find_by_name<C>(db: &C, name: &str) -> Result<Option<passive_agents::Model>>
     where
         C: ConnectionTrait,
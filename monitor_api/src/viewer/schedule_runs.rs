@@ -0,0 +1,50 @@
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::schedule_runs;
+
+pub struct ScheduleRunViewer;
+
+#[default_viewer(schedule_runs)]
+impl ScheduleRunViewer {
+    /// Record agent result `model` of a schedule run.
+    pub async fn create<C>(db: &C, model: schedule_runs::Model) -> Result<schedule_runs::Model>
+    where
+        C: ConnectionTrait,
+    {
+        schedule_runs::ActiveModel {
+            sid: Set(model.sid),
+            rid: Set(model.rid),
+            aid: Set(model.aid),
+            cid: Set(model.cid),
+            status: Set(model.status),
+            code: Set(model.code),
+            message: Set(model.message),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Delete runs created before `time`.
+    pub async fn delete_before<C>(db: &C, time: i64) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        schedule_runs::Entity::delete_many()
+            .filter(schedule_runs::Column::CreatedAt.lt(time))
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+}
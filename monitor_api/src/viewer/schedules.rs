@@ -0,0 +1,107 @@
+use actix_cloud::chrono::Utc;
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::schedules;
+
+pub struct ScheduleViewer;
+
+#[default_viewer(schedules)]
+impl ScheduleViewer {
+    /// Create schedule `model`, `id` and `last_run_at` are ignored.
+    ///
+    /// This method will NOT add schedule to scheduler, please invoke `insert` AFTER commit.
+    pub async fn create<C>(db: &C, model: schedules::Model) -> Result<schedules::Model>
+    where
+        C: ConnectionTrait,
+    {
+        schedules::ActiveModel {
+            name: Set(model.name),
+            cmd: Set(model.cmd),
+            target: Set(model.target),
+            option: Set(model.option),
+            cron: Set(model.cron),
+            timezone: Set(model.timezone),
+            concurrency: Set(model.concurrency),
+            alert: Set(model.alert),
+            enabled: Set(model.enabled),
+            uid: Set(model.uid),
+            last_run_at: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Update all editable fields of schedule `model.id`.
+    ///
+    /// This method will NOT update scheduler, please invoke `insert` AFTER commit.
+    pub async fn update<C>(db: &C, model: schedules::Model) -> Result<schedules::Model>
+    where
+        C: ConnectionTrait,
+    {
+        schedules::ActiveModel {
+            id: Unchanged(model.id),
+            name: Set(model.name),
+            cmd: Set(model.cmd),
+            target: Set(model.target),
+            option: Set(model.option),
+            cron: Set(model.cron),
+            timezone: Set(model.timezone),
+            concurrency: Set(model.concurrency),
+            alert: Set(model.alert),
+            enabled: Set(model.enabled),
+            uid: Set(model.uid),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Set last run time of schedule `id` to now.
+    pub async fn touch<C>(db: &C, id: &HyUuid) -> Result<schedules::Model>
+    where
+        C: ConnectionTrait,
+    {
+        schedules::ActiveModel {
+            id: Unchanged(id.to_owned()),
+            last_run_at: Set(Some(Utc::now().timestamp_millis())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn find_by_name<C>(db: &C, name: &str) -> Result<Option<schedules::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        schedules::Entity::find()
+            .filter(schedules::Column::Name.eq(name))
+            .one(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+
+    pub async fn find_enabled<C>(db: &C) -> Result<Vec<schedules::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        schedules::Entity::find()
+            .filter(schedules::Column::Enabled.eq(true))
+            .all(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}
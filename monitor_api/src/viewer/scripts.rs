@@ -0,0 +1,67 @@
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::scripts;
+
+pub struct ScriptViewer;
+
+#[default_viewer(scripts)]
+impl ScriptViewer {
+    /// Create script `model`, `id` is ignored.
+    pub async fn create<C>(db: &C, model: scripts::Model) -> Result<scripts::Model>
+    where
+        C: ConnectionTrait,
+    {
+        scripts::ActiveModel {
+            name: Set(model.name),
+            description: Set(model.description),
+            interpreter: Set(model.interpreter),
+            body: Set(model.body),
+            params: Set(model.params),
+            target: Set(model.target),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Update all editable fields of script `model.id`.
+    pub async fn update<C>(db: &C, model: scripts::Model) -> Result<scripts::Model>
+    where
+        C: ConnectionTrait,
+    {
+        scripts::ActiveModel {
+            id: Unchanged(model.id),
+            name: Set(model.name),
+            description: Set(model.description),
+            interpreter: Set(model.interpreter),
+            body: Set(model.body),
+            params: Set(model.params),
+            target: Set(model.target),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn find_by_name<C>(db: &C, name: &str) -> Result<Option<scripts::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        scripts::Entity::find()
+            .filter(scripts::Column::Name.eq(name))
+            .one(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}
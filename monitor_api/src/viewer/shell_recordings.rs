@@ -0,0 +1,80 @@
+use actix_cloud::chrono::Utc;
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged, prelude::Expr,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::shell_recordings;
+
+pub struct ShellRecordingViewer;
+
+#[default_viewer(shell_recordings)]
+impl ShellRecordingViewer {
+    /// Create recording `model`.
+    pub async fn create<C>(
+        db: &C,
+        model: shell_recordings::Model,
+    ) -> Result<shell_recordings::Model>
+    where
+        C: ConnectionTrait,
+    {
+        shell_recordings::ActiveModel {
+            id: Set(model.id),
+            aid: Set(model.aid),
+            uid: Set(model.uid),
+            token: Set(model.token),
+            cmd: Set(model.cmd),
+            size: Set(0),
+            truncated: Set(false),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Mark recording `id` finished with file `size`.
+    pub async fn finish<C>(
+        db: &C,
+        id: &HyUuid,
+        size: i64,
+        truncated: bool,
+    ) -> Result<shell_recordings::Model>
+    where
+        C: ConnectionTrait,
+    {
+        shell_recordings::ActiveModel {
+            id: Unchanged(*id),
+            size: Set(size),
+            truncated: Set(truncated),
+            finished_at: Set(Some(Utc::now().timestamp_millis())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Mark all unfinished recordings as finished, used when recorders are lost.
+    pub async fn abort_running<C>(db: &C) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        shell_recordings::Entity::update_many()
+            .col_expr(
+                shell_recordings::Column::FinishedAt,
+                Expr::value(Utc::now().timestamp_millis()),
+            )
+            .filter(shell_recordings::Column::FinishedAt.is_null())
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+}
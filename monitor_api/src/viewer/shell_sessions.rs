@@ -0,0 +1,78 @@
+use actix_cloud::chrono::Utc;
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged, prelude::Expr,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::shell_sessions;
+
+pub struct ShellSessionViewer;
+
+#[default_viewer(shell_sessions)]
+impl ShellSessionViewer {
+    /// Create session `model`.
+    pub async fn create<C>(db: &C, model: shell_sessions::Model) -> Result<shell_sessions::Model>
+    where
+        C: ConnectionTrait,
+    {
+        shell_sessions::ActiveModel {
+            id: Set(model.id),
+            aid: Set(model.aid),
+            uid: Set(model.uid),
+            token: Set(model.token),
+            cmd: Set(model.cmd),
+            ip: Set(model.ip),
+            bytes_in: Set(0),
+            bytes_out: Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Mark session `id` finished with transferred bytes.
+    pub async fn finish<C>(
+        db: &C,
+        id: &HyUuid,
+        bytes_in: i64,
+        bytes_out: i64,
+    ) -> Result<shell_sessions::Model>
+    where
+        C: ConnectionTrait,
+    {
+        shell_sessions::ActiveModel {
+            id: Unchanged(*id),
+            bytes_in: Set(bytes_in),
+            bytes_out: Set(bytes_out),
+            finished_at: Set(Some(Utc::now().timestamp_millis())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Mark all unfinished sessions as finished, used when sessions are lost.
+    pub async fn abort_running<C>(db: &C) -> Result<u64>
+    where
+        C: ConnectionTrait,
+    {
+        shell_sessions::Entity::update_many()
+            .col_expr(
+                shell_sessions::Column::FinishedAt,
+                Expr::value(Utc::now().timestamp_millis()),
+            )
+            .filter(shell_sessions::Column::FinishedAt.is_null())
+            .exec(db)
+            .await
+            .map(|x| x.rows_affected)
+            .map_err(Into::into)
+    }
+}
@@ -0,0 +1,115 @@
+use skynet_api::{
+    HyUuid, Result, anyhow,
+    hyuuid::uuids2strings,
+    request::Condition,
+    sea_orm::{
+        self, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait,
+        QueryFilter, Set, Unchanged,
+    },
+};
+use skynet_macro::default_viewer;
+
+use crate::entity::socks_users;
+
+pub struct SocksUserViewer;
+
+#[default_viewer(socks_users)]
+impl SocksUserViewer {
+    /// Create proxy user `uid` named `username` with hashed `password` and `salt`.
+    pub async fn create<C>(
+        db: &C,
+        uid: &HyUuid,
+        username: &str,
+        password: &str,
+        salt: &str,
+        enable: bool,
+    ) -> Result<socks_users::Model>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::ActiveModel {
+            uid: Set(*uid),
+            username: Set(username.to_owned()),
+            password: Set(password.to_owned()),
+            salt: Set(salt.to_owned()),
+            enable: Set(enable),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn update<C>(
+        db: &C,
+        id: &HyUuid,
+        username: &str,
+        enable: bool,
+    ) -> Result<socks_users::Model>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::ActiveModel {
+            id: Unchanged(*id),
+            username: Set(username.to_owned()),
+            enable: Set(enable),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Set hashed `password` and `salt` of proxy user `id`.
+    pub async fn set_password<C>(
+        db: &C,
+        id: &HyUuid,
+        password: &str,
+        salt: &str,
+    ) -> Result<socks_users::Model>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::ActiveModel {
+            id: Unchanged(*id),
+            password: Set(password.to_owned()),
+            salt: Set(salt.to_owned()),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn find_by_uid<C>(db: &C, uid: &HyUuid) -> Result<Option<socks_users::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::Entity::find()
+            .filter(socks_users::Column::Uid.eq(*uid))
+            .one(db)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn find_by_username<C>(db: &C, username: &str) -> Result<Option<socks_users::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::Entity::find()
+            .filter(socks_users::Column::Username.eq(username))
+            .one(db)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn find_all<C>(db: &C) -> Result<Vec<socks_users::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        socks_users::Entity::find()
+            .all(db)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}